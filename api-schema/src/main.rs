@@ -1,35 +1,252 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use iam_core::{
+    codegen,
+    config::{Config, ConfigLayer},
+    connection,
+    database::IAM_SEED,
+    diagram::{self, Format},
+    introspect,
+    lint::{self, Severity, Source},
+    migration::MIGRATION_TYPES,
+    schema::{strip_keyword, Schema},
+    AppError, IAM_SCHEMA_FILE,
+};
 use typedb_driver::{
-    concept::{Transitivity, ValueType}, transaction::concept::api::{EntityTypeAPI, ThingTypeAPI}, Connection, DatabaseManager, Error, Promise, Session, SessionType, TransactionType
+    concept::{Transitivity, ValueType},
+    transaction::concept::api::{EntityTypeAPI, ThingTypeAPI},
+    Connection, DatabaseManager, Promise, Session, SessionType, TransactionType,
 };
 
-fn main() -> Result<(), Error> {
-    //const DB_NAME: &str = "schema-api-db";
-    //const SERVER_ADDR: &str = "127.0.0.1:1729";
-    let driver = Connection::new_core("127.0.0.1:1729")?;
+const DB_NAME: &str = "schema-api-db";
+
+/// Schema tools for a TypeDB database. The database name and server are configured with `API_SCHEMA_*`
+/// environment variables.
+#[derive(Debug, Parser)]
+#[command(name = "api-schema")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Define a `tag` attribute type and let every concrete entity type own it (the default)
+    Tag,
+    /// Compare the live schema with a schema file and exit with status 1 if they differ
+    Drift {
+        /// Schema file to compare with [default: the bundled IAM schema and migrations]
+        #[arg(long, value_name = "PATH")]
+        schema: Option<PathBuf>,
+    },
+    /// Print a class diagram of the schema file, or of the live schema with --live
+    Diagram {
+        /// Diagram language
+        #[arg(long, value_parser = ["dot", "mermaid"], default_value = "dot")]
+        format: String,
+        /// Schema file to draw [default: the bundled IAM schema and migrations]
+        #[arg(long, value_name = "PATH", conflicts_with = "live")]
+        schema: Option<PathBuf>,
+        /// Draw the schema of the database instead of a file
+        #[arg(long)]
+        live: bool,
+        /// Only draw this type, its subtypes and the types they own or play roles in
+        #[arg(long, value_name = "TYPE")]
+        focus: Option<String>,
+    },
+    /// Print the live schema as a `define` query in a stable, sorted layout, such as that of iam-schema.tql
+    Export {
+        /// Write to this file instead of standard output
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print Rust types for the concrete entity and relation types of a schema file, as build.rs generates them
+    /// for iam-core
+    Codegen {
+        /// Schema file to generate from [default: the bundled IAM schema and migrations]
+        #[arg(long, value_name = "PATH")]
+        schema: Option<PathBuf>,
+        /// Path of the models module that the generated code uses
+        #[arg(long, value_name = "PATH", default_value = "iam_core::models")]
+        models: String,
+        /// Write to this file instead of standard output
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Check .tql files without a server and exit with status 1 on errors. Files holding a `define` query make up
+    /// the schema that the other files are checked against
+    Lint {
+        #[arg(required = true, value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+}
+
+fn config() -> Result<Config, AppError> {
+    Ok(ConfigLayer { db_name: Some(DB_NAME.to_owned()), ..ConfigLayer::defaults() }
+        .merge(ConfigLayer::from_env("API_SCHEMA_", |key| std::env::var(key).ok()))
+        .validate()?)
+}
+
+fn tag(driver: Connection, db_name: &str) -> Result<(), AppError> {
     let databases = DatabaseManager::new(driver);
-/*     if databases.contains(DB_NAME)? {
-        databases.get(DB_NAME)?.delete()?;
-    }
-    databases.create(DB_NAME)?; */
-    
-    Ok({
-        let session = Session::new(databases.get("schema-api-db")?, SessionType::Schema)?;
-        let tx = session.transaction(TransactionType::Write)?;
-        //let user = tx.concept().put_entity_type("user".to_owned()).resolve()?;
-        //let mut admin = tx.concept().put_entity_type("admin".to_owned()).resolve()?;
-        //admin.set_supertype(&tx, user).resolve()?;
-        let tag = tx.concept().put_attribute_type("tag".to_owned(),ValueType::String).resolve()?;
-        let entities = tx.concept().get_entity_type("entity".to_owned()).resolve().unwrap().unwrap().get_subtypes(&tx, Transitivity::Explicit).unwrap();
-        for res in entities {
-            //println!("{}", entity.unwrap().label)
-            let mut entity = res.unwrap();
-            if !{entity.is_abstract()} {
-                _ = entity.set_owns(&tx, tag.clone(),None,vec![]).resolve();
+    let session = Session::new(databases.get(db_name)?, SessionType::Schema)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    let tag = tx.concept().put_attribute_type("tag".to_owned(), ValueType::String).resolve()?;
+    let entity = tx.concept().get_entity_type("entity".to_owned()).resolve()?;
+    let entity = entity.ok_or_else(|| AppError::NotFound("root entity type".to_owned()))?;
+    for subtype in entity.get_subtypes(&tx, Transitivity::Explicit)? {
+        let mut subtype = subtype?;
+        if !subtype.is_abstract() {
+            subtype.set_owns(&tx, tag.clone(), None, vec![]).resolve()?;
+        }
+    }
+    tx.commit().resolve()?;
+    Ok(())
+}
+
+/// Reads a schema file, or the bundled IAM schema with the rules of its later migrations as a database set up by
+/// the sample app has it, and returns it with the name of the file.
+fn schema_file(path: Option<PathBuf>) -> Result<(Schema, String), AppError> {
+    match path {
+        Some(path) => {
+            let file = path.display().to_string();
+            Ok((Schema::from_file(&path).map_err(|error| AppError::schema_load(file.clone(), error))?, file))
+        }
+        None => Ok((IAM_SEED.parse_schema()?, IAM_SCHEMA_FILE.to_owned())),
+    }
+}
+
+/// Prints the changes from the schema file to the live schema, ignoring the types that record migrations, and
+/// returns whether there were any.
+fn drift(driver: &Connection, db_name: &str, path: Option<PathBuf>) -> Result<bool, AppError> {
+    let (expected, file) = schema_file(path)?;
+    let changes = expected.diff(&introspect::live_schema(driver, db_name)?.without(MIGRATION_TYPES));
+    if changes.is_empty() {
+        println!("The schema of database {} matches {}.", db_name, file);
+    } else {
+        println!(
+            "The schema of database {} differs from {} (+ only in the database, - only in the file):",
+            db_name, file
+        );
+        for change in &changes {
+            println!("{}", change);
+        }
+    }
+    Ok(!changes.is_empty())
+}
+
+/// Writes the live schema without the types that record migrations.
+fn export(driver: &Connection, db_name: &str, output: Option<PathBuf>) -> Result<(), AppError> {
+    let typeql = introspect::live_schema(driver, db_name)?.without(MIGRATION_TYPES).to_typeql();
+    match output {
+        Some(path) => fs::write(path, typeql)?,
+        None => print!("{}", typeql),
+    }
+    Ok(())
+}
+
+fn diagram(schema: &Schema, format: &str, focus: Option<&str>) -> Result<(), AppError> {
+    let format = if format == "mermaid" { Format::Mermaid } else { Format::Dot };
+    print!("{}", diagram::render(schema, format, focus)?);
+    Ok(())
+}
+
+/// Prints the diagnostics for the files and returns whether any of them is an error.
+fn lint(files: &[PathBuf]) -> Result<bool, AppError> {
+    let texts = files
+        .iter()
+        .map(|path| Ok((path.display().to_string(), fs::read_to_string(path)?)))
+        .collect::<Result<Vec<_>, AppError>>()?;
+    let (schema_files, data_files): (Vec<_>, Vec<_>) = texts
+        .iter()
+        .map(|(file, text)| Source { file, text })
+        .partition(|source| strip_keyword(source.text, "define").is_some());
+    let diagnostics = lint::lint(&schema_files, &data_files);
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    Ok(diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error))
+}
+
+fn run(command: Command) -> Result<ExitCode, AppError> {
+    let connect = || -> Result<(Connection, String), AppError> {
+        let config = config()?;
+        Ok((connection::connect(&config)?, config.db_name))
+    };
+    match command {
+        Command::Lint { files } => Ok(if lint(&files)? { ExitCode::FAILURE } else { ExitCode::SUCCESS }),
+        Command::Codegen { schema, models, output } => {
+            let code = codegen::rust_types(&schema_file(schema)?.0, &models);
+            match output {
+                Some(path) => fs::write(path, code)?,
+                None => print!("{}", code),
             }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Diagram { format, schema, live: false, focus } => {
+            diagram(&schema_file(schema)?.0, &format, focus.as_deref()).map(|()| ExitCode::SUCCESS)
+        }
+        Command::Diagram { format, focus, .. } => {
+            let (driver, db_name) = connect()?;
+            let schema = introspect::live_schema(&driver, &db_name)?.without(MIGRATION_TYPES);
+            diagram(&schema, &format, focus.as_deref()).map(|()| ExitCode::SUCCESS)
+        }
+        Command::Tag => {
+            let (driver, db_name) = connect()?;
+            tag(driver, &db_name).map(|()| ExitCode::SUCCESS)
+        }
+        Command::Drift { schema } => {
+            let (driver, db_name) = connect()?;
+            let drifted = drift(&driver, &db_name, schema)?;
+            Ok(if drifted { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+        }
+        Command::Export { output } => {
+            let (driver, db_name) = connect()?;
+            export(&driver, &db_name, output).map(|()| ExitCode::SUCCESS)
         }
-        tx.commit().resolve()?;
-    })
-    
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command.unwrap_or(Command::Tag)) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
 }
 
-//let entities = tx.concept().get_entity_type("entity").resolve()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The IAM schema as the files on disk define it: the schema file, then every migration in order, each
+    /// replacing the types and rules it defines again.
+    fn schema_on_disk() -> Schema {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../iam-core");
+        let mut schema = Schema::from_file(dir.join(IAM_SCHEMA_FILE)).unwrap();
+        let migrations = fs::read_dir(dir.join("migrations")).unwrap().map(|entry| entry.unwrap().path());
+        let mut migrations = migrations.filter(|path| path.extension() == Some("tql".as_ref())).collect::<Vec<_>>();
+        migrations.sort();
+        assert!(migrations.len() > 1, "{:?}", migrations);
+        for path in migrations {
+            let migration = Schema::from_file(&path).unwrap();
+            migration.types().cloned().for_each(|type_def| schema.add_type(type_def));
+            migration.rules().iter().cloned().for_each(|rule| schema.add_rule(rule));
+        }
+        schema
+    }
+
+    #[test]
+    fn the_default_schema_is_that_of_the_seed() {
+        let (schema, file) = schema_file(None).unwrap();
+        assert_eq!(file, IAM_SCHEMA_FILE);
+        let changes = schema.diff(&schema_on_disk());
+        assert!(changes.is_empty(), "{:?}", changes);
+        let rules = schema.rules().iter().map(|rule| rule.label.name.as_str()).collect::<Vec<_>>();
+        assert!(rules.contains(&"inherit-group-permission"), "{:?}", rules);
+    }
+}
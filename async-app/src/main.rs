@@ -1,42 +1,40 @@
-use typedb_driver::{
-    concept::{Attribute, Concept, Value},
-    Connection, DatabaseManager, Error, Options, Promise, Session, SessionType, TransactionType
+use iam_core::{
+    asynchronous,
+    config::{Config, ConfigLayer},
+    database::Seed,
+    AppError,
 };
-use serde_json::json;
+use tokio_stream::StreamExt;
 
-fn main() {
+const DB_NAME: &str = "access-management-db";
 
-test();
+const SEED: Seed<'static> = Seed {
+    schema_file: "schema",
+    schema: "define subject sub entity;",
+    data_file: "data",
+    data: "insert $s isa subject, has name 'Bob';",
+    definitions: &[("schema", "define subject owns name; name sub attribute, value string;")],
+};
 
+fn config() -> Result<Config, AppError> {
+    let layer = ConfigLayer { db_name: Some(DB_NAME.to_owned()), ..ConfigLayer::default() };
+    let env = ConfigLayer::from_env("ASYNC_APP_", |key| std::env::var(key).ok());
+    Ok(ConfigLayer::defaults().merge(layer).merge(env).validate()?)
 }
 
-async fn test() {
-    let connection = Connection::new_core("localhost:1729");
-    let databases = DatabaseManager::new(connection.unwrap());
-
-    databases.create("access-management-db").await;
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    let config = config()?;
+    let db_name = config.db_name.clone();
+    let policy = config.bootstrap;
+    let driver = asynchronous::connect(config).await?;
 
-    let session = Session::new(databases.get("access-management-db").await.unwrap(), SessionType::Schema).await.unwrap();
-    let tx = session.transaction(TransactionType::Write).await.unwrap();
-    tx.query().define("define subject sub entity;").await;
-    tx.query().define("define subject owns name; name sub attribute, value string;").await;
-    tx.commit().await;
-    drop(session);
+    let outcome = asynchronous::bootstrap(driver.clone(), db_name.clone(), policy, SEED).await?;
+    println!("Database {}: {:?}", db_name, outcome);
 
-    let session = Session::new(databases.get("access-management-db").await.unwrap(), SessionType::Data).await.unwrap();
-    let tx = session.transaction(TransactionType::Write).await.unwrap();
-    tx.query().insert("insert $s isa subject, has name 'Bob';");
-    tx.commit().await;
-    
-    let tx = session.transaction(TransactionType::Read).await.unwrap();
-    let stream = tx.query().fetch("match $s isa subject; fetch $s: name;").unwrap();
-    while let Some(item) = stream.next().await {
-        println!(
-            "Name: {}",
-            serde_json::to_string_pretty(&item.unwrap()).unwrap()            
-        );
+    let mut answers = asynchronous::fetch(driver, db_name, "match $s isa subject; fetch $s: name;".to_owned());
+    while let Some(answer) = answers.next().await {
+        println!("Name: {}", answer?);
     }
-    drop(session);
-    return();
-
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,20 @@
+//! Generates the Rust types of `iam-schema.tql` into `$OUT_DIR/schema_types.rs`, which `src/schema_types.rs`
+//! includes. The schema model and the generator are the crate's own modules, compiled into the build script.
+use std::{env, fs, path::Path};
+
+#[allow(dead_code)]
+#[path = "src/codegen.rs"]
+mod codegen;
+#[allow(dead_code)]
+#[path = "src/schema.rs"]
+mod schema;
+
+fn main() {
+    for path in ["iam-schema.tql", "src/codegen.rs", "src/schema.rs"] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    let schema = schema::Schema::from_file("iam-schema.tql").unwrap_or_else(|error| panic!("{}", error));
+    let out_dir = env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo");
+    fs::write(Path::new(&out_dir).join("schema_types.rs"), codegen::rust_types(&schema, "crate::models"))
+        .expect("failed to write schema_types.rs");
+}
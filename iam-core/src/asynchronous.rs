@@ -0,0 +1,163 @@
+// tag::async[]
+//! Asynchronous access for tokio services. The driver is built with its blocking API, so every operation runs on
+//! tokio's blocking thread pool, and fetch answers are forwarded through a channel as they arrive.
+use std::{panic, sync::Arc};
+
+use tokio::{sync::mpsc, task};
+use tokio_stream::wrappers::ReceiverStream;
+use typedb_driver::{answer::JSON, Connection, DatabaseManager, Session, SessionType, TransactionType};
+
+use crate::{
+    config::{Bootstrap, Config},
+    connection,
+    database::{self, Bootstrapped, Seed},
+    decision::{AccessRequest, Decision},
+    error::AppError,
+    explanation::ExplainedFile,
+    models::Person,
+    store::{IamStore, ViewableFile},
+};
+
+/// The number of fetch answers buffered ahead of the consumer of a [`fetch`] stream.
+const FETCH_BUFFER: usize = 64;
+
+/// Runs blocking work on the blocking thread pool, resuming its panic if it panics.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    task::spawn_blocking(work).await.unwrap_or_else(|error| panic::resume_unwind(error.into_panic()))
+}
+
+pub async fn connect(config: Config) -> Result<Connection, AppError> {
+    blocking(move || connection::connect(&config)).await
+}
+
+pub async fn exists(driver: Connection, db_name: String) -> Result<bool, AppError> {
+    blocking(move || database::exists(&driver, &db_name)).await
+}
+
+pub async fn create(driver: Connection, db_name: String) -> Result<(), AppError> {
+    blocking(move || database::create(&driver, &db_name)).await
+}
+
+pub async fn delete(driver: Connection, db_name: String) -> Result<(), AppError> {
+    blocking(move || database::delete(&driver, &db_name)).await
+}
+
+/// See [`database::bootstrap`].
+pub async fn bootstrap(
+    driver: Connection,
+    db_name: String,
+    policy: Bootstrap,
+    seed: Seed<'static>,
+) -> Result<Bootstrapped, AppError> {
+    blocking(move || database::bootstrap(&driver, &db_name, policy, &seed)).await
+}
+
+/// See [`database::define_schema`].
+pub async fn define_schema(driver: Connection, db_name: String, file: String, schema: String) -> Result<(), AppError> {
+    blocking(move || database::define_schema(&driver, &db_name, &file, &schema)).await
+}
+
+/// See [`database::load_data`].
+pub async fn load_data(driver: Connection, db_name: String, file: String, data: String) -> Result<usize, AppError> {
+    blocking(move || database::load_data(&driver, &db_name, &file, &data)).await
+}
+
+/// Runs a fetch query in a read transaction and streams its answers. The transaction stays open until the last
+/// answer has been sent or the stream is dropped.
+pub fn fetch(driver: Connection, db_name: String, query: String) -> ReceiverStream<Result<JSON, AppError>> {
+    let (sender, receiver) = mpsc::channel(FETCH_BUFFER);
+    task::spawn_blocking(move || {
+        let answers = || -> Result<(), AppError> {
+            let databases = DatabaseManager::new(driver);
+            let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
+            let tx = session.transaction(TransactionType::Read)?;
+            for answer in tx.query().fetch(&query)? {
+                if sender.blocking_send(answer.map_err(AppError::from)).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        };
+        if let Err(error) = answers() {
+            let _ = sender.blocking_send(Err(error));
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+/// The operations of an [`IamStore`] as async functions, for sharing one store between tasks.
+#[derive(Clone)]
+pub struct AsyncIamStore {
+    store: Arc<dyn IamStore + Send + Sync>,
+}
+
+impl AsyncIamStore {
+    pub fn new(store: impl IamStore + Send + Sync + 'static) -> Self {
+        Self { store: Arc::new(store) }
+    }
+
+    pub async fn users(&self) -> Result<Vec<Person>, AppError> {
+        let store = self.store.clone();
+        blocking(move || store.users()).await
+    }
+
+    pub async fn insert_user(&self, full_name: &str, email: &str) -> Result<Vec<Person>, AppError> {
+        let (store, full_name, email) = (self.store.clone(), full_name.to_owned(), email.to_owned());
+        blocking(move || store.insert_user(&full_name, &email)).await
+    }
+
+    pub async fn files_by_user(&self, full_name: &str, inference: bool) -> Result<Vec<ViewableFile>, AppError> {
+        let (store, full_name) = (self.store.clone(), full_name.to_owned());
+        blocking(move || store.files_by_user(&full_name, inference)).await
+    }
+
+    pub async fn groups(&self, subject: &str, inference: bool) -> Result<Vec<String>, AppError> {
+        let (store, subject) = (self.store.clone(), subject.to_owned());
+        blocking(move || store.groups(&subject, inference)).await
+    }
+
+    pub async fn explain_files_by_user(&self, full_name: &str) -> Result<Vec<ExplainedFile>, AppError> {
+        let (store, full_name) = (self.store.clone(), full_name.to_owned());
+        blocking(move || store.explain_files_by_user(&full_name)).await
+    }
+
+    pub async fn decide(&self, request: &AccessRequest, inference: bool) -> Result<Decision, AppError> {
+        let (store, request) = (self.store.clone(), request.clone());
+        blocking(move || store.decide(&request, inference)).await
+    }
+
+    pub async fn update_filepath(&self, old_path: &str, new_path: &str) -> Result<usize, AppError> {
+        let (store, old_path, new_path) = (self.store.clone(), old_path.to_owned(), new_path.to_owned());
+        blocking(move || store.update_filepath(&old_path, &new_path)).await
+    }
+
+    pub async fn delete_file(&self, path: &str) -> Result<(), AppError> {
+        let (store, path) = (self.store.clone(), path.to_owned());
+        blocking(move || store.delete_file(&path)).await
+    }
+}
+// end::async[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_operations_run_concurrently() {
+        let store = AsyncIamStore::new(MemoryStore::iam().unwrap());
+        let lookups = ["Kevin Morrison", "Pearle Goodman", "Masako Holley"].map(|name| {
+            let store = store.clone();
+            tokio::spawn(async move { store.files_by_user(name, true).await.map(|files| files.len()) })
+        });
+        let mut counts = Vec::new();
+        for lookup in lookups {
+            counts.push(lookup.await.unwrap().unwrap());
+        }
+        assert_eq!(counts[..2], [10, 5]);
+        assert_eq!(store.users().await.unwrap().len(), 3);
+        assert!(matches!(store.delete_file("missing").await, Err(AppError::NotFound(_))));
+    }
+}
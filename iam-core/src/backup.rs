@@ -0,0 +1,802 @@
+// tag::backup[]
+//! Backups of a whole database: its schema and every entity and relation, with their attribute values and role
+//! players. Attributes are kept as the values of their owners, as in the [in-memory graph](crate::graph).
+//!
+//! Things are given IDs such as `person-1`, numbered per type in the order of their attribute values and role
+//! players, so two backups of the same data are the same however the things were inserted. Relations come after
+//! their role players.
+//!
+//! A backup is written either as JSON Lines, the schema on the first line and one thing on each of the others:
+//!
+//! ```json
+//! {"schema":"define\n\n..."}
+//! {"attributes":{"email":["kevin.morrison@typedb.com"],"full-name":["Kevin Morrison"]},"id":"person-1","type":"person"}
+//! {"id":"access-1","players":{"action":["operation-1"],"object":["file-1"]},"type":"access"}
+//! ```
+//!
+//! or as TypeQL, the schema's `define` query followed by an `insert` query with a statement for each thing, which
+//! the [bulk loader](crate::loader) replays.
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Map, Value as JSON};
+use typedb_driver::{
+    answer::ConceptMap,
+    concept::{Concept, Value},
+    Connection, Promise, SessionType, TransactionType,
+};
+use typeql::{pattern::Constant, variable::TypeReference};
+
+use crate::{
+    database,
+    error::AppError,
+    graph::Graph,
+    loader::{self, LoadOptions, Progress, Statement},
+    query::{Literal, QueryError},
+    schema::{Schema, ValueType},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackupFormat {
+    Jsonl,
+    TypeQL,
+}
+
+impl BackupFormat {
+    /// The format of a file with the extension `.jsonl` or `.tql`.
+    pub fn from_extension(path: &Path) -> Result<Self, BackupError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") => Ok(BackupFormat::Jsonl),
+            Some("tql") => Ok(BackupFormat::TypeQL),
+            _ => Err(BackupError::Format(path.display().to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BackupError {
+    /// The file name says neither JSON Lines nor TypeQL.
+    Format(String),
+    Malformed {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Format(file) => write!(f, "Unknown backup format of {}: expected .jsonl or .tql", file),
+            BackupError::Malformed { file, line, message } => {
+                write!(f, "Malformed backup {}, line {}: {}", file, line, message)
+            }
+        }
+    }
+}
+
+impl Error for BackupError {}
+
+/// An entity, relation or attribute of a backup. Attributes only have records of their own when no entity or
+/// relation owns them, when they play roles, or when they own attributes themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub id: String,
+    pub type_: String,
+    /// The value of an attribute.
+    pub value: Option<Literal>,
+    /// Attribute values, sorted by attribute type and value.
+    pub attributes: Vec<(String, Literal)>,
+    /// The IDs of the role players by role, in the order they were numbered.
+    pub players: Vec<(String, String)>,
+}
+
+impl Record {
+    /// The insert statement of the thing, binding it to a variable named after its ID, failing for strings that
+    /// TypeQL cannot represent.
+    pub fn typeql(&self) -> Result<String, QueryError> {
+        let mut text = format!("${}", self.id);
+        if let Some(value) = &self.value {
+            text.push_str(&format!(" {}", value.to_typeql()?));
+        }
+        if !self.players.is_empty() {
+            let players = self.players.iter().map(|(role, id)| format!("{}: ${}", role, id)).collect::<Vec<_>>();
+            text.push_str(&format!(" ({})", players.join(", ")));
+        }
+        text.push_str(&format!(" isa {}", self.type_));
+        for (attribute, value) in &self.attributes {
+            text.push_str(&format!(", has {} {}", attribute, value.to_typeql()?));
+        }
+        Ok(text)
+    }
+
+    fn to_json(&self) -> JSON {
+        let mut object = Map::new();
+        object.insert("id".to_owned(), json!(self.id));
+        object.insert("type".to_owned(), json!(self.type_));
+        if let Some(value) = &self.value {
+            object.insert("value".to_owned(), literal_json(value));
+        }
+        if !self.attributes.is_empty() {
+            let mut attributes = Map::new();
+            for (attribute, value) in &self.attributes {
+                let values = attributes.entry(attribute.clone()).or_insert_with(|| json!([]));
+                values.as_array_mut().unwrap().push(literal_json(value));
+            }
+            object.insert("attributes".to_owned(), JSON::Object(attributes));
+        }
+        if !self.players.is_empty() {
+            let mut players = Map::new();
+            for (role, id) in &self.players {
+                players.entry(role.clone()).or_insert_with(|| json!([])).as_array_mut().unwrap().push(json!(id));
+            }
+            object.insert("players".to_owned(), JSON::Object(players));
+        }
+        JSON::Object(object)
+    }
+
+    fn from_json(schema: &Schema, json: &JSON) -> Result<Self, String> {
+        let string = |key: &str| json.get(key).and_then(JSON::as_str).map(str::to_owned);
+        let id = string("id").ok_or("no id")?;
+        let type_ = string("type").ok_or("no type")?;
+        let value = match json.get("value") {
+            Some(value) => {
+                let value_type = schema.value_type(&type_).ok_or_else(|| format!("{} is not an attribute", type_))?;
+                let literal = json_literal(value_type, value)
+                    .ok_or_else(|| format!("{} is not a {:?} value for {}", value, value_type, type_))?;
+                Some(literal)
+            }
+            None => None,
+        };
+        let mut record = Record { id, type_, value, attributes: Vec::new(), players: Vec::new() };
+        for (attribute, values) in json.get("attributes").and_then(JSON::as_object).into_iter().flatten() {
+            let value_type = schema.value_type(attribute).ok_or_else(|| format!("unknown attribute {}", attribute))?;
+            for value in values.as_array().ok_or_else(|| format!("{} is not an array", attribute))? {
+                let literal = json_literal(value_type, value)
+                    .ok_or_else(|| format!("{} is not a {:?} value for {}", value, value_type, attribute))?;
+                record.attributes.push((attribute.clone(), literal));
+            }
+        }
+        for (role, ids) in json.get("players").and_then(JSON::as_object).into_iter().flatten() {
+            for id in ids.as_array().ok_or_else(|| format!("{} is not an array", role))? {
+                let id = id.as_str().ok_or_else(|| format!("player {} of {} is not an ID", id, role))?;
+                record.players.push((role.clone(), id.to_owned()));
+            }
+        }
+        Ok(record)
+    }
+}
+
+fn literal_json(literal: &Literal) -> JSON {
+    match literal {
+        Literal::String(value) => json!(value),
+        Literal::Long(value) => json!(value),
+        Literal::Double(value) => json!(value),
+        Literal::Boolean(value) => json!(value),
+        Literal::DateTime(value) => json!(value.format("%FT%T%.3f").to_string()),
+    }
+}
+
+fn json_literal(value_type: ValueType, json: &JSON) -> Option<Literal> {
+    Some(match value_type {
+        ValueType::String => Literal::String(json.as_str()?.to_owned()),
+        ValueType::Long => Literal::Long(json.as_i64()?),
+        ValueType::Double => Literal::Double(json.as_f64()?),
+        ValueType::Boolean => Literal::Boolean(json.as_bool()?),
+        ValueType::DateTime => Literal::DateTime(NaiveDateTime::parse_from_str(json.as_str()?, "%FT%T%.f").ok()?),
+    })
+}
+
+/// A thing as read from its store, with players referred to by the store's own IDs.
+struct Found<K> {
+    key: K,
+    type_: String,
+    value: Option<Literal>,
+    attributes: Vec<(String, Literal)>,
+    players: Vec<(String, K)>,
+}
+
+/// Numbers the things per type, round by round: each round takes the things whose players are numbered already
+/// and orders them by type, value, attribute values and players. Things left in a cycle of relations are numbered last,
+/// in the order of their keys.
+fn number<K: Ord + Clone>(found: Vec<Found<K>>) -> Vec<Record> {
+    // The ID of each thing numbered so far, with its position in the numbering for ordering the relations.
+    let mut ids = BTreeMap::<K, (String, usize)>::new();
+    let mut counts = BTreeMap::<String, usize>::new();
+    let mut numbered = Vec::new();
+    let mut pending = found;
+    for found in &mut pending {
+        found.attributes.sort_by_cached_key(|(attribute, value)| (attribute.clone(), value.to_string()));
+    }
+    while !pending.is_empty() {
+        let (mut ready, rest): (Vec<_>, Vec<_>) =
+            pending.into_iter().partition(|found| found.players.iter().all(|(_, player)| ids.contains_key(player)));
+        if ready.is_empty() {
+            ready = rest;
+            ready.sort_by(|a, b| a.key.cmp(&b.key));
+            pending = Vec::new();
+        } else {
+            ready.sort_by_cached_key(|found| {
+                let attributes =
+                    found.attributes.iter().map(|(attribute, value)| (attribute.clone(), value.to_string()));
+                let mut players =
+                    found.players.iter().map(|(role, player)| (role.clone(), ids[player].1)).collect::<Vec<_>>();
+                players.sort();
+                let value = found.value.as_ref().map(ToString::to_string);
+                (found.type_.clone(), value, attributes.collect::<Vec<_>>(), players)
+            });
+            pending = rest;
+        }
+        for found in &ready {
+            let count = counts.entry(found.type_.clone()).or_default();
+            *count += 1;
+            ids.insert(found.key.clone(), (format!("{}-{}", found.type_, count), ids.len()));
+        }
+        numbered.extend(ready);
+    }
+    numbered
+        .into_iter()
+        .map(|found| {
+            let mut players = found.players.into_iter().map(|(role, player)| (role, &ids[&player])).collect::<Vec<_>>();
+            players.sort_by_key(|(role, (_, position))| (role.clone(), *position));
+            let players = players.into_iter().map(|(role, (id, _))| (role, id.clone())).collect();
+            let id = ids[&found.key].0.clone();
+            Record { id, type_: found.type_, value: found.value, attributes: found.attributes, players }
+        })
+        .collect()
+}
+
+/// The schema and data of a database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    /// The schema as a `define` query.
+    pub schema: String,
+    pub records: Vec<Record>,
+}
+
+impl Backup {
+    /// A backup of the things of an in-memory graph.
+    pub fn from_graph(graph: &Graph) -> Self {
+        let found = graph
+            .instances("thing")
+            .map(|(id, thing)| Found {
+                key: id,
+                type_: thing.type_.clone(),
+                value: None,
+                attributes: thing
+                    .attributes
+                    .iter()
+                    .map(|(attribute, value)| (attribute.clone(), constant_literal(value)))
+                    .collect(),
+                players: thing.role_players.clone(),
+            })
+            .collect();
+        Backup { schema: graph.schema().to_typeql(), records: number(found) }
+    }
+
+    /// The number of things of each type.
+    pub fn counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for record in &self.records {
+            *counts.entry(record.type_.as_str()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The insert statements of the things, players first.
+    pub fn statements(&self) -> impl Iterator<Item = Result<Statement, QueryError>> + '_ {
+        self.records.iter().enumerate().map(|(index, record)| Ok(Statement::new(record.typeql()?, index + 2)))
+    }
+
+    pub fn write(&self, format: BackupFormat, writer: &mut impl Write) -> io::Result<()> {
+        match format {
+            BackupFormat::Jsonl => {
+                writeln!(writer, "{}", json!({ "schema": self.schema }))?;
+                for record in &self.records {
+                    writeln!(writer, "{}", record.to_json())?;
+                }
+            }
+            BackupFormat::TypeQL => {
+                writeln!(writer, "{}\n", self.schema.trim_end())?;
+                writeln!(writer, "insert")?;
+                for record in &self.records {
+                    let typeql = record.typeql().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+                    writeln!(writer, "{};", typeql)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a JSON Lines backup, the contents of the file called `file`.
+    pub fn read_jsonl(file: &str, text: &str) -> Result<Self, BackupError> {
+        let malformed = |line: usize, message: String| BackupError::Malformed { file: file.to_owned(), line, message };
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let schema = match lines.next() {
+            Some((_, line)) => serde_json::from_str::<JSON>(line)
+                .ok()
+                .and_then(|header| Some(header.get("schema")?.as_str()?.to_owned()))
+                .ok_or_else(|| malformed(1, "expected the schema on the first line".to_owned()))?,
+            None => return Err(malformed(1, "empty file".to_owned())),
+        };
+        let parsed = Schema::parse(&schema).map_err(|error| malformed(1, error.to_string()))?;
+        let mut records = Vec::new();
+        for (index, line) in lines {
+            let json = serde_json::from_str::<JSON>(line).map_err(|error| malformed(index + 1, error.to_string()))?;
+            records.push(Record::from_json(&parsed, &json).map_err(|message| malformed(index + 1, message))?);
+        }
+        Ok(Backup { schema, records })
+    }
+}
+
+fn constant_literal(constant: &Constant) -> Literal {
+    match constant {
+        Constant::Long(value) => Literal::Long(*value),
+        Constant::Double(value) => Literal::Double(*value),
+        Constant::Boolean(value) => Literal::Boolean(*value),
+        Constant::String(value) => Literal::String(value.clone()),
+        Constant::DateTime(value) => Literal::DateTime(*value),
+    }
+}
+
+fn value_literal(value: &Value) -> Literal {
+    match value {
+        Value::Long(value) => Literal::Long(*value),
+        Value::Double(value) => Literal::Double(*value),
+        Value::Boolean(value) => Literal::Boolean(*value),
+        Value::String(value) => Literal::String(value.clone()),
+        Value::DateTime(value) => Literal::DateTime(*value),
+    }
+}
+
+/// The IID and type label of an entity, relation or attribute.
+fn thing(answer: &ConceptMap, var: &str) -> Option<(String, String)> {
+    match answer.get(var)? {
+        Concept::Entity(entity) => Some((entity.iid.to_string(), entity.type_.label.clone())),
+        Concept::Relation(relation) => Some((relation.iid.to_string(), relation.type_.label.clone())),
+        Concept::Attribute(attribute) => Some((attribute.iid.to_string(), attribute.type_.label.clone())),
+        _ => None,
+    }
+}
+
+// tag::backup-export[]
+/// A backup of the database, read in a single read transaction. A role player is recorded under the most
+/// specific role it plays, e.g. `object` rather than the `owned` it overrides. Attributes that an entity or
+/// relation owns are recorded with their owners, and the others as things of their own.
+pub fn export(driver: &Connection, db_name: &str) -> Result<Backup, AppError> {
+    let schema_text = database::schema(driver, db_name)?;
+    let schema = Schema::parse(&schema_text)
+        .map_err(|error| AppError::schema_load(format!("the schema of database {}", db_name), error))?;
+    let session = database::session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    let mut found = BTreeMap::new();
+    for query in ["match $x isa entity; get;", "match $x isa relation; get;", "match $x isa attribute; get;"] {
+        for answer in tx.query().get(query)? {
+            let answer = answer?;
+            if let Some((key, type_)) = thing(&answer, "x") {
+                let value = match answer.get("x") {
+                    Some(Concept::Attribute(attribute)) => Some(value_literal(&attribute.value)),
+                    _ => None,
+                };
+                found.insert(key.clone(), Found { key, type_, value, attributes: Vec::new(), players: Vec::new() });
+            }
+        }
+    }
+    // The attributes recorded with an entity or relation that owns them.
+    let mut owned = HashSet::new();
+    for answer in tx.query().get("match $x has $a; get;")? {
+        let answer = answer?;
+        if let (Some((key, _)), Some(Concept::Attribute(attribute))) = (thing(&answer, "x"), answer.get("a")) {
+            if let Some(found) = found.get_mut(&key) {
+                found.attributes.push((attribute.type_.label.clone(), value_literal(&attribute.value)));
+                if found.value.is_none() {
+                    owned.insert(attribute.iid.to_string());
+                }
+            }
+        }
+    }
+    let mut played = BTreeMap::<(String, String), Vec<String>>::new();
+    for answer in tx.query().get("match $r ($role: $p) isa relation; get;")? {
+        let answer = answer?;
+        if let (Some((relation, _)), Some((player, _)), Some(Concept::RoleType(role))) =
+            (thing(&answer, "r"), thing(&answer, "p"), answer.get("role"))
+        {
+            if !role.is_root {
+                played.entry((relation, player)).or_default().push(role.label.name.clone());
+            }
+        }
+    }
+    let players = played.keys().map(|(_, player)| player.clone()).collect::<HashSet<_>>();
+    found.retain(|key, found| {
+        found.value.is_none() || !owned.contains(key) || players.contains(key) || !found.attributes.is_empty()
+    });
+    for ((relation, player), roles) in played {
+        let Some(found) = found.get_mut(&relation) else { continue };
+        let type_ = found.type_.clone();
+        let roles = roles
+            .iter()
+            .filter(|role| !roles.iter().any(|other| other != *role && schema.is_role_subtype(&type_, other, role)));
+        let roles = roles.cloned().collect::<HashSet<_>>();
+        found.players.extend(roles.into_iter().map(|role| (role, player.clone())));
+    }
+    Ok(Backup { schema: schema_text, records: number(found.into_values().collect()) })
+}
+// end::backup-export[]
+
+// tag::backup-restore[]
+/// The attribute that holds the ID of each thing while a backup is restored. Every statement inserts its thing
+/// with its ID, so a later batch matches exactly the thing it refers to, even when things have no attributes or
+/// the same attributes and role players as others. It is removed once the restored counts are checked.
+pub const RESTORE_ID: &str = "backup-restore-id";
+
+/// Creates the database, which must not exist yet, with the schema of the backup at `path` and loads its things
+/// with the bulk loader, matching them again by their [`RESTORE_ID`]s. Fails with [`AppError::Conflict`] when the
+/// schema already defines that attribute or when the database ends up with other numbers of things of each type
+/// than the backup, in which case it is left as it is to inspect or delete.
+pub fn restore(
+    driver: &Connection,
+    db_name: &str,
+    path: &Path,
+    options: &LoadOptions,
+    progress: &(dyn Fn(&Progress) + Sync),
+) -> Result<Progress, AppError> {
+    let file = path.display().to_string();
+    let format = BackupFormat::from_extension(path)?;
+    let text = std::fs::read_to_string(path)?;
+    if database::exists(driver, db_name)? {
+        return Err(AppError::Conflict(format!("database {} exists; restore into a new database", db_name)));
+    }
+    let (schema_text, statements, expected) = match format {
+        BackupFormat::Jsonl => {
+            let backup = Backup::read_jsonl(&file, &text)?;
+            let statements = backup.statements().collect::<Result<Vec<_>, _>>()?;
+            let expected = backup.counts().into_iter().map(|(type_, count)| (type_.to_owned(), count)).collect();
+            (backup.schema, statements, expected)
+        }
+        BackupFormat::TypeQL => {
+            let (schema, data) = split_typeql(&file, &text)?;
+            let statements = loader::Statements::new(&file, data.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+            let expected = inserted_counts(&statements);
+            (schema.to_owned(), statements, expected)
+        }
+    };
+    let schema = Schema::parse(&schema_text).map_err(|error| AppError::schema_load(&file, error))?;
+    let owners = restore_id_owners(&schema)?;
+    database::create(driver, db_name)?;
+    database::define_schema(driver, db_name, &file, &schema_text)?;
+    database::define_schema(driver, db_name, RESTORE_ID, &define_restore_id(&owners))?;
+    let ids = statements.iter().filter_map(|statement| statement.binds.clone()).collect::<Vec<_>>();
+    let statements = statements.into_iter().map(with_restore_id).map(Ok);
+    let progress = loader::load_statements(driver, db_name, &file, statements, options, progress)?;
+    check_counts(&expected, &restored_counts(driver, db_name, expected.keys())?)?;
+    remove_restore_id(driver, db_name, &owners, &ids, options.batch_size)?;
+    Ok(progress)
+}
+
+/// The types that own [`RESTORE_ID`] while a backup is restored: the concrete types that do not inherit it from
+/// a concrete supertype.
+fn restore_id_owners(schema: &Schema) -> Result<Vec<&str>, AppError> {
+    if schema.get(RESTORE_ID).is_some() {
+        return Err(AppError::Conflict(format!("the schema of the backup defines {}, which restore uses", RESTORE_ID)));
+    }
+    let concrete = |label: &str| schema.get(label).is_some_and(|type_def| !type_def.is_abstract);
+    let owners = schema.types().map(|type_def| type_def.label.as_str()).filter(|label| {
+        schema.kind(label).is_some() && concrete(label) && !schema.supertypes(label).skip(1).any(concrete)
+    });
+    Ok(owners.collect())
+}
+
+/// Defines [`RESTORE_ID`] as a unique attribute of `owners`. It is not a key, as the attributes that an entity or
+/// relation owns are inserted without an ID of their own.
+fn define_restore_id(owners: &[&str]) -> String {
+    let mut query = format!("define\n{} sub attribute, value string;\n", RESTORE_ID);
+    for owner in owners {
+        query.push_str(&format!("{} owns {} @unique;\n", owner, RESTORE_ID));
+    }
+    query
+}
+
+fn undefine_restore_id(owners: &[&str]) -> String {
+    let mut query = "undefine\n".to_owned();
+    for owner in owners {
+        query.push_str(&format!("{} owns {};\n", owner, RESTORE_ID));
+    }
+    query.push_str(&format!("{} sub attribute;\n", RESTORE_ID));
+    query
+}
+
+/// The statement inserting its thing with the name of the variable it binds as its [`RESTORE_ID`].
+fn with_restore_id(statement: Statement) -> Statement {
+    match &statement.binds {
+        Some(var) => Statement::new(format!("{}, has {} \"{}\"", statement.text, RESTORE_ID, var), statement.line),
+        None => statement,
+    }
+}
+
+/// The number of things of each type that the statements insert.
+fn inserted_counts(statements: &[Statement]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for statement in statements.iter().filter(|statement| statement.binds.is_some()) {
+        let Ok(typeql::pattern::Statement::Thing(thing)) = typeql::parse_statement(&statement.text) else { continue };
+        if let Some(TypeReference::Label(label)) = thing.isa.map(|isa| isa.type_reference) {
+            *counts.entry(label.name).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// The number of things of each of `types` that have a [`RESTORE_ID`], read in a single read transaction.
+fn restored_counts<'a>(
+    driver: &Connection,
+    db_name: &str,
+    types: impl Iterator<Item = &'a String>,
+) -> Result<BTreeMap<String, usize>, AppError> {
+    let session = database::session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    let mut counts = BTreeMap::new();
+    for type_ in types {
+        let query = format!("match $x isa! {}, has {} $id; get $x; count;", type_, RESTORE_ID);
+        let count = match tx.query().get_aggregate(&query).resolve()? {
+            Some(Value::Long(count)) => count as usize,
+            other => return Err(AppError::Conflict(format!("unexpected count of {}: {:?}", type_, other))),
+        };
+        counts.insert(type_.clone(), count);
+    }
+    Ok(counts)
+}
+
+/// Fails with [`AppError::Conflict`] naming every type whose number of restored things differs from the backup.
+fn check_counts(expected: &BTreeMap<String, usize>, actual: &BTreeMap<String, usize>) -> Result<(), AppError> {
+    let mismatches = expected
+        .iter()
+        .filter_map(|(type_, count)| {
+            let restored = actual.get(type_).copied().unwrap_or_default();
+            (restored != *count).then(|| format!("{} {} of {}", restored, type_, count))
+        })
+        .collect::<Vec<_>>();
+    match mismatches.is_empty() {
+        true => Ok(()),
+        false => Err(AppError::Conflict(format!("restored {}", mismatches.join(", ")))),
+    }
+}
+
+/// Deletes the [`RESTORE_ID`]s, `batch_size` of them in each transaction like the loader inserted them, and then
+/// their definition.
+fn remove_restore_id(
+    driver: &Connection,
+    db_name: &str,
+    owners: &[&str],
+    ids: &[String],
+    batch_size: usize,
+) -> Result<(), AppError> {
+    {
+        let session = database::session(driver, db_name, SessionType::Data)?;
+        for batch in ids.chunks(batch_size.max(1)) {
+            let tx = session.transaction(TransactionType::Write)?;
+            for id in batch {
+                tx.query().delete(&delete_restore_id(id)).resolve()?;
+            }
+            tx.commit().resolve()?;
+        }
+    }
+    let session = database::session(driver, db_name, SessionType::Schema)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    tx.query().undefine(&undefine_restore_id(owners)).resolve()?;
+    Ok(tx.commit().resolve()?)
+}
+
+fn delete_restore_id(id: &str) -> String {
+    format!("match $id \"{1}\" isa {0}; delete $id isa {0};", RESTORE_ID, id)
+}
+
+/// Splits a TypeQL backup into its `define` query and the `insert` query that starts on a line of its own.
+pub fn split_typeql<'a>(file: &str, text: &'a str) -> Result<(&'a str, &'a str), BackupError> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim() == "insert" {
+            return Ok((&text[..offset], &text[offset..]));
+        }
+        offset += line.len();
+    }
+    let line = text.lines().count();
+    Err(BackupError::Malformed { file: file.to_owned(), line, message: "no insert query".to_owned() })
+}
+// end::backup-restore[]
+// end::backup[]
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{config::ConfigLayer, connection, database::IAM_SEED, IAM_DATA, IAM_SCHEMA};
+
+    fn iam_graph() -> Graph {
+        let mut graph = Graph::new(Schema::parse(IAM_SCHEMA).unwrap());
+        graph.load(IAM_DATA).unwrap();
+        graph
+    }
+
+    fn restore_graph(schema: &str, data: &str) -> Graph {
+        let mut graph = Graph::new(Schema::parse(schema).unwrap());
+        graph.load(data).unwrap();
+        graph
+    }
+
+    #[test]
+    fn ids_are_stable_and_players_come_first() {
+        let backup = Backup::from_graph(&iam_graph());
+        let counts = backup.counts();
+        assert_eq!((counts["person"], counts["file"], counts["access"], counts["permission"]), (3, 10, 20, 15));
+        let kevin = backup.records.iter().find(|record| record.typeql().unwrap().contains("Kevin Morrison")).unwrap();
+        assert_eq!(kevin.id, "person-1");
+        let mut seen = HashSet::new();
+        for record in &backup.records {
+            assert!(record.players.iter().all(|(_, player)| seen.contains(player)), "{}", record.typeql().unwrap());
+            seen.insert(&record.id);
+        }
+
+        let mut reversed = Graph::new(Schema::parse(IAM_SCHEMA).unwrap());
+        let statements = backup.records.iter().rev().map(|record| record.typeql().unwrap()).collect::<Vec<_>>();
+        reversed.load(&format!("insert {};", statements.join(";\n"))).unwrap();
+        assert_eq!(Backup::from_graph(&reversed), backup);
+    }
+
+    #[test]
+    fn jsonl_backups_round_trip() {
+        let backup = Backup::from_graph(&iam_graph());
+        let mut out = Vec::new();
+        backup.write(BackupFormat::Jsonl, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(
+            "\n{\"id\":\"access-1\",\"players\":{\"action\":[\"operation-1\"],\"object\":[\"file-1\"]},\"type\":\"access\"}\n"
+        ));
+        let read = Backup::read_jsonl("backup.jsonl", &text).unwrap();
+        assert_eq!(read, backup);
+
+        let statements = read.statements().map(|statement| statement.unwrap().text).collect::<Vec<_>>();
+        let restored = restore_graph(&read.schema, &format!("insert {};", statements.join(";\n")));
+        assert_eq!(Backup::from_graph(&restored).counts(), backup.counts());
+        assert_eq!(Backup::from_graph(&restored), backup);
+
+        let broken = text.replacen("\"size-kb\":[", "\"size-kb\":[\"big\",", 1);
+        assert!(matches!(Backup::read_jsonl("backup.jsonl", &broken), Err(BackupError::Malformed { .. })));
+    }
+
+    #[test]
+    fn quoted_values_and_standalone_attributes_round_trip() {
+        let mut graph = iam_graph();
+        graph.load(r#"insert $p isa person, has full-name 'Carla "CJ" Jones', has email "carla@typedb.com";"#).unwrap();
+        let mut backup = Backup::from_graph(&graph);
+        let statements = backup.statements().map(|statement| statement.unwrap().text).collect::<Vec<_>>();
+        assert!(statements.iter().any(|text| text.contains(r#"has full-name 'Carla "CJ" Jones'"#)));
+        let restored = restore_graph(&backup.schema, &format!("insert {};", statements.join(";\n")));
+        assert_eq!(Backup::from_graph(&restored), backup);
+
+        let unowned = Record {
+            id: "name-1".to_owned(),
+            type_: "name".to_owned(),
+            value: Some(Literal::from("O'Brien")),
+            attributes: Vec::new(),
+            players: Vec::new(),
+        };
+        assert_eq!(unowned.typeql().unwrap(), r#"$name-1 "O'Brien" isa name"#);
+        assert!(typeql::parse_query(&format!("insert {};", unowned.typeql().unwrap())).is_ok());
+        backup.records.push(unowned);
+        let mut out = Vec::new();
+        backup.write(BackupFormat::Jsonl, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(r#"{"id":"name-1","type":"name","value":"O'Brien"}"#));
+        assert_eq!(Backup::read_jsonl("backup.jsonl", &text).unwrap(), backup);
+    }
+
+    #[test]
+    fn typeql_backups_replay() {
+        let backup = Backup::from_graph(&iam_graph());
+        let mut out = Vec::new();
+        backup.write(BackupFormat::TypeQL, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let (schema, data) = split_typeql("backup.tql", &text).unwrap();
+        assert!(schema.starts_with("define") && data.starts_with("insert\n"));
+        let statements = loader::Statements::new("backup.tql", data.as_bytes()).collect::<Result<Vec<_>, _>>();
+        assert_eq!(statements.unwrap().len(), backup.records.len());
+        assert_eq!(Backup::from_graph(&restore_graph(schema, data)), backup);
+        assert!(split_typeql("backup.tql", schema).is_err());
+    }
+
+    #[test]
+    fn restores_match_things_by_their_ids_across_batches() {
+        let schema = "define\n\
+            label sub attribute, value string;\n\
+            node sub entity, owns label, plays edge:from, plays edge:to;\n\
+            edge sub relation, relates from, relates to;";
+        let data = "insert $a isa node; $b isa node; (from: $a, to: $b) isa edge; (from: $a, to: $b) isa edge;\n\
+            $c isa node, has label \"c\"; $d isa node, has label \"c\"; (from: $c, to: $d) isa edge;";
+        let backup = Backup::from_graph(&restore_graph(schema, data));
+        assert_eq!(backup.records.len(), 7);
+
+        let restored_schema = Schema::parse(&backup.schema).unwrap();
+        let owners = restore_id_owners(&restored_schema).unwrap();
+        assert_eq!(owners, ["edge", "label", "node"]);
+        assert!(typeql::parse_query(&define_restore_id(&owners)).is_ok());
+        assert!(typeql::parse_query(&undefine_restore_id(&owners)).is_ok());
+        assert!(typeql::parse_query(&delete_restore_id("node-1")).is_ok());
+
+        let mut planner = loader::Planner::new("backup.jsonl", 2);
+        let mut batches = Vec::new();
+        for statement in backup.statements() {
+            batches.extend(planner.push(with_restore_id(statement.unwrap())).unwrap());
+        }
+        batches.extend(planner.flush().unwrap());
+        assert_eq!(batches.len(), 4);
+        let mut ids = HashSet::new();
+        for batch in &batches {
+            assert!(typeql::parse_query(&batch.query).is_ok(), "{}", batch.query);
+            let (matched, inserted) = batch.query.split_once("insert\n").unwrap();
+            for statement in matched.lines().skip(1).chain(inserted.lines()) {
+                let id = statement.split_whitespace().next().unwrap().trim_start_matches('$');
+                assert!(statement.ends_with(&format!(", has {} \"{}\";", RESTORE_ID, id)), "{}", statement);
+            }
+            ids.extend(inserted.lines().map(str::to_owned));
+        }
+        assert_eq!(ids.len(), backup.records.len());
+
+        let statements = backup.statements().collect::<Result<Vec<_>, _>>().unwrap();
+        let expected = backup.counts().into_iter().map(|(type_, count)| (type_.to_owned(), count)).collect();
+        assert_eq!(inserted_counts(&statements), expected);
+        assert!(check_counts(&expected, &expected).is_ok());
+        let mut short = expected.clone();
+        short.insert("edge".to_owned(), 2);
+        let error = check_counts(&expected, &short).unwrap_err();
+        assert!(matches!(&error, AppError::Conflict(message) if message == "restored 2 edge of 3"), "{}", error);
+
+        let mut clashing = restored_schema.clone();
+        clashing.add_type(
+            Schema::parse("define backup-restore-id sub attribute, value long;")
+                .unwrap()
+                .get(RESTORE_ID)
+                .unwrap()
+                .clone(),
+        );
+        assert!(matches!(restore_id_owners(&clashing), Err(AppError::Conflict(_))));
+    }
+
+    /// Backs up a seeded IAM database and restores it into a new one, on the server that the `IAM_CORE_TEST_`
+    /// variables configure, e.g. `IAM_CORE_TEST_ADDRESSES`. Run it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs a TypeDB server"]
+    fn restores_into_a_new_database() {
+        let layer = ConfigLayer::from_env("IAM_CORE_TEST_", |key| env::var(key).ok());
+        let config = ConfigLayer::defaults().merge(layer).validate().unwrap();
+        let driver = connection::connect(&config).unwrap();
+        let (source, target) = (format!("{}-backup", config.db_name), format!("{}-restore", config.db_name));
+        for db_name in [&source, &target] {
+            if database::exists(&driver, db_name).unwrap() {
+                database::delete(&driver, db_name).unwrap();
+            }
+        }
+        database::create_seeded(&driver, &source, &IAM_SEED).unwrap();
+        let backup = export(&driver, &source).unwrap();
+        let path = env::temp_dir().join(format!("iam-backup-{}.jsonl", process::id()));
+        let mut out = Vec::new();
+        backup.write(BackupFormat::Jsonl, &mut out).unwrap();
+        fs::write(&path, out).unwrap();
+
+        let options = LoadOptions { batch_size: 4, ..LoadOptions::default() };
+        let restored = restore(&driver, &target, &path, &options, &|_| ()).and_then(|_| export(&driver, &target));
+        fs::remove_file(&path).unwrap();
+        for db_name in [&source, &target] {
+            let _ = database::delete(&driver, db_name);
+        }
+        let restored = restored.unwrap();
+        assert_eq!(restored.counts(), backup.counts());
+        assert_eq!(restored.records, backup.records);
+        assert!(!restored.schema.contains(RESTORE_ID));
+    }
+}
@@ -0,0 +1,235 @@
+// tag::codegen[]
+//! Generates Rust source for the types of a [`Schema`]: a struct for every concrete entity type, deriving
+//! `TypeQLEntity`, and for every concrete relation type a struct of its attributes implementing `Model` and an
+//! enum of its roles. `build.rs` runs it on `iam-schema.tql` to produce [`crate::schema_types`].
+//!
+//! An attribute owned with `@key` becomes a field of its value type and any other attribute an `Option` of it,
+//! since a schema does not say how many values a thing has.
+use std::fmt::Write;
+
+use crate::schema::{Kind, Schema, TypeDef, ValueType};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+    "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while", "yield",
+];
+
+/// `group-membership` as `GroupMembership`.
+pub fn type_name(label: &str) -> String {
+    label
+        .split(['-', '_'])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// `size-kb` as `size_kb`, escaping Rust keywords.
+pub fn field_name(label: &str) -> String {
+    let name = label.replace('-', "_");
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
+
+fn rust_type(value_type: ValueType) -> &'static str {
+    match value_type {
+        ValueType::Boolean => "bool",
+        ValueType::DateTime => "chrono::NaiveDateTime",
+        ValueType::Double => "f64",
+        ValueType::Long => "i64",
+        ValueType::String => "String",
+    }
+}
+
+/// The supertypes of a type from the outermost down to the type itself.
+fn lineage<'a>(schema: &'a Schema, label: &'a str) -> Vec<&'a TypeDef> {
+    let mut lineage = schema.supertypes(label).filter_map(|label| schema.get(label)).collect::<Vec<_>>();
+    lineage.reverse();
+    lineage
+}
+
+/// The attributes a type owns, inherited ones first, with whether they are keys. Overridden attributes are left out.
+fn attributes<'a>(schema: &'a Schema, label: &'a str) -> Vec<(&'a str, bool)> {
+    let mut attributes: Vec<(&str, bool)> = Vec::new();
+    for type_def in lineage(schema, label) {
+        for owns in &type_def.owns {
+            attributes.retain(|(attribute, _)| Some(*attribute) != owns.overrides.as_deref());
+            match attributes.iter_mut().find(|(attribute, _)| *attribute == owns.attribute) {
+                Some((_, key)) => *key |= owns.key,
+                None => attributes.push((&owns.attribute, owns.key)),
+            }
+        }
+    }
+    attributes
+}
+
+/// The roles a relation type relates, inherited ones first. A role overridden with `relates ... as ...` is replaced.
+fn roles<'a>(schema: &'a Schema, label: &'a str) -> Vec<&'a str> {
+    let mut roles: Vec<&str> = Vec::new();
+    for type_def in lineage(schema, label) {
+        for relates in &type_def.relates {
+            roles.retain(|role| Some(*role) != relates.overrides.as_deref() && *role != relates.role);
+            roles.push(&relates.role);
+        }
+    }
+    roles
+}
+
+fn fields(schema: &Schema, label: &str) -> Vec<(String, String, String)> {
+    attributes(schema, label)
+        .into_iter()
+        .map(|(attribute, key)| {
+            let value = schema.value_type(attribute).map_or("String", rust_type);
+            let ty = if key { value.to_owned() } else { format!("Option<{}>", value) };
+            (attribute.to_owned(), field_name(attribute), ty)
+        })
+        .collect()
+}
+
+fn doc(type_def: &TypeDef) -> String {
+    match type_def.supertype.as_deref() {
+        Some(supertype) => format!("/// `{}`, a subtype of `{}`.\n", type_def.label, supertype),
+        None => format!("/// `{}`.\n", type_def.label),
+    }
+}
+
+fn write_entity(out: &mut String, schema: &Schema, type_def: &TypeDef, models: &str) {
+    out.push_str(&doc(type_def));
+    out.push_str("#[derive(Clone, Debug, PartialEq, typeql_derive::TypeQLEntity)]\n");
+    writeln!(out, "#[typeql(type = \"{}\", crate = \"{}\")]", type_def.label, models).unwrap();
+    writeln!(out, "pub struct {} {{", type_name(&type_def.label)).unwrap();
+    for (attribute, field, ty) in fields(schema, &type_def.label) {
+        if attribute.replace('-', "_") == field {
+            writeln!(out, "    pub {}: {},", field, ty).unwrap();
+        } else {
+            writeln!(out, "    #[typeql(attribute = \"{}\")]\n    pub {}: {},", attribute, field, ty).unwrap();
+        }
+    }
+    out.push_str("}\n\n");
+}
+
+fn write_relation(out: &mut String, schema: &Schema, type_def: &TypeDef, models: &str) {
+    let name = type_name(&type_def.label);
+    let roles = roles(schema, &type_def.label);
+    let variants = roles.iter().map(|role| type_name(role)).collect::<Vec<_>>();
+
+    writeln!(out, "/// The roles of `{}`.", type_def.label).unwrap();
+    out.push_str("#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]\n");
+    writeln!(out, "pub enum {}Role {{", name).unwrap();
+    for variant in &variants {
+        writeln!(out, "    {},", variant).unwrap();
+    }
+    out.push_str("}\n\n");
+    writeln!(out, "impl {}Role {{", name).unwrap();
+    let all = variants.iter().map(|variant| format!("Self::{}", variant)).collect::<Vec<_>>();
+    writeln!(out, "    pub const ALL: &'static [Self] = &[{}];\n", all.join(", ")).unwrap();
+    out.push_str("    pub fn label(self) -> &'static str {\n        match self {\n");
+    for (role, variant) in roles.iter().zip(&variants) {
+        writeln!(out, "            Self::{} => \"{}\",", variant, role).unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+    out.push_str("    /// The variable that the player of this role in the relation bound to `var` is bound to.\n");
+    writeln!(
+        out,
+        "    pub fn player_var(self, var: &str) -> String {{\n        {}::player_var(var, self.label())\n    }}",
+        models
+    )
+    .unwrap();
+    out.push_str("}\n\n");
+    writeln!(out, "impl std::fmt::Display for {}Role {{", name).unwrap();
+    out.push_str("    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n");
+    out.push_str("        f.write_str(self.label())\n    }\n}\n\n");
+
+    let fields = fields(schema, &type_def.label);
+    out.push_str(&doc(type_def));
+    out.push_str("#[derive(Clone, Debug, PartialEq)]\n");
+    if fields.is_empty() {
+        writeln!(out, "pub struct {};\n", name).unwrap();
+    } else {
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (_, field, ty) in &fields {
+            writeln!(out, "    pub {}: {},", field, ty).unwrap();
+        }
+        out.push_str("}\n\n");
+    }
+    writeln!(out, "impl {} {{", name).unwrap();
+    let attributes = fields.iter().map(|(attribute, _, _)| format!("\"{}\"", attribute)).collect::<Vec<_>>();
+    writeln!(out, "    pub const ATTRIBUTES: &'static [&'static str] = &[{}];", attributes.join(", ")).unwrap();
+    out.push_str("}\n\n");
+    writeln!(out, "impl {}::Model for {} {{", models, name).unwrap();
+    writeln!(out, "    const TYPES: &'static [&'static str] = &[\"{}\"];\n", type_def.label).unwrap();
+    writeln!(
+        out,
+        "    fn decode(answer: &(impl {0}::Answer + ?Sized), var: &str) -> Result<Self, {0}::ModelError> {{",
+        models
+    )
+    .unwrap();
+    writeln!(out, "        {}::check_type(answer, var, Self::TYPES)?;", models).unwrap();
+    if fields.is_empty() {
+        writeln!(out, "        Ok({})", name).unwrap();
+    } else {
+        out.push_str("        Ok(Self {\n");
+        for (attribute, field, ty) in &fields {
+            let decode = if ty.starts_with("Option<") { "optional" } else { "one" };
+            writeln!(out, "            {}: {}::{}(answer, var, \"{}\")?,", field, models, decode, attribute).unwrap();
+        }
+        out.push_str("        })\n");
+    }
+    out.push_str("    }\n}\n\n");
+}
+
+/// Rust source for the concrete entity and relation types of the schema, sorted by label. The generated code
+/// refers to the models module by the path `models`, such as `crate::models` or `iam_core::models`.
+pub fn rust_types(schema: &Schema, models: &str) -> String {
+    let mut out = String::new();
+    for type_def in schema.types().filter(|type_def| !type_def.is_abstract) {
+        match schema.kind(&type_def.label) {
+            Some(Kind::Entity) => write_entity(&mut out, schema, type_def, models),
+            Some(Kind::Relation) => write_relation(&mut out, schema, type_def, models),
+            _ => (),
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+// end::codegen[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{Model, TypeQLEntity},
+        schema_types::{self, GroupMembershipRole},
+        IAM_SCHEMA,
+    };
+
+    #[test]
+    fn generates_entities_and_relations_of_the_iam_schema() {
+        let code = rust_types(&Schema::parse(IAM_SCHEMA).unwrap(), "crate::models");
+        assert!(code.contains(
+            "#[typeql(type = \"person\", crate = \"crate::models\")]\npub struct Person {\n    \
+             pub credential: Option<String>,\n    pub email: Option<String>,\n    pub full_name: Option<String>,\n}\n"
+        ));
+        assert!(code.contains("    pub review_date: Option<chrono::NaiveDateTime>,\n    pub validity: Option<bool>,\n"));
+        assert!(code.contains("pub enum AccessRole {\n    Action,\n    Object,\n}\n"));
+        assert!(!code.contains("pub struct SizeKb"));
+        assert!(!code.contains("pub struct Subject "));
+    }
+
+    #[test]
+    fn generated_types_follow_the_schema() {
+        assert_eq!(schema_types::File::TYPE, "file");
+        assert_eq!(schema_types::File::ATTRIBUTES, ["object-type", "path", "size-kb"]);
+        assert_eq!(schema_types::Permission::ATTRIBUTES, ["review-date", "validity"]);
+        assert_eq!(schema_types::GroupMembership::TYPES, ["group-membership"]);
+        let roles = GroupMembershipRole::ALL.iter().map(|role| role.label()).collect::<Vec<_>>();
+        assert_eq!(roles, ["member", "group"]);
+        assert_eq!(GroupMembershipRole::Group.player_var("m"), "m-group");
+    }
+}
@@ -0,0 +1,284 @@
+// tag::config[]
+//! Layered configuration: built-in defaults, then a TOML file, then prefixed environment variables, then
+//! command-line flags. Each layer only overrides the keys it sets; the merged result is validated once.
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    TypeDb,
+    Memory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edition {
+    Core,
+    Cloud,
+}
+
+/// What to do when the database to set up already exists. A database that does not exist yet is always created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bootstrap {
+    /// Keep the database if its schema matches the expected one, and fail otherwise.
+    Reuse,
+    /// Delete the database and create it again.
+    Replace,
+    /// Fail without touching the database.
+    FailIfExists,
+    /// Keep the database and its data, defining the expected schema on top if the schema differs.
+    EnsureSchema,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub backend: Backend,
+    pub bootstrap: Bootstrap,
+    pub db_name: String,
+    pub edition: Edition,
+    pub addresses: Vec<String>,
+    pub username: String,
+    pub password: String,
+    pub tls_root_ca: Option<PathBuf>,
+}
+
+/// One configuration layer. Keys match the TOML file; environment variables use the upper-cased key with the
+/// binary's prefix, e.g. `SAMPLE_APP_DB_NAME`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigLayer {
+    pub backend: Option<String>,
+    pub bootstrap: Option<String>,
+    pub db_name: Option<String>,
+    pub edition: Option<String>,
+    pub addresses: Option<Vec<String>>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls_root_ca: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    File { path: PathBuf, reason: String },
+    Invalid { key: &'static str, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, reason } => {
+                write!(f, "Failed to read configuration file {}: {}", path.display(), reason)
+            }
+            ConfigError::Invalid { key, reason } => write!(f, "Invalid configuration value for '{}': {}", key, reason),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+fn invalid(key: &'static str, reason: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid { key, reason: reason.into() }
+}
+
+impl ConfigLayer {
+    /// The values the sample app used before it was configurable.
+    pub fn defaults() -> Self {
+        Self {
+            backend: Some("typedb".to_owned()),
+            bootstrap: Some("reuse".to_owned()),
+            db_name: Some("sample_app_db".to_owned()),
+            edition: Some("core".to_owned()),
+            addresses: Some(vec!["127.0.0.1:1729".to_owned()]),
+            username: Some("admin".to_owned()),
+            password: Some("password".to_owned()),
+            tls_root_ca: None,
+        }
+    }
+
+    /// Reads a TOML layer. A missing file is an empty layer unless `required` is set.
+    pub fn from_file(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(ConfigError::File { path: path.to_owned(), reason: error.to_string() }),
+        };
+        toml::from_str(&text).map_err(|error| ConfigError::File { path: path.to_owned(), reason: error.to_string() })
+    }
+
+    /// Reads the variables named `prefix` followed by the upper-cased key, such as `SAMPLE_APP_` and `DB_NAME`.
+    pub fn from_env(prefix: &str, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |key: &str| lookup(&format!("{}{}", prefix, key)).filter(|value| !value.is_empty());
+        Self {
+            backend: var("BACKEND"),
+            bootstrap: var("BOOTSTRAP"),
+            db_name: var("DB_NAME"),
+            edition: var("EDITION"),
+            addresses: var("ADDRESSES")
+                .map(|value| value.split(',').map(|address| address.trim().to_owned()).collect()),
+            username: var("USERNAME"),
+            password: var("PASSWORD"),
+            tls_root_ca: var("TLS_ROOT_CA").map(PathBuf::from),
+        }
+    }
+
+    /// Returns `self` with every key that is set in `other` replaced by the value from `other`.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            backend: other.backend.or(self.backend),
+            bootstrap: other.bootstrap.or(self.bootstrap),
+            db_name: other.db_name.or(self.db_name),
+            edition: other.edition.or(self.edition),
+            addresses: other.addresses.or(self.addresses),
+            username: other.username.or(self.username),
+            password: other.password.or(self.password),
+            tls_root_ca: other.tls_root_ca.or(self.tls_root_ca),
+        }
+    }
+
+    pub fn validate(self) -> Result<Config, ConfigError> {
+        let backend = match self.backend.as_deref().map(str::to_lowercase).as_deref() {
+            Some("typedb") => Backend::TypeDb,
+            Some("memory") => Backend::Memory,
+            Some(other) => return Err(invalid("backend", format!("'{}' must be either 'typedb' or 'memory'", other))),
+            None => return Err(invalid("backend", "missing")),
+        };
+        let bootstrap = match self.bootstrap.as_deref().map(str::to_lowercase).as_deref() {
+            Some("reuse") => Bootstrap::Reuse,
+            Some("replace") => Bootstrap::Replace,
+            Some("fail-if-exists") => Bootstrap::FailIfExists,
+            Some("ensure-schema") => Bootstrap::EnsureSchema,
+            Some(other) => {
+                let expected = "'reuse', 'replace', 'fail-if-exists' or 'ensure-schema'";
+                return Err(invalid("bootstrap", format!("'{}' must be one of {}", other, expected)));
+            }
+            None => return Err(invalid("bootstrap", "missing")),
+        };
+        let db_name = self.db_name.ok_or_else(|| invalid("db-name", "missing"))?;
+        if db_name.is_empty() || !db_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(invalid("db-name", format!("'{}' must be non-empty and contain only [A-Za-z0-9_-]", db_name)));
+        }
+        let edition = match self.edition.as_deref().map(str::to_lowercase).as_deref() {
+            Some("core") => Edition::Core,
+            Some("cloud") => Edition::Cloud,
+            Some(other) => return Err(invalid("edition", format!("'{}' must be either 'core' or 'cloud'", other))),
+            None => return Err(invalid("edition", "missing")),
+        };
+        let addresses = self.addresses.unwrap_or_default();
+        if addresses.is_empty() {
+            return Err(invalid("addresses", "at least one server address is required"));
+        }
+        for address in &addresses {
+            match address.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => (),
+                _ => return Err(invalid("addresses", format!("'{}' is not of the form host:port", address))),
+            }
+        }
+        if edition == Edition::Core && addresses.len() > 1 {
+            return Err(invalid("addresses", "TypeDB Core takes exactly one server address"));
+        }
+        let username = self.username.unwrap_or_default();
+        let password = self.password.unwrap_or_default();
+        if edition == Edition::Cloud {
+            if username.is_empty() {
+                return Err(invalid("username", "required for TypeDB Cloud"));
+            }
+            if password.is_empty() {
+                return Err(invalid("password", "required for TypeDB Cloud"));
+            }
+        }
+        if let Some(path) = &self.tls_root_ca {
+            if edition == Edition::Core {
+                return Err(invalid("tls-root-ca", "only supported for TypeDB Cloud"));
+            }
+            if !path.is_file() {
+                return Err(invalid("tls-root-ca", format!("{} is not a readable file", path.display())));
+            }
+        }
+        Ok(Config {
+            backend,
+            bootstrap,
+            db_name,
+            edition,
+            addresses,
+            username,
+            password,
+            tls_root_ca: self.tls_root_ca,
+        })
+    }
+}
+// end::config[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> ConfigLayer {
+        let vars = vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<Vec<_>>();
+        ConfigLayer::from_env("SAMPLE_APP_", |key| vars.iter().find(|(k, _)| k == key).map(|(_, value)| value.clone()))
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let file: ConfigLayer = toml::from_str("db-name = \"from_file\"\nusername = \"file-user\"").unwrap();
+        let env = env(&[
+            ("SAMPLE_APP_DB_NAME", "from_env"),
+            ("SAMPLE_APP_ADDRESSES", "a:1729, b:1729"),
+            ("SAMPLE_APP_BOOTSTRAP", "fail-if-exists"),
+        ]);
+        let flags = ConfigLayer { edition: Some("cloud".to_owned()), ..Default::default() };
+        let config = ConfigLayer::defaults().merge(file).merge(env).merge(flags).validate().unwrap();
+        assert_eq!(config.db_name, "from_env");
+        assert_eq!(config.bootstrap, Bootstrap::FailIfExists);
+        assert_eq!(config.edition, Edition::Cloud);
+        assert_eq!(config.addresses, vec!["a:1729", "b:1729"]);
+        assert_eq!(config.username, "file-user");
+        assert_eq!(config.password, "password");
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = ConfigLayer::defaults().validate().unwrap();
+        assert_eq!(config.backend, Backend::TypeDb);
+        assert_eq!(config.bootstrap, Bootstrap::Reuse);
+        assert_eq!(config.db_name, "sample_app_db");
+        assert_eq!(config.edition, Edition::Core);
+        assert_eq!(config.addresses, vec!["127.0.0.1:1729"]);
+    }
+
+    #[test]
+    fn validation_errors_name_the_key() {
+        let key_of = |layer: ConfigLayer| match ConfigLayer::defaults().merge(layer).validate() {
+            Err(ConfigError::Invalid { key, .. }) => key,
+            other => panic!("expected a validation error, got {:?}", other),
+        };
+        assert_eq!(key_of(ConfigLayer { backend: Some("sqlite".to_owned()), ..Default::default() }), "backend");
+        assert_eq!(key_of(ConfigLayer { bootstrap: Some("recreate".to_owned()), ..Default::default() }), "bootstrap");
+        assert_eq!(key_of(ConfigLayer { edition: Some("enterprise".to_owned()), ..Default::default() }), "edition");
+        assert_eq!(key_of(ConfigLayer { db_name: Some("my db".to_owned()), ..Default::default() }), "db-name");
+        assert_eq!(
+            key_of(ConfigLayer { addresses: Some(vec!["localhost".to_owned()]), ..Default::default() }),
+            "addresses"
+        );
+        assert_eq!(
+            key_of(ConfigLayer { addresses: Some(vec!["a:1".to_owned(), "b:2".to_owned()]), ..Default::default() }),
+            "addresses"
+        );
+        assert_eq!(
+            key_of(ConfigLayer { tls_root_ca: Some(PathBuf::from("ca.pem")), ..Default::default() }),
+            "tls-root-ca"
+        );
+        let cloud =
+            ConfigLayer { edition: Some("cloud".to_owned()), password: Some(String::new()), ..Default::default() };
+        assert_eq!(key_of(cloud), "password");
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        let error = toml::from_str::<ConfigLayer>("db_nmae = \"x\"").unwrap_err();
+        assert!(error.to_string().contains("db_nmae"));
+    }
+}
@@ -0,0 +1,20 @@
+// tag::connection[]
+//! Connecting to TypeDB Core or Cloud as configured.
+use typedb_driver::{Connection, Credential};
+
+use crate::{
+    config::{Config, Edition},
+    error::AppError,
+};
+
+/// Opens a connection to the first address for TypeDB Core, or to all addresses with the configured credential
+/// for TypeDB Cloud. Any failure is an [`AppError::Connection`].
+pub fn connect(config: &Config) -> Result<Connection, AppError> {
+    match config.edition {
+        Edition::Core => Connection::new_core(&config.addresses[0]),
+        Edition::Cloud => Credential::with_tls(&config.username, &config.password, config.tls_root_ca.as_deref())
+            .and_then(|credential| Connection::new_cloud(&config.addresses, credential)),
+    }
+    .map_err(AppError::Connection)
+}
+// end::connection[]
@@ -0,0 +1,202 @@
+// tag::database[]
+//! Database lifecycle: creating and deleting databases, defining a schema and loading data, each in its own
+//! session and committed transaction.
+use typedb_driver::{
+    answer::JSON, concept::Value, Connection, DatabaseManager, Promise, Session, SessionType, TransactionType,
+};
+
+use crate::{
+    config::Bootstrap,
+    error::AppError,
+    loader::{self, LoadOptions},
+    migration::{GROUP_INHERITANCE, MIGRATION_TYPES, VALID_VIEW_PERMISSION},
+    query::Query,
+    schema::Schema,
+    IAM_DATA, IAM_DATA_FILE, IAM_SCHEMA, IAM_SCHEMA_FILE,
+};
+
+/// The number of users in [`IAM_DATA`].
+pub const IAM_USER_COUNT: i64 = 3;
+
+pub(crate) fn session(driver: &Connection, db_name: &str, session_type: SessionType) -> Result<Session, AppError> {
+    let databases = DatabaseManager::new(driver.clone());
+    Ok(Session::new(databases.get(db_name)?, session_type)?)
+}
+
+pub fn exists(driver: &Connection, db_name: &str) -> Result<bool, AppError> {
+    Ok(DatabaseManager::new(driver.clone()).contains(db_name)?)
+}
+
+pub fn create(driver: &Connection, db_name: &str) -> Result<(), AppError> {
+    Ok(DatabaseManager::new(driver.clone()).create(db_name)?)
+}
+
+pub fn delete(driver: &Connection, db_name: &str) -> Result<(), AppError> {
+    Ok(DatabaseManager::new(driver.clone()).get(db_name)?.delete()?)
+}
+
+// tag::db-schema-setup[]
+/// Defines `schema`, the contents of the file called `file`, in a schema session. A rejected definition is an
+/// [`AppError::SchemaLoad`].
+pub fn define_schema(driver: &Connection, db_name: &str, file: &str, schema: &str) -> Result<(), AppError> {
+    let session = session(driver, db_name, SessionType::Schema)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    tx.query().define(schema).resolve().map_err(|error| AppError::schema_load(file, error))?;
+    Ok(tx.commit().resolve()?)
+}
+// end::db-schema-setup[]
+
+// tag::db-dataset-setup[]
+/// Runs the insert query `data`, the contents of the file called `file`, in a data session, and returns the
+/// number of answers. A rejected insert is an [`AppError::SchemaLoad`]. Large datasets are better loaded in
+/// batches with [`loader::load`].
+pub fn load_data(driver: &Connection, db_name: &str, file: &str, data: &str) -> Result<usize, AppError> {
+    let session = session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    let answers = tx
+        .query()
+        .insert(data)
+        .and_then(|answers| answers.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| AppError::schema_load(file, error))?;
+    tx.commit().resolve()?;
+    Ok(answers.len())
+}
+// end::db-dataset-setup[]
+
+/// Runs a fetch query in a read transaction of a data session and collects the answers.
+pub fn fetch(driver: &Connection, db_name: &str, query: &str) -> Result<Vec<JSON>, AppError> {
+    let session = session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    let answers = tx.query().fetch(query)?.collect::<Result<_, _>>()?;
+    Ok(answers)
+}
+
+/// A schema and dataset to create a database with, and the names of the files they come from for messages.
+#[derive(Clone, Copy, Debug)]
+pub struct Seed<'a> {
+    pub schema_file: &'a str,
+    pub schema: &'a str,
+    pub data_file: &'a str,
+    pub data: &'a str,
+    /// Further `define` queries to apply after the schema, as `(file, query)`.
+    pub definitions: &'a [(&'a str, &'a str)],
+}
+
+impl Seed<'_> {
+    /// The schema with the types and rules of the further definitions.
+    pub fn parse_schema(&self) -> Result<Schema, AppError> {
+        let mut schema = Schema::parse(self.schema).map_err(|error| AppError::schema_load(self.schema_file, error))?;
+        for (file, definition) in self.definitions {
+            let definition = Schema::parse(definition).map_err(|error| AppError::schema_load(*file, error))?;
+            definition.types().for_each(|type_def| schema.add_type(type_def.clone()));
+            definition.rules().iter().for_each(|rule| schema.add_rule(rule.clone()));
+        }
+        Ok(schema)
+    }
+}
+
+/// The IAM schema and dataset, with the rules that the later IAM migrations define, so that a seeded database has
+/// the schema of one with all of [`Migrations::iam`](crate::migration::Migrations::iam) applied.
+pub const IAM_SEED: Seed<'static> = Seed {
+    schema_file: IAM_SCHEMA_FILE,
+    schema: IAM_SCHEMA,
+    data_file: IAM_DATA_FILE,
+    data: IAM_DATA,
+    definitions: &[
+        ("0003_valid-view-permission.tql", VALID_VIEW_PERMISSION),
+        ("0004_group-inheritance.tql", GROUP_INHERITANCE),
+    ],
+};
+
+/// Defines the schema of `seed` and its further definitions, each in its own schema transaction.
+fn define_seed_schema(driver: &Connection, db_name: &str, seed: &Seed) -> Result<(), AppError> {
+    define_schema(driver, db_name, seed.schema_file, seed.schema)?;
+    for (file, definition) in seed.definitions {
+        define_schema(driver, db_name, file, definition)?;
+    }
+    Ok(())
+}
+
+/// Creates the database with the schema and dataset of `seed`, loading the dataset in batches with the default
+/// [`LoadOptions`].
+pub fn create_seeded(driver: &Connection, db_name: &str, seed: &Seed) -> Result<(), AppError> {
+    create(driver, db_name)?;
+    define_seed_schema(driver, db_name, seed)?;
+    loader::load(driver, db_name, seed.data_file, seed.data.as_bytes(), &LoadOptions::default(), &|_| ())?;
+    Ok(())
+}
+
+/// The schema of the database as a `define` query, as reported by the server.
+pub fn schema(driver: &Connection, db_name: &str) -> Result<String, AppError> {
+    Ok(DatabaseManager::new(driver.clone()).get(db_name)?.schema()?)
+}
+
+/// Whether the schema of the database defines the same types and rules as the schema of `seed`, apart from the
+/// types that record applied migrations.
+pub fn schema_matches(driver: &Connection, db_name: &str, seed: &Seed) -> Result<bool, AppError> {
+    let expected = seed.parse_schema()?;
+    let actual = Schema::parse(&schema(driver, db_name)?)
+        .map_err(|error| AppError::schema_load(format!("the schema of database {}", db_name), error))?;
+    Ok(actual.without(MIGRATION_TYPES).same_definitions(&expected))
+}
+
+/// What [`bootstrap`] did to the database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bootstrapped {
+    Created,
+    Replaced,
+    Reused,
+    SchemaDefined,
+}
+
+// tag::bootstrap[]
+/// Sets up the database from `seed` without asking questions. A missing database is created; an existing one is
+/// handled as `policy` says, after comparing its schema with the schema of `seed` where the policy depends on it.
+/// A conflicting database is an [`AppError::Conflict`].
+pub fn bootstrap(driver: &Connection, db_name: &str, policy: Bootstrap, seed: &Seed) -> Result<Bootstrapped, AppError> {
+    if !exists(driver, db_name)? {
+        create_seeded(driver, db_name, seed)?;
+        return Ok(Bootstrapped::Created);
+    }
+    match policy {
+        Bootstrap::Replace => {
+            delete(driver, db_name)?;
+            create_seeded(driver, db_name, seed)?;
+            Ok(Bootstrapped::Replaced)
+        }
+        Bootstrap::FailIfExists => Err(AppError::Conflict(format!("database {} already exists", db_name))),
+        Bootstrap::Reuse | Bootstrap::EnsureSchema if schema_matches(driver, db_name, seed)? => {
+            Ok(Bootstrapped::Reused)
+        }
+        Bootstrap::Reuse => Err(AppError::Conflict(format!(
+            "the schema of database {} does not match {}; choose another bootstrap policy to replace or update it",
+            db_name, seed.schema_file
+        ))),
+        Bootstrap::EnsureSchema => {
+            define_seed_schema(driver, db_name, seed)?;
+            Ok(Bootstrapped::SchemaDefined)
+        }
+    }
+}
+// end::bootstrap[]
+
+// tag::test-db[]
+pub fn count_users(driver: &Connection, db_name: &str) -> Result<i64, AppError> {
+    let session = session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    let query = Query::new("match $u isa user; get $u; count;").build()?;
+    match tx.query().get_aggregate(&query).resolve()? {
+        Some(Value::Long(count)) => Ok(count),
+        other => Err(AppError::Conflict(format!("unexpected user count: {:?}", other))),
+    }
+}
+
+/// Checks that the database holds the IAM dataset, failing with [`AppError::Conflict`] otherwise.
+pub fn check_iam(driver: &Connection, db_name: &str) -> Result<(), AppError> {
+    match count_users(driver, db_name)? {
+        IAM_USER_COUNT => Ok(()),
+        count => Err(AppError::Conflict(format!("expected {} users in the database, found {}", IAM_USER_COUNT, count))),
+    }
+}
+// end::test-db[]
+// end::database[]
@@ -0,0 +1,142 @@
+// tag::decision[]
+//! Access decisions: whether a subject may perform an action on an object. A subject is named by any of its
+//! identifiers, such as an email or a business unit name, or by its full name; an object by any of its
+//! identifiers, such as a path, a record number or a database name; and an action by its name.
+//!
+//! The request is allowed when a permission of the subject grants an access with the action on the object, and
+//! that permission is not marked invalid with `validity false`. Permissions without a validity count as valid,
+//! which includes inferred ones: the rules only infer permissions from valid ones.
+//! With inference, the subject also holds the permissions of every group it belongs to, directly or through other
+//! groups; those are reported as the group's permissions, so that an invalid group permission denies its members.
+use std::{collections::BTreeMap, fmt};
+
+use chrono::NaiveDateTime;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessRequest {
+    pub subject: String,
+    pub action: String,
+    pub object: String,
+}
+
+impl fmt::Display for AccessRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.subject, self.action, self.object)
+    }
+}
+
+/// A permission of the subject to the access with the action on the object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grant {
+    /// The permission relation: its IID, or its ID in the in-memory graph.
+    pub permission: String,
+    /// The access relation, identified like the permission.
+    pub access: String,
+    /// Whether the permission was inferred by a rule.
+    pub inferred: bool,
+    /// The identifier of the group that holds the permission, when the subject inherits it.
+    pub group: Option<String>,
+    pub validity: Option<bool>,
+    pub review_date: Option<NaiveDateTime>,
+}
+
+impl Grant {
+    pub fn is_valid(&self) -> bool {
+        self.validity != Some(false)
+    }
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "permission {} of access {}", self.permission, self.access)?;
+        if self.inferred {
+            write!(f, ", inferred")?;
+        }
+        if let Some(group) = &self.group {
+            write!(f, ", inherited from {}", group)?;
+        }
+        if !self.is_valid() {
+            write!(f, ", invalid")?;
+        }
+        if let Some(review_date) = self.review_date {
+            write!(f, ", reviewed {}", review_date.format("%F"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub request: AccessRequest,
+    /// Every permission that matches the request, valid or not, sorted by permission.
+    pub grants: Vec<Grant>,
+}
+
+impl Decision {
+    pub fn new(request: AccessRequest, mut grants: Vec<Grant>) -> Self {
+        grants.sort_by(|a, b| (&a.permission, &a.access).cmp(&(&b.permission, &b.access)));
+        grants.dedup();
+        Self { request, grants }
+    }
+
+    pub fn allowed(&self) -> bool {
+        self.grants.iter().any(Grant::is_valid)
+    }
+}
+
+/// The grants of the subject and its groups, keyed by who holds each permission, without the permissions that the
+/// rules infer for a holder from those of a group it is a member of, which are reported as the group's permission
+/// instead. `is_member(holder, group)` tells whether the holder belongs to the group, directly or not.
+pub(crate) fn without_inherited<H>(grants: Vec<(H, Grant)>, is_member: impl Fn(&H, &H) -> bool) -> Vec<Grant> {
+    let inherited = |(holder, grant): &(H, Grant)| {
+        grant.inferred && grants.iter().any(|(group, other)| other.access == grant.access && is_member(holder, group))
+    };
+    let kept = grants.iter().map(|grant| !inherited(grant)).collect::<Vec<_>>();
+    grants.into_iter().zip(kept).filter(|(_, kept)| *kept).map(|((_, grant), _)| grant).collect()
+}
+
+/// The groups that grant access, given whether each only holds inferred permissions for it, without those that hold
+/// them because they are members of another of the groups, as [`without_inherited`] does for grants.
+/// `is_member(group, other)` tells whether the group belongs to the other group, directly or not.
+pub(crate) fn granting_groups<G: Ord>(groups: BTreeMap<G, bool>, is_member: impl Fn(&G, &G) -> bool) -> Vec<G> {
+    let inherited = |group: &G, inferred: bool| inferred && groups.keys().any(|other| is_member(group, other));
+    let kept = groups.iter().map(|(group, inferred)| !inherited(group, *inferred)).collect::<Vec<_>>();
+    groups.into_keys().zip(kept).filter(|(_, kept)| *kept).map(|(group, _)| group).collect()
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.allowed() { "allow" } else { "deny" };
+        write!(f, "{}: {}", verdict, self.request)
+    }
+}
+// end::decision[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grant(permission: &str, validity: Option<bool>) -> Grant {
+        Grant {
+            permission: permission.to_owned(),
+            access: "a".to_owned(),
+            inferred: false,
+            group: None,
+            validity,
+            review_date: None,
+        }
+    }
+
+    #[test]
+    fn only_valid_permissions_allow() {
+        let request =
+            AccessRequest { subject: "kevin".to_owned(), action: "view_file".to_owned(), object: "LICENSE".to_owned() };
+        let deny = Decision::new(request.clone(), vec![grant("p2", Some(false))]);
+        assert!(!deny.allowed());
+        assert_eq!(deny.to_string(), "deny: kevin view_file LICENSE");
+        let allow = Decision::new(request, vec![grant("p2", Some(false)), grant("p1", None)]);
+        assert!(allow.allowed());
+        assert_eq!(allow.grants[0].permission, "p1");
+        assert_eq!(allow.grants[1].to_string(), "permission p2 of access a, invalid");
+    }
+}
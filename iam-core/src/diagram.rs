@@ -0,0 +1,158 @@
+// tag::diagram[]
+//! Class diagrams of a [`Schema`] in Graphviz DOT or Mermaid. Every type is a node listing whether it is abstract,
+//! its value type and the roles it relates; edges point from a type to its supertype, to the attribute types it
+//! owns and to the relation types whose roles it plays.
+use std::collections::BTreeSet;
+
+use crate::{
+    error::AppError,
+    schema::{root_label, Kind, Schema, TypeDef},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+enum Edge<'a> {
+    Sub,
+    Owns(&'a str),
+    Plays(&'a str),
+}
+
+/// Renders the whole schema, or with `focus` only that type, its subtypes and the types they own or play roles in.
+pub fn render(schema: &Schema, format: Format, focus: Option<&str>) -> Result<String, AppError> {
+    if let Some(label) = focus.filter(|label| schema.get(label).is_none()) {
+        return Err(AppError::NotFound(format!("type {} in the schema", label)));
+    }
+    let sources = schema
+        .types()
+        .filter(|type_def| focus.is_none_or(|focus| schema.is_subtype(&type_def.label, focus)))
+        .collect::<Vec<_>>();
+    let mut edges = Vec::new();
+    for type_def in &sources {
+        let supertype = type_def.supertype.as_deref().filter(|label| schema.get(label).is_some());
+        if let Some(supertype) = supertype.filter(|_| focus != Some(type_def.label.as_str())) {
+            edges.push((type_def.label.as_str(), supertype, Edge::Sub));
+        }
+        for owns in &type_def.owns {
+            let key = if owns.key { "owns @key" } else { "owns" };
+            edges.push((type_def.label.as_str(), owns.attribute.as_str(), Edge::Owns(key)));
+        }
+        for plays in &type_def.plays {
+            edges.push((type_def.label.as_str(), plays.relation.as_str(), Edge::Plays(plays.role.as_str())));
+        }
+    }
+    let labels = sources
+        .iter()
+        .map(|type_def| type_def.label.as_str())
+        .chain(edges.iter().map(|(_, target, _)| *target))
+        .collect::<BTreeSet<_>>();
+    let nodes = labels.into_iter().filter_map(|label| schema.get(label)).collect::<Vec<_>>();
+    Ok(match format {
+        Format::Dot => dot(schema, &nodes, &edges),
+        Format::Mermaid => mermaid(schema, &nodes, &edges),
+    })
+}
+
+/// The lines inside a node below its label.
+fn members(type_def: &TypeDef) -> Vec<String> {
+    let mut members = Vec::new();
+    if type_def.is_abstract {
+        members.push("abstract".to_owned());
+    }
+    if let Some(value_type) = type_def.value_type {
+        members.push(format!("value {}", value_type));
+    }
+    members.extend(type_def.relates.iter().map(ToString::to_string));
+    members
+}
+
+fn dot(schema: &Schema, nodes: &[&TypeDef], edges: &[(&str, &str, Edge)]) -> String {
+    let mut out = String::from("digraph schema {\n    rankdir=BT;\n    node [shape=record, fontname=\"Helvetica\"];\n");
+    for type_def in nodes {
+        let shape = match schema.kind(&type_def.label) {
+            Some(Kind::Attribute) => ", style=rounded",
+            Some(Kind::Relation) => ", style=dashed",
+            _ => "",
+        };
+        let members = members(type_def).into_iter().map(|member| format!("{}\\l", member)).collect::<String>();
+        let label =
+            if members.is_empty() { type_def.label.clone() } else { format!("{{{}|{}}}", type_def.label, members) };
+        out.push_str(&format!("    \"{}\" [label=\"{}\"{}];\n", type_def.label, label, shape));
+    }
+    for (source, target, edge) in edges {
+        let attributes = match edge {
+            Edge::Sub => "arrowhead=empty".to_owned(),
+            Edge::Owns(label) => format!("style=dotted, label=\"{}\"", label),
+            Edge::Plays(role) => format!("label=\"plays {}\"", role),
+        };
+        out.push_str(&format!("    \"{}\" -> \"{}\" [{}];\n", source, target, attributes));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid identifiers cannot contain hyphens, so nodes are named with underscores and labelled with the type.
+fn mermaid_id(label: &str) -> String {
+    label.replace('-', "_")
+}
+
+fn mermaid(schema: &Schema, nodes: &[&TypeDef], edges: &[(&str, &str, Edge)]) -> String {
+    let mut out = String::from("classDiagram\n");
+    for type_def in nodes {
+        out.push_str(&format!("    class {}[\"{}\"] {{\n", mermaid_id(&type_def.label), type_def.label));
+        if let Some(kind) = schema.kind(&type_def.label) {
+            out.push_str(&format!("        <<{}>>\n", root_label(kind)));
+        }
+        for member in members(type_def) {
+            out.push_str(&format!("        {}\n", member));
+        }
+        out.push_str("    }\n");
+    }
+    for (source, target, edge) in edges {
+        let (source, target) = (mermaid_id(source), mermaid_id(target));
+        match edge {
+            Edge::Sub => out.push_str(&format!("    {} <|-- {}\n", target, source)),
+            Edge::Owns(label) => out.push_str(&format!("    {} ..> {} : {}\n", source, target, label)),
+            Edge::Plays(role) => out.push_str(&format!("    {} --> {} : plays {}\n", source, target, role)),
+        }
+    }
+    out
+}
+// end::diagram[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IAM_SCHEMA;
+
+    #[test]
+    fn renders_dot_for_the_whole_schema() {
+        let schema = Schema::parse(IAM_SCHEMA).unwrap();
+        let dot = render(&schema, Format::Dot, None).unwrap();
+        assert!(dot.starts_with("digraph schema {"));
+        assert!(dot.contains("    \"person\" -> \"user\" [arrowhead=empty];\n"));
+        assert!(dot.contains("    \"person\" -> \"email\" [style=dotted, label=\"owns\"];\n"));
+        assert!(dot.contains("    \"subject\" -> \"permission\" [label=\"plays subject\"];\n"));
+        assert!(dot
+            .contains("\"group-membership\" [label=\"{group-membership|relates group as parent\\l}\", style=dashed]"));
+        let nodes = dot.lines().filter(|line| line.contains(" [label=") && !line.contains(" -> "));
+        assert_eq!(nodes.count(), schema.types().count());
+    }
+
+    #[test]
+    fn renders_mermaid_for_a_subtree() {
+        let schema = Schema::parse(IAM_SCHEMA).unwrap();
+        let mermaid = render(&schema, Format::Mermaid, Some("user-group")).unwrap();
+        assert!(mermaid.starts_with("classDiagram\n"));
+        assert!(mermaid.contains("    class business_unit[\"business-unit\"] {\n        <<entity>>\n    }\n"));
+        assert!(mermaid.contains("    user_group <|-- user_role\n"));
+        assert!(mermaid.contains("    user_group --> group_membership : plays group\n"));
+        assert!(mermaid.contains("    user_account ..> email : owns\n"));
+        assert!(!mermaid.contains("class person["));
+        assert!(!mermaid.contains("subject <|-- user_group"));
+        assert!(matches!(render(&schema, Format::Mermaid, Some("nobody")), Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,216 @@
+// tag::error[]
+//! The errors of the sample app. Failures are classified by what went wrong rather than where, and errors from
+//! the driver, the configuration and the in-memory backend are kept as sources.
+use std::{error::Error, fmt, io};
+
+use typedb_driver::{error::ConnectionError, Error as TypeDBError};
+
+use crate::{
+    backup::BackupError, config::ConfigError, graph::GraphError, import::ImportError, loader::LoadError,
+    migration::MigrationError, models::ModelError, query::QueryError, rules::RuleError,
+};
+
+#[derive(Debug)]
+pub enum AppError {
+    /// Nothing matched, e.g. no user with the given name or no file with the given path.
+    NotFound(String),
+    /// The database does not exist on the server.
+    DatabaseNotFound { name: String, source: Box<TypeDBError> },
+    /// More than one match where exactly one is required, e.g. two users with the same full name.
+    Ambiguous(String),
+    /// The existing data prevents the operation, e.g. a database that fails its check.
+    Conflict(String),
+    /// The schema or dataset could not be read, parsed or defined.
+    SchemaLoad { file: String, source: Box<dyn Error + Send + Sync> },
+    /// The server could not be reached or the connection was lost.
+    Connection(TypeDBError),
+    /// Any other error reported by the driver or the server.
+    Driver(TypeDBError),
+    /// The configuration file could not be read, or a setting is missing or not valid.
+    Config(ConfigError),
+    /// The migrations could not be read.
+    Migration(MigrationError),
+    /// A data file could not be split into batches, or its checkpoint does not fit it.
+    Load(LoadError),
+    /// A backup could not be read.
+    Backup(BackupError),
+    /// An import mapping does not fit the schema, or its data is not well-formed.
+    Import(ImportError),
+    /// A query template and its parameters do not fit, e.g. an unbound or unquotable value.
+    Query(QueryError),
+    /// An answer could not be decoded into a model.
+    Model(ModelError),
+    /// The in-memory data could not be parsed or does not match the schema.
+    Data(GraphError),
+    /// The in-memory backend cannot evaluate a schema rule, or a rule inferred invalid data.
+    Rule(RuleError),
+    /// A file could not be read or written.
+    Io(io::Error),
+}
+
+impl AppError {
+    pub fn schema_load(file: impl Into<String>, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::SchemaLoad { file: file.into(), source: source.into() }
+    }
+
+    /// The process exit code for the error: 3 when nothing was found, 4 when the data is ambiguous or conflicting
+    /// (2 is taken by usage errors), and otherwise the matching BSD `sysexits.h` code (`EX_DATAERR`,
+    /// `EX_UNAVAILABLE`, `EX_SOFTWARE`, `EX_IOERR` or `EX_CONFIG`).
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AppError::NotFound(_) | AppError::DatabaseNotFound { .. } => 3,
+            AppError::Ambiguous(_) | AppError::Conflict(_) => 4,
+            AppError::SchemaLoad { .. }
+            | AppError::Migration(_)
+            | AppError::Load(_)
+            | AppError::Backup(_)
+            | AppError::Import(_)
+            | AppError::Data(_)
+            | AppError::Rule(_) => 65,
+            AppError::Connection(_) => 69,
+            AppError::Driver(_) | AppError::Query(_) | AppError::Model(_) => 70,
+            AppError::Io(_) => 74,
+            AppError::Config(_) => 78,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(what) => write!(f, "Not found: {}", what),
+            AppError::DatabaseNotFound { name, .. } => write!(f, "Not found: database {}", name),
+            AppError::Ambiguous(what) => write!(f, "Ambiguous: {}", what),
+            AppError::Conflict(what) => write!(f, "Conflict: {}", what),
+            AppError::SchemaLoad { file, source } => write!(f, "Failed to load {}: {}", file, source),
+            AppError::Connection(error) => write!(f, "Connection failed: {}", error),
+            AppError::Driver(error) => write!(f, "TypeDB error: {}", error),
+            AppError::Config(error) => write!(f, "{}", error),
+            AppError::Migration(error) => write!(f, "{}", error),
+            AppError::Load(error) => write!(f, "{}", error),
+            AppError::Backup(error) => write!(f, "{}", error),
+            AppError::Import(error) => write!(f, "{}", error),
+            AppError::Query(error) => write!(f, "{}", error),
+            AppError::Model(error) => write!(f, "{}", error),
+            AppError::Data(error) => write!(f, "{}", error),
+            AppError::Rule(error) => write!(f, "{}", error),
+            AppError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl Error for AppError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppError::NotFound(_) | AppError::Ambiguous(_) | AppError::Conflict(_) => None,
+            AppError::SchemaLoad { source, .. } => Some(source.as_ref()),
+            AppError::DatabaseNotFound { source, .. } => Some(source.as_ref()),
+            AppError::Connection(error) | AppError::Driver(error) => Some(error),
+            AppError::Config(error) => Some(error),
+            AppError::Migration(error) => Some(error),
+            AppError::Load(error) => Some(error),
+            AppError::Backup(error) => Some(error),
+            AppError::Import(error) => Some(error),
+            AppError::Query(error) => Some(error),
+            AppError::Model(error) => Some(error),
+            AppError::Data(error) => Some(error),
+            AppError::Rule(error) => Some(error),
+            AppError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<TypeDBError> for AppError {
+    fn from(error: TypeDBError) -> Self {
+        match &error {
+            TypeDBError::Connection(ConnectionError::DatabaseDoesNotExist { name }) => {
+                AppError::DatabaseNotFound { name: name.clone(), source: Box::new(error) }
+            }
+            TypeDBError::Connection(_) => AppError::Connection(error),
+            _ => AppError::Driver(error),
+        }
+    }
+}
+
+impl From<ConfigError> for AppError {
+    fn from(error: ConfigError) -> Self {
+        AppError::Config(error)
+    }
+}
+
+impl From<MigrationError> for AppError {
+    fn from(error: MigrationError) -> Self {
+        AppError::Migration(error)
+    }
+}
+
+impl From<LoadError> for AppError {
+    fn from(error: LoadError) -> Self {
+        AppError::Load(error)
+    }
+}
+
+impl From<BackupError> for AppError {
+    fn from(error: BackupError) -> Self {
+        AppError::Backup(error)
+    }
+}
+
+impl From<ImportError> for AppError {
+    fn from(error: ImportError) -> Self {
+        AppError::Import(error)
+    }
+}
+
+impl From<QueryError> for AppError {
+    fn from(error: QueryError) -> Self {
+        AppError::Query(error)
+    }
+}
+
+impl From<ModelError> for AppError {
+    fn from(error: ModelError) -> Self {
+        AppError::Model(error)
+    }
+}
+
+impl From<GraphError> for AppError {
+    fn from(error: GraphError) -> Self {
+        AppError::Data(error)
+    }
+}
+
+impl From<RuleError> for AppError {
+    fn from(error: RuleError) -> Self {
+        AppError::Rule(error)
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(error: io::Error) -> Self {
+        AppError::Io(error)
+    }
+}
+// end::error[]
+
+#[cfg(test)]
+mod tests {
+    use typedb_driver::error::InternalError;
+
+    use super::*;
+
+    #[test]
+    fn driver_errors_are_classified() {
+        let missing = TypeDBError::Connection(ConnectionError::DatabaseDoesNotExist { name: "iam".to_owned() });
+        let missing = AppError::from(missing);
+        assert_eq!((missing.to_string().as_str(), missing.exit_code()), ("Not found: database iam", 3));
+        assert!(matches!(&missing, AppError::DatabaseNotFound { name, .. } if name == "iam"));
+        assert!(missing.source().is_some());
+        let closed = AppError::from(TypeDBError::Connection(ConnectionError::ConnectionIsClosed));
+        assert!(matches!(closed, AppError::Connection(_)));
+        assert_eq!(closed.exit_code(), 69);
+        let internal = AppError::from(TypeDBError::Internal(InternalError::RecvError));
+        assert!(matches!(internal, AppError::Driver(_)));
+        assert!(internal.source().is_some());
+    }
+}
@@ -0,0 +1,114 @@
+// tag::explanation[]
+//! Explanations of answers that depend on inferred relations: for each inferred relation, the rules that
+//! concluded it and the relations that each rule's condition matched, which may in turn be inferred. Things are
+//! described by one of their identifiers (an email, a name, a path, ...), and relations by their role players, so
+//! that an explanation reads like the data it was derived from.
+use std::fmt;
+
+/// A relation that an answer depends on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fact {
+    pub type_: String,
+    /// The role players as `(role, description)`, in the order of the pattern that matched the relation.
+    pub players: Vec<(String, String)>,
+    pub inferred: bool,
+    /// One explanation per way the rules conclude the relation; empty for relations in the data.
+    pub explanations: Vec<Explanation>,
+}
+
+/// A rule that concluded a fact, with the relations its condition matched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    pub rule: String,
+    pub condition: Vec<Fact>,
+}
+
+/// A file that a user may view, with the `view_file` permissions that grant it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplainedFile {
+    pub path: String,
+    pub permissions: Vec<Fact>,
+}
+
+impl Fact {
+    /// The fact and its explanations as an indented tree, one line per fact or rule.
+    pub fn tree(&self) -> String {
+        let mut tree = self.to_string();
+        self.write_branches(&mut tree, "");
+        tree
+    }
+
+    fn write_branches(&self, tree: &mut String, indent: &str) {
+        for (index, explanation) in self.explanations.iter().enumerate() {
+            let (line, below) = branch(index + 1 == self.explanations.len());
+            tree.push_str(&format!("\n{}{}rule {}", indent, line, explanation.rule));
+            let indent = format!("{}{}", indent, below);
+            for (index, fact) in explanation.condition.iter().enumerate() {
+                let (line, below) = branch(index + 1 == explanation.condition.len());
+                tree.push_str(&format!("\n{}{}{}", indent, line, fact));
+                fact.write_branches(tree, &format!("{}{}", indent, below));
+            }
+        }
+    }
+}
+
+/// The prefix of a line in the tree, and the indent of the lines below it.
+fn branch(last: bool) -> (&'static str, &'static str) {
+    if last {
+        ("└── ", "    ")
+    } else {
+        ("├── ", "│   ")
+    }
+}
+
+impl fmt::Display for Fact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", self.type_)?;
+        for (index, (role, player)) in self.players.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            if !role.is_empty() {
+                write!(f, "{}: ", role)?;
+            }
+            write!(f, "{}", player)?;
+        }
+        write!(f, ")")?;
+        if self.inferred {
+            write!(f, " [inferred]")?;
+        }
+        Ok(())
+    }
+}
+// end::explanation[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact(type_: &str, players: &[(&str, &str)], explanations: Vec<Explanation>) -> Fact {
+        Fact {
+            type_: type_.to_owned(),
+            players: players.iter().map(|(role, player)| (role.to_string(), player.to_string())).collect(),
+            inferred: !explanations.is_empty(),
+            explanations,
+        }
+    }
+
+    #[test]
+    fn renders_explanations_as_a_tree() {
+        let access = fact("access", &[("object", "LICENSE"), ("action", "modify_file")], Vec::new());
+        let condition =
+            vec![access.clone(), fact("permission", &[("subject", "kevin"), ("access", "...")], Vec::new())];
+        let explanation = Explanation { rule: "add-view-permission".to_owned(), condition };
+        let permission = fact("permission", &[("subject", "kevin"), ("", "x")], vec![explanation]);
+        assert_eq!(
+            permission.tree(),
+            "permission (subject: kevin, x) [inferred]
+└── rule add-view-permission
+    ├── access (object: LICENSE, action: modify_file)
+    └── permission (subject: kevin, access: ...)"
+        );
+        assert_eq!(access.tree(), access.to_string());
+    }
+}
@@ -0,0 +1,314 @@
+// tag::graph[]
+//! In-memory instances of a [`Schema`]: entities and relations with their owned attribute values and role players.
+//! Attribute values are stored on their owners rather than as shared attribute instances.
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use typeql::{
+    common::token,
+    pattern::{Constant, HasConstraint, Predicate, Value},
+    query::Query,
+    variable::{ConceptVariable, TypeReference},
+};
+
+use crate::schema::{Kind, Schema, ValueType};
+
+pub type ThingId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thing {
+    pub type_: String,
+    pub attributes: Vec<(String, Constant)>,
+    pub role_players: Vec<(String, ThingId)>,
+}
+
+impl Thing {
+    pub fn values<'a>(&'a self, attribute: &'a str) -> impl Iterator<Item = &'a Constant> + 'a {
+        self.attributes.iter().filter(move |(owned, _)| owned == attribute).map(|(_, value)| value)
+    }
+
+    pub fn strings<'a>(&'a self, attribute: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.values(attribute).filter_map(|value| match value {
+            Constant::String(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn has_string(&self, attribute: &str, value: &str) -> bool {
+        self.strings(attribute).any(|owned| owned == value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GraphError {
+    Parse(String),
+    Invalid(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Parse(message) => write!(f, "Failed to parse data: {}", message),
+            GraphError::Invalid(message) => write!(f, "Data does not match the schema: {}", message),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+fn invalid(message: impl Into<String>) -> GraphError {
+    GraphError::Invalid(message.into())
+}
+
+#[derive(Clone, Debug)]
+pub struct Graph {
+    schema: Schema,
+    things: BTreeMap<ThingId, Thing>,
+    next_id: ThingId,
+}
+
+impl Graph {
+    pub fn new(schema: Schema) -> Self {
+        Self { schema, things: BTreeMap::new(), next_id: 0 }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn get(&self, id: ThingId) -> Option<&Thing> {
+        self.things.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: ThingId) -> Option<&mut Thing> {
+        self.things.get_mut(&id)
+    }
+
+    /// Instances of the type and of all its subtypes.
+    pub fn instances<'a>(&'a self, type_: &'a str) -> impl Iterator<Item = (ThingId, &'a Thing)> + 'a {
+        self.things
+            .iter()
+            .filter(move |(_, thing)| self.schema.is_subtype(&thing.type_, type_))
+            .map(|(id, thing)| (*id, thing))
+    }
+
+    /// Players of `role` in a relation, including players of roles that override it.
+    pub fn players<'a>(&'a self, relation: &'a Thing, role: &'a str) -> impl Iterator<Item = ThingId> + 'a {
+        relation
+            .role_players
+            .iter()
+            .filter(move |(played, _)| self.schema.is_role_subtype(&relation.type_, played, role))
+            .map(|(_, player)| *player)
+    }
+
+    /// Relations of the type (or a subtype) in which `player` plays `role`.
+    pub fn relations<'a>(
+        &'a self,
+        type_: &'a str,
+        role: &'a str,
+        player: ThingId,
+    ) -> impl Iterator<Item = (ThingId, &'a Thing)> + 'a {
+        self.instances(type_).filter(move |(_, relation)| self.players(relation, role).any(|played| played == player))
+    }
+
+    pub fn insert(&mut self, thing: Thing) -> Result<ThingId, GraphError> {
+        self.validate(&thing)?;
+        Ok(self.insert_unchecked(thing))
+    }
+
+    fn insert_unchecked(&mut self, thing: Thing) -> ThingId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.things.insert(id, thing);
+        id
+    }
+
+    /// Adds an attribute value to an existing thing, checked against the schema like an insert.
+    pub fn add_attribute(&mut self, id: ThingId, attribute: &str, value: Constant) -> Result<(), GraphError> {
+        let mut thing = self.things.get(&id).ok_or_else(|| invalid(format!("thing {} does not exist", id)))?.clone();
+        thing.attributes.push((attribute.to_owned(), value));
+        self.validate(&thing)?;
+        self.things.insert(id, thing);
+        Ok(())
+    }
+
+    /// Removes a thing and its role in every relation; relations left without role players are removed too.
+    pub fn remove(&mut self, id: ThingId) -> Option<Thing> {
+        let removed = self.things.remove(&id)?;
+        let mut emptied = Vec::new();
+        for (relation_id, relation) in self.things.iter_mut() {
+            let players = relation.role_players.len();
+            relation.role_players.retain(|(_, player)| *player != id);
+            if players > 0 && relation.role_players.is_empty() {
+                emptied.push(*relation_id);
+            }
+        }
+        for relation_id in emptied {
+            self.remove(relation_id);
+        }
+        Some(removed)
+    }
+
+    fn validate(&self, thing: &Thing) -> Result<(), GraphError> {
+        let type_def = self.schema.get(&thing.type_).ok_or_else(|| invalid(format!("unknown type {}", thing.type_)))?;
+        if type_def.is_abstract {
+            return Err(invalid(format!("type {} is abstract", thing.type_)));
+        }
+        match self.schema.kind(&thing.type_) {
+            Some(Kind::Entity) if thing.role_players.is_empty() => (),
+            Some(Kind::Entity) => return Err(invalid(format!("entity type {} has no roles", thing.type_))),
+            Some(Kind::Relation) => (),
+            _ => return Err(invalid(format!("type {} is neither an entity nor a relation type", thing.type_))),
+        }
+        for (attribute, value) in &thing.attributes {
+            if !self.schema.owns(&thing.type_, attribute) {
+                return Err(invalid(format!("type {} does not own {}", thing.type_, attribute)));
+            }
+            let value_type = self.schema.value_type(attribute);
+            if !value_type.is_some_and(|value_type| accepts(value_type, value)) {
+                return Err(invalid(format!("{} is not a valid value for {}", value, attribute)));
+            }
+        }
+        for (role, player) in &thing.role_players {
+            if !self.schema.relates(&thing.type_, role) {
+                return Err(invalid(format!("relation type {} does not relate {}", thing.type_, role)));
+            }
+            if !self.things.contains_key(player) {
+                return Err(invalid(format!("role player {} of {} does not exist", player, thing.type_)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the statements of a match-less `insert` query, such as `iam-data-single-query.tql`, and returns the
+    /// number of things inserted. Nothing is inserted if any statement is invalid.
+    pub fn load(&mut self, text: &str) -> Result<usize, GraphError> {
+        let insert = match typeql::parse_query(text).map_err(|error| GraphError::Parse(error.to_string()))? {
+            Query::Insert(insert) if insert.match_clause.is_none() => insert,
+            _ => return Err(GraphError::Parse("only match-less insert queries are supported".to_owned())),
+        };
+        let mut staged = self.clone();
+        let mut variables = BTreeMap::new();
+        let mut relations = Vec::new();
+        for statement in &insert.statements {
+            let type_ = match statement.isa.as_ref().map(|isa| &isa.type_reference) {
+                Some(TypeReference::Label(label)) => label.name.clone(),
+                _ => return Err(GraphError::Parse(format!("statement without a type: {}", statement))),
+            };
+            let mut attributes = Vec::new();
+            for has in &statement.has {
+                match has {
+                    HasConstraint::HasPredicate(
+                        label,
+                        Predicate {
+                            predicate: token::Predicate::Eq | token::Predicate::EqLegacy,
+                            value: Value::Constant(value),
+                        },
+                    ) => attributes.push((label.name.clone(), value.clone())),
+                    other => return Err(GraphError::Parse(format!("unsupported attribute: {}", other))),
+                }
+            }
+            let thing = Thing { type_, attributes, role_players: Vec::new() };
+            staged.validate(&thing)?;
+            let id = staged.insert_unchecked(thing);
+            if let ConceptVariable::Named(name) = &statement.variable {
+                if variables.insert(name.clone(), id).is_some() {
+                    return Err(GraphError::Parse(format!("variable ${} is inserted more than once", name)));
+                }
+            }
+            if let Some(relation) = &statement.relation {
+                relations.push((id, relation));
+            }
+        }
+        for (id, relation) in relations {
+            for role_player in &relation.role_players {
+                let role = match &role_player.role_type {
+                    Some(TypeReference::Label(label)) => label.name.clone(),
+                    _ => return Err(GraphError::Parse(format!("role player without a role: {}", relation))),
+                };
+                let player = match &role_player.player {
+                    ConceptVariable::Named(name) => variables.get(name).copied(),
+                    _ => None,
+                };
+                let player = player.ok_or_else(|| invalid(format!("undefined role player {}", role_player.player)))?;
+                staged.things.get_mut(&id).unwrap().role_players.push((role, player));
+            }
+            staged.validate(&staged.things[&id])?;
+        }
+        let inserted = staged.things.len() - self.things.len();
+        *self = staged;
+        Ok(inserted)
+    }
+}
+
+fn accepts(value_type: ValueType, value: &Constant) -> bool {
+    matches!(
+        (value_type, value),
+        (ValueType::Boolean, Constant::Boolean(_))
+            | (ValueType::DateTime, Constant::DateTime(_))
+            | (ValueType::Double, Constant::Double(_) | Constant::Long(_))
+            | (ValueType::Long, Constant::Long(_))
+            | (ValueType::String, Constant::String(_))
+    )
+}
+// end::graph[]
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn iam_graph() -> Graph {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut graph = Graph::new(Schema::from_file(dir.join("iam-schema.tql")).unwrap());
+        graph.load(&std::fs::read_to_string(dir.join("iam-data-single-query.tql")).unwrap()).unwrap();
+        graph
+    }
+
+    #[test]
+    fn loads_iam_dataset() {
+        let graph = iam_graph();
+        assert_eq!(graph.instances("person").count(), 3);
+        assert_eq!(graph.instances("user").count(), 3);
+        assert_eq!(graph.instances("file").count(), 10);
+        assert_eq!(graph.instances("action").count(), 2);
+        let (kevin, _) = graph.instances("person").find(|(_, p)| p.has_string("full-name", "Kevin Morrison")).unwrap();
+        assert_eq!(graph.relations("permission", "subject", kevin).count(), 10);
+    }
+
+    #[test]
+    fn rejects_data_that_does_not_match_the_schema() {
+        let mut graph = iam_graph();
+        let before = graph.things.len();
+        assert!(matches!(graph.load("insert $u isa user, has full-name \"x\";"), Err(GraphError::Invalid(_))));
+        assert!(matches!(graph.load("insert $f isa file, has email \"x\";"), Err(GraphError::Invalid(_))));
+        assert!(matches!(graph.load("insert $f isa file, has size-kb \"big\";"), Err(GraphError::Invalid(_))));
+        assert!(matches!(
+            graph.load("insert $f isa file; $a (object: $f, action: $missing) isa access;"),
+            Err(GraphError::Invalid(_))
+        ));
+        assert_eq!(graph.things.len(), before);
+    }
+
+    #[test]
+    fn attributes_inherit_their_value_type() {
+        let schema = "define name sub attribute, abstract, value string; nickname sub name; \
+            person sub entity, owns nickname;";
+        let mut graph = Graph::new(Schema::parse(schema).unwrap());
+        assert_eq!(graph.load("insert $p isa person, has nickname \"Kev\";").unwrap(), 1);
+        assert!(matches!(graph.load("insert $p isa person, has nickname 3;"), Err(GraphError::Invalid(_))));
+    }
+
+    #[test]
+    fn removing_a_player_removes_emptied_relations() {
+        let mut graph = iam_graph();
+        let (file, _) = graph.instances("file").find(|(_, f)| f.has_string("path", "iopvu.java")).unwrap();
+        let accesses = graph.relations("access", "object", file).count();
+        assert_eq!(accesses, 2);
+        let before = graph.things.len();
+        graph.remove(file);
+        assert_eq!(graph.relations("access", "object", file).count(), 0);
+        // The accesses keep their action, so only the file itself is gone.
+        assert_eq!(graph.things.len(), before - 1);
+    }
+}
@@ -0,0 +1,671 @@
+// tag::import[]
+//! Imports CSV and JSON exports of source systems, such as HR and asset inventories, into the IAM schema. A
+//! mapping file says which things every row inserts and which columns become their attributes and role players:
+//!
+//! ```toml
+//! [[insert]]
+//! name = "access"
+//! type = "access"
+//! roles.object = { type = "file", attribute = "path", column = "path" }
+//! roles.action = { type = "operation", attribute = "name", column = "action" }
+//!
+//! [[insert]]
+//! type = "permission"
+//! attributes = { validity = "valid" }
+//! roles.subject = { type = "person", attribute = "email", column = "email" }
+//! roles.access = { thing = "access" }
+//! ```
+//!
+//! A role player is either a thing inserted from the same row, named by `thing`, or looked up by the value of one
+//! of its attributes: a thing inserted by an earlier row, or otherwise one already in the database. Rows that
+//! cannot be imported, e.g. because of a value that does not fit its attribute type or a player that does not
+//! exist, are reported and skipped. The other rows are turned into statements for the [bulk loader](crate::loader).
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use typedb_driver::{Connection, SessionType, TransactionType};
+
+use crate::{
+    database,
+    error::AppError,
+    loader::{self, LoadOptions, Progress, Statement},
+    query::{self, Literal},
+    schema::{Kind, Schema, ValueType},
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// The format of a file with the extension `.csv` or `.json`.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Mapping {
+    /// The format of the data, instead of the one its file extension says.
+    pub format: Option<Format>,
+    #[serde(rename = "insert", default)]
+    pub inserts: Vec<Insert>,
+}
+
+/// A thing that every row inserts.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Insert {
+    /// The name that later inserts of the same row refer to the thing by [default: its type].
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// The column that each attribute takes its value from. An empty cell leaves the attribute out.
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// The player of each role, for relation types.
+    #[serde(default)]
+    pub roles: BTreeMap<String, Player>,
+}
+
+impl Insert {
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.type_)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Player {
+    /// A thing inserted from the same row.
+    Thing { thing: String },
+    /// The thing of the type that owns the attribute with the value in the column.
+    Lookup {
+        #[serde(rename = "type")]
+        type_: String,
+        attribute: String,
+        column: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The mapping cannot be read or does not fit the schema.
+    Mapping { path: PathBuf, message: String },
+    /// The data is not well-formed CSV or JSON.
+    Source { file: String, message: String },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Mapping { path, message } => write!(f, "Invalid mapping {}: {}", path.display(), message),
+            ImportError::Source { file, message } => write!(f, "Failed to read {}: {}", file, message),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+impl Mapping {
+    pub fn from_file(path: &Path) -> Result<Self, ImportError> {
+        let error = |message: String| ImportError::Mapping { path: path.to_owned(), message };
+        let text = fs::read_to_string(path).map_err(|source| error(source.to_string()))?;
+        let mapping: Mapping = toml::from_str(&text).map_err(|source| error(source.to_string()))?;
+        mapping.validate(path, &database::IAM_SEED.parse_schema().expect("the IAM schema parses"))?;
+        Ok(mapping)
+    }
+
+    /// Checks that every insert is of a concrete type owning its attributes and relating its roles, and that every
+    /// player can be found.
+    pub fn validate(&self, path: &Path, schema: &Schema) -> Result<(), ImportError> {
+        let error = |message: String| Err(ImportError::Mapping { path: path.to_owned(), message });
+        if self.inserts.is_empty() {
+            return error("no [[insert]] entries".to_owned());
+        }
+        for (index, insert) in self.inserts.iter().enumerate() {
+            let type_ = &insert.type_;
+            let kind = schema.kind(type_);
+            if !matches!(kind, Some(Kind::Entity | Kind::Relation)) {
+                return error(format!("{} is not an entity or relation type", type_));
+            }
+            if schema.get(type_).is_some_and(|type_def| type_def.is_abstract) {
+                return error(format!("{} is abstract", type_));
+            }
+            if let Some(attribute) = insert.attributes.keys().find(|attribute| !schema.owns(type_, attribute)) {
+                return error(format!("{} does not own {}", type_, attribute));
+            }
+            if kind == Some(Kind::Relation) && insert.roles.is_empty() {
+                return error(format!("relation {} needs roles", type_));
+            }
+            if kind == Some(Kind::Entity) && !insert.roles.is_empty() {
+                return error(format!("entity {} has no roles", type_));
+            }
+            for (role, player) in &insert.roles {
+                if !schema.relates(type_, role) {
+                    return error(format!("{} does not relate {}", type_, role));
+                }
+                match player {
+                    Player::Thing { thing } => {
+                        if !self.inserts[..index].iter().any(|earlier| earlier.name() == thing) {
+                            return error(format!(
+                                "role {} of {} refers to {}, which no earlier insert is",
+                                role, type_, thing
+                            ));
+                        }
+                    }
+                    Player::Lookup { type_: player, attribute, .. } => {
+                        let owned = schema.types().any(|type_def| {
+                            schema.is_subtype(&type_def.label, player) && schema.owns(&type_def.label, attribute)
+                        });
+                        if !owned {
+                            return error(format!("no {} owns {}", player, attribute));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record of the data: a CSV line or an object of a JSON array, by column.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Row {
+    /// The line of the CSV file, or the position in the JSON array counting from 1.
+    pub number: usize,
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejected {
+    pub row: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}: {}", self.row, self.reason)
+    }
+}
+
+/// Splits CSV text into records of fields, with the line each starts on. Fields may be quoted, with `""` for a
+/// quote, and quoted fields may span lines.
+fn csv_records(file: &str, text: &str) -> Result<Vec<(usize, Vec<String>)>, ImportError> {
+    let mut records = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut line, mut start, mut quoted, mut any) = (1, 1, false, false);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => (),
+            (false, '\n') => {
+                line += 1;
+                if any || !field.is_empty() || !record.is_empty() {
+                    record.push(std::mem::take(&mut field));
+                    records.push((start, std::mem::take(&mut record)));
+                }
+                (start, any) = (line, false);
+                continue;
+            }
+            (true, '\n') => {
+                line += 1;
+                field.push(c);
+            }
+            _ => field.push(c),
+        }
+        any = true;
+    }
+    if quoted {
+        return Err(ImportError::Source {
+            file: file.to_owned(),
+            message: format!("unterminated quote in line {}", start),
+        });
+    }
+    if any {
+        record.push(field);
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+/// Reads the rows of CSV with a header line or of a JSON array of objects. Records that do not fit the header, or
+/// array items that are not objects, are rejected.
+pub fn read_rows(file: &str, text: &str, format: Format) -> Result<(Vec<Row>, Vec<Rejected>), ImportError> {
+    let (mut rows, mut rejected) = (Vec::new(), Vec::new());
+    match format {
+        Format::Csv => {
+            let mut records = csv_records(file, text)?.into_iter();
+            let Some((_, header)) = records.next() else { return Ok((rows, rejected)) };
+            let header = header.iter().map(|column| column.trim().to_owned()).collect::<Vec<_>>();
+            for (number, fields) in records {
+                if fields.len() != header.len() {
+                    let reason = format!("{} fields where the header has {}", fields.len(), header.len());
+                    rejected.push(Rejected { row: number, reason });
+                    continue;
+                }
+                rows.push(Row { number, values: header.iter().cloned().zip(fields).collect() });
+            }
+        }
+        Format::Json => {
+            let json: serde_json::Value = serde_json::from_str(text)
+                .map_err(|error| ImportError::Source { file: file.to_owned(), message: error.to_string() })?;
+            let serde_json::Value::Array(items) = json else {
+                return Err(ImportError::Source { file: file.to_owned(), message: "expected an array".to_owned() });
+            };
+            for (index, item) in items.into_iter().enumerate() {
+                let serde_json::Value::Object(object) = item else {
+                    rejected.push(Rejected { row: index + 1, reason: "not an object".to_owned() });
+                    continue;
+                };
+                let values = object
+                    .into_iter()
+                    .filter_map(|(column, value)| match value {
+                        serde_json::Value::Null => None,
+                        serde_json::Value::String(value) => Some((column, value)),
+                        value => Some((column, value.to_string())),
+                    })
+                    .collect();
+                rows.push(Row { number: index + 1, values });
+            }
+        }
+    }
+    Ok((rows, rejected))
+}
+
+/// The value of a cell as a literal of the attribute's value type, rejecting strings that TypeQL cannot represent.
+fn literal(schema: &Schema, attribute: &str, value: &str) -> Result<Literal, String> {
+    let invalid = |expected: &str| format!("{:?} is not a {} value for {}", value, expected, attribute);
+    Ok(match schema.value_type(attribute) {
+        Some(ValueType::Long) => Literal::Long(value.parse().map_err(|_| invalid("long"))?),
+        Some(ValueType::Double) => Literal::Double(value.parse().map_err(|_| invalid("double"))?),
+        Some(ValueType::Boolean) => match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Literal::Boolean(true),
+            "false" | "no" | "0" => Literal::Boolean(false),
+            _ => return Err(invalid("boolean")),
+        },
+        Some(ValueType::DateTime) => Literal::DateTime(
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+                })
+                .map_err(|_| invalid("datetime"))?,
+        ),
+        Some(ValueType::String) | None => {
+            query::quote_string(value).map_err(|error| error.to_string())?;
+            Literal::String(value.to_owned())
+        }
+    })
+}
+
+/// What an import produced from the rows of a file.
+#[derive(Debug, Default)]
+pub struct Generated {
+    /// The statements of the imported rows, preceded by those matching the things already in the database.
+    pub statements: Vec<Statement>,
+    pub imported: usize,
+    pub rejected: Vec<Rejected>,
+}
+
+/// Turns rows into statements, resolving role players to the things inserted by earlier rows or, through
+/// `exists`, to things in the database. `exists` is asked once for each pattern such as
+/// `$x isa person, has email "a@b.c"`.
+pub struct Generator<'a> {
+    schema: &'a Schema,
+    mapping: &'a Mapping,
+    exists: &'a mut dyn FnMut(&str) -> Result<bool, AppError>,
+    /// The variables of the things inserted so far, by type or supertype, attribute and value.
+    inserted: HashMap<(String, String, String), String>,
+    /// The variables of the things in the database that were found, or `None` for those that were not.
+    found: HashMap<(String, String, String), Option<String>>,
+    next_var: usize,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(
+        schema: &'a Schema,
+        mapping: &'a Mapping,
+        exists: &'a mut dyn FnMut(&str) -> Result<bool, AppError>,
+    ) -> Self {
+        Self { schema, mapping, exists, inserted: HashMap::new(), found: HashMap::new(), next_var: 0 }
+    }
+
+    fn var(&mut self, type_: &str) -> String {
+        self.next_var += 1;
+        format!("{}-{}", type_, self.next_var)
+    }
+
+    fn cell<'r>(row: &'r Row, column: &str) -> Option<&'r str> {
+        row.values.get(column).map(|value| value.trim()).filter(|value| !value.is_empty())
+    }
+
+    /// The variable of the player looked up by an attribute value, with the statement that matches it when it is
+    /// in the database and was not needed before. Players found for the current row are added to `row_found`,
+    /// and only become known to later rows once the row is accepted, as the row's statements bind them.
+    fn lookup(
+        &mut self,
+        type_: &str,
+        attribute: &str,
+        value: &Literal,
+        row_found: &mut HashMap<(String, String, String), String>,
+    ) -> Result<Result<(String, Option<Statement>), String>, AppError> {
+        let key = (type_.to_owned(), attribute.to_owned(), value.to_string());
+        if let Some(var) = self.inserted.get(&key).or_else(|| row_found.get(&key)) {
+            return Ok(Ok((var.clone(), None)));
+        }
+        let missing = || format!("no {} with {} {}", type_, attribute, value);
+        if let Some(found) = self.found.get(&key) {
+            return Ok(found.clone().map(|var| (var, None)).ok_or_else(missing));
+        }
+        let var = self.var(type_);
+        let pattern = format!("${} isa {}, has {} {}", var, type_, attribute, value.to_typeql()?);
+        if (self.exists)(&pattern)? {
+            row_found.insert(key, var.clone());
+            Ok(Ok((var, Some(Statement::existing(pattern)))))
+        } else {
+            self.found.insert(key, None);
+            Ok(Err(missing()))
+        }
+    }
+
+    /// The statements inserting the things of one row, or why the row is rejected.
+    fn row(&mut self, row: &Row) -> Result<Result<Vec<Statement>, String>, AppError> {
+        let mut statements = Vec::new();
+        let mut names = HashMap::new();
+        let mut registered = Vec::new();
+        let mut found = HashMap::new();
+        for insert in &self.mapping.inserts {
+            let mut has = Vec::new();
+            for (attribute, column) in &insert.attributes {
+                if let Some(value) = Self::cell(row, column) {
+                    match literal(self.schema, attribute, value) {
+                        Ok(literal) => has.push((attribute.as_str(), literal)),
+                        Err(reason) => return Ok(Err(format!("column {}: {}", column, reason))),
+                    }
+                }
+            }
+            let mut players = Vec::new();
+            for (role, player) in &insert.roles {
+                let var = match player {
+                    Player::Thing { thing } => names.get(thing.as_str()).cloned().expect("validated mapping"),
+                    Player::Lookup { type_, attribute, column } => {
+                        let Some(value) = Self::cell(row, column) else {
+                            return Ok(Err(format!("column {}: no value for role {}", column, role)));
+                        };
+                        let value = match literal(self.schema, attribute, value) {
+                            Ok(value) => value,
+                            Err(reason) => return Ok(Err(format!("column {}: {}", column, reason))),
+                        };
+                        match self.lookup(type_, attribute, &value, &mut found)? {
+                            Ok((var, existing)) => {
+                                statements.extend(existing);
+                                var
+                            }
+                            Err(reason) => return Ok(Err(reason)),
+                        }
+                    }
+                };
+                players.push(format!("{}: ${}", role, var));
+            }
+            if has.is_empty() && players.is_empty() {
+                return Ok(Err(format!("no values for {}", insert.type_)));
+            }
+            let var = self.var(&insert.type_);
+            let mut text = format!("${}", var);
+            if !players.is_empty() {
+                text.push_str(&format!(" ({})", players.join(", ")));
+            }
+            text.push_str(&format!(" isa {}", insert.type_));
+            for (attribute, value) in &has {
+                text.push_str(&format!(", has {} {}", attribute, value.to_typeql()?));
+                for type_ in self.schema.supertypes(&insert.type_) {
+                    registered.push(((type_.to_owned(), attribute.to_string(), value.to_string()), var.clone()));
+                }
+            }
+            statements.push(Statement::new(text, row.number));
+            names.insert(insert.name(), var);
+        }
+        for (key, var) in registered {
+            self.inserted.entry(key).or_insert(var);
+        }
+        self.found.extend(found.into_iter().map(|(key, var)| (key, Some(var))));
+        Ok(Ok(statements))
+    }
+
+    /// Generates the statements of all rows, adding the rows it rejects to `rejected`.
+    pub fn generate(&mut self, rows: &[Row], rejected: Vec<Rejected>) -> Result<Generated, AppError> {
+        let mut generated = Generated { rejected, ..Generated::default() };
+        for row in rows {
+            match self.row(row)? {
+                Ok(statements) => {
+                    generated.statements.extend(statements);
+                    generated.imported += 1;
+                }
+                Err(reason) => generated.rejected.push(Rejected { row: row.number, reason }),
+            }
+        }
+        generated.rejected.sort_by_key(|rejected| rejected.row);
+        Ok(generated)
+    }
+}
+
+/// Reads a data file in the format of its mapping or extension, and generates its statements.
+pub fn generate(
+    mapping: &Mapping,
+    path: &Path,
+    exists: &mut dyn FnMut(&str) -> Result<bool, AppError>,
+) -> Result<Generated, AppError> {
+    let file = path.display().to_string();
+    let format = mapping.format.or_else(|| Format::from_extension(path)).ok_or_else(|| ImportError::Source {
+        file: file.clone(),
+        message: "unknown format; name the file .csv or .json, or set format in the mapping".to_owned(),
+    })?;
+    let text = fs::read_to_string(path)?;
+    let (rows, rejected) = read_rows(&file, &text, format)?;
+    let schema = database::IAM_SEED.parse_schema()?;
+    Generator::new(&schema, mapping, exists).generate(&rows, rejected)
+}
+
+/// Imports a data file into the database with the bulk loader, looking up the players that earlier rows did not
+/// insert in the database. Returns the rows imported and rejected with the final progress of the load.
+pub fn import(
+    driver: &Connection,
+    db_name: &str,
+    mapping: &Mapping,
+    path: &Path,
+    options: &LoadOptions,
+    progress: &(dyn Fn(&Progress) + Sync),
+) -> Result<(Generated, Progress), AppError> {
+    let generated = {
+        let session = database::session(driver, db_name, SessionType::Data)?;
+        let tx = session.transaction(TransactionType::Read)?;
+        let mut exists = |pattern: &str| -> Result<bool, AppError> {
+            Ok(tx.query().get(&format!("match {}; get; limit 1;", pattern))?.next().transpose()?.is_some())
+        };
+        generate(mapping, path, &mut exists)?
+    };
+    let file = path.display().to_string();
+    let statements = generated.statements.iter().cloned().map(Ok);
+    let progress = loader::load_statements(driver, db_name, &file, statements, options, progress)?;
+    Ok((generated, progress))
+}
+// end::import[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IAM_DATA, IAM_SCHEMA};
+
+    fn mapping(text: &str) -> Result<Mapping, ImportError> {
+        let mapping: Mapping = toml::from_str(text).unwrap();
+        mapping.validate(Path::new("mapping.toml"), &Schema::parse(IAM_SCHEMA).unwrap())?;
+        Ok(mapping)
+    }
+
+    fn texts(generated: &Generated) -> Vec<(bool, &str)> {
+        generated.statements.iter().map(|statement| (statement.existing, statement.text.as_str())).collect()
+    }
+
+    #[test]
+    fn reads_quoted_csv_and_json_rows() {
+        let csv = "Name,Email\n\"Ravi \"\"RJ\"\" Joshi\",ravi@typedb.com\r\n\"Two\nlines\",x\nonly one field\n";
+        let (rows, rejected) = read_rows("hr.csv", csv, Format::Csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].values["Name"], "Ravi \"RJ\" Joshi");
+        assert_eq!((rows[1].number, rows[1].values["Name"].as_str()), (3, "Two\nlines"));
+        assert_eq!(rejected, [Rejected { row: 5, reason: "1 fields where the header has 2".to_owned() }]);
+        assert!(read_rows("hr.csv", "a\n\"open", Format::Csv).is_err());
+
+        let json = r#"[{"path": "a.txt", "size": 55, "owner": null}, 7]"#;
+        let (rows, rejected) = read_rows("assets.json", json, Format::Json).unwrap();
+        assert_eq!(
+            rows[0].values,
+            HashMap::from([("path".to_owned(), "a.txt".to_owned()), ("size".to_owned(), "55".to_owned())])
+        );
+        assert_eq!(rejected, [Rejected { row: 2, reason: "not an object".to_owned() }]);
+    }
+
+    #[test]
+    fn validates_mappings_against_the_schema() {
+        assert!(mapping("[[insert]]\ntype = \"person\"\nattributes = { email = \"Email\" }").is_ok());
+        for invalid in [
+            "[[insert]]\ntype = \"user\"",
+            "[[insert]]\ntype = \"person\"\nattributes = { path = \"Path\" }",
+            "[[insert]]\ntype = \"access\"\nroles.subject = { thing = \"x\" }",
+            "[[insert]]\ntype = \"access\"\nroles.object = { thing = \"file\" }",
+            "[[insert]]\ntype = \"access\"\nroles.object = { type = \"object\", attribute = \"email\", column = \"c\" }",
+        ] {
+            assert!(mapping(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn generates_statements_and_rejects_rows() {
+        let mapping = mapping(
+            "[[insert]]\ntype = \"file\"\nattributes = { path = \"path\", size-kb = \"size\" }\n\n\
+             [[insert]]\nname = \"access\"\ntype = \"access\"\n\
+             roles.object = { thing = \"file\" }\n\
+             roles.action = { type = \"operation\", attribute = \"name\", column = \"action\" }\n\n\
+             [[insert]]\ntype = \"permission\"\nattributes = { validity = \"valid\" }\n\
+             roles.subject = { type = \"person\", attribute = \"email\", column = \"email\" }\n\
+             roles.access = { thing = \"access\" }",
+        )
+        .unwrap();
+        let csv = "path,size,action,email,valid\n\
+                   a.txt,5,view_file,kevin@typedb.com,yes\n\
+                   b.txt,big,view_file,kevin@typedb.com,yes\n\
+                   c.txt,7,view_file,nobody@typedb.com,\n\
+                   d.txt,,view_file,kevin@typedb.com,no\n";
+        let (rows, rejected) = read_rows("files.csv", csv, Format::Csv).unwrap();
+        let schema = Schema::parse(IAM_SCHEMA).unwrap();
+        let mut asked = Vec::new();
+        let mut exists = |pattern: &str| {
+            asked.push(pattern.to_owned());
+            Ok(!pattern.contains("nobody"))
+        };
+        let generated = Generator::new(&schema, &mapping, &mut exists).generate(&rows, rejected).unwrap();
+        assert_eq!(generated.imported, 2);
+        assert_eq!(
+            generated.rejected,
+            [
+                Rejected { row: 3, reason: "column size: \"big\" is not a long value for size-kb".to_owned() },
+                Rejected { row: 4, reason: "no person with email \"nobody@typedb.com\"".to_owned() },
+            ]
+        );
+        assert_eq!(
+            texts(&generated)[..5],
+            [
+                (false, "$file-1 isa file, has path \"a.txt\", has size-kb 5"),
+                (true, "$operation-2 isa operation, has name \"view_file\""),
+                (false, "$access-3 (action: $operation-2, object: $file-1) isa access"),
+                (true, "$person-4 isa person, has email \"kevin@typedb.com\""),
+                (false, "$permission-5 (access: $access-3, subject: $person-4) isa permission, has validity true"),
+            ]
+        );
+        assert_eq!(texts(&generated)[5], (false, "$file-9 isa file, has path \"d.txt\""));
+        assert_eq!(asked.len(), 3);
+    }
+
+    #[test]
+    fn rejected_rows_leave_no_lookups_behind() {
+        let mapping = mapping(
+            "[[insert]]\nname = \"access\"\ntype = \"access\"\n\
+             roles.object = { type = \"file\", attribute = \"path\", column = \"path\" }\n\
+             roles.action = { type = \"operation\", attribute = \"name\", column = \"action\" }\n\n\
+             [[insert]]\ntype = \"permission\"\n\
+             roles.subject = { type = \"person\", attribute = \"email\", column = \"email\" }\n\
+             roles.access = { thing = \"access\" }",
+        )
+        .unwrap();
+        let csv = "email,path,action\nnobody@x.com,a.txt,view_file\nkevin@x.com,a.txt,view_file\n";
+        let (rows, rejected) = read_rows("f.csv", csv, Format::Csv).unwrap();
+        let schema = Schema::parse(IAM_SCHEMA).unwrap();
+        let mut exists = |pattern: &str| Ok(!pattern.contains("nobody"));
+        let generated = Generator::new(&schema, &mapping, &mut exists).generate(&rows, rejected).unwrap();
+        assert_eq!(generated.imported, 1);
+        assert_eq!(
+            generated.rejected,
+            [Rejected { row: 2, reason: "no person with email \"nobody@x.com\"".to_owned() }]
+        );
+        assert_eq!(
+            texts(&generated),
+            [
+                (true, "$operation-5 isa operation, has name \"view_file\""),
+                (true, "$file-6 isa file, has path \"a.txt\""),
+                (false, "$access-7 (action: $operation-5, object: $file-6) isa access"),
+                (true, "$person-8 isa person, has email \"kevin@x.com\""),
+                (false, "$permission-9 (access: $access-7, subject: $person-8) isa permission"),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_the_examples_in_turn() {
+        let schema = Schema::parse(IAM_SCHEMA).unwrap();
+        let examples = [
+            (include_str!("../import/hr.toml"), include_str!("../import/hr.csv"), Format::Csv),
+            (include_str!("../import/assets.toml"), include_str!("../import/assets.json"), Format::Json),
+            (include_str!("../import/permissions.toml"), include_str!("../import/permissions.csv"), Format::Csv),
+        ];
+        let mut inserted = vec![IAM_DATA.to_owned()];
+        let mut results = Vec::new();
+        for (mapping, data, format) in examples {
+            let mapping = self::mapping(mapping).unwrap();
+            assert_eq!(mapping.format.unwrap_or(format), format);
+            let (rows, rejected) = read_rows("data", data, format).unwrap();
+            let database = inserted.clone();
+            let mut exists = |pattern: &str| {
+                let has = &pattern[pattern.find(", ").unwrap() + 2..];
+                Ok(database.iter().any(|text| text.contains(has)))
+            };
+            let generated = Generator::new(&schema, &mapping, &mut exists).generate(&rows, rejected).unwrap();
+            inserted.extend(generated.statements.iter().filter(|s| !s.existing).map(|s| s.text.clone()));
+            results.push((generated.imported, generated.rejected.iter().map(|r| r.row).collect::<Vec<_>>()));
+        }
+        assert_eq!(results, [(2, vec![4]), (2, vec![3]), (2, vec![4])]);
+        assert!(inserted.iter().any(|text| text.contains("has review-date 2024-03-01T09:30:00")));
+        assert!(inserted.iter().any(|text| text.contains(r#"has full-name 'Carla "CJ" Jones'"#)));
+    }
+}
@@ -0,0 +1,129 @@
+// tag::introspect[]
+//! Reads the schema of a live database through the concept API into a [`Schema`], so that it can be compared with
+//! a schema file.
+use std::collections::BTreeSet;
+
+use typedb_driver::{
+    concept::{Annotation, AttributeType, RelationType, RoleType, Transitivity, ValueType as DriverValueType},
+    transaction::concept::api::{AttributeTypeAPI, EntityTypeAPI, RelationTypeAPI, ThingTypeAPI},
+    Connection, Promise, SessionType, Transaction, TransactionType,
+};
+
+use crate::{
+    database,
+    error::AppError,
+    schema::{Owns, Plays, Relates, Schema, TypeDef, ValueType},
+};
+
+/// Reads every entity, relation and attribute type below the root types, with what each declares itself, and
+/// every rule.
+pub fn live_schema(driver: &Connection, db_name: &str) -> Result<Schema, AppError> {
+    let session = database::session(driver, db_name, SessionType::Schema)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    let mut schema = Schema::default();
+
+    let entity = root(tx.concept().get_entity_type("entity".to_owned()).resolve()?, "entity")?;
+    for entity_type in entity.get_subtypes(&tx, Transitivity::Transitive)? {
+        let entity_type = entity_type?;
+        if entity_type.is_root() {
+            continue;
+        }
+        let supertype = entity_type.get_supertype(&tx).resolve()?.map(|supertype| supertype.label);
+        schema.add_type(thing_type(&tx, &entity_type, supertype)?);
+    }
+
+    let relation = root(tx.concept().get_relation_type("relation".to_owned()).resolve()?, "relation")?;
+    for relation_type in relation.get_subtypes(&tx, Transitivity::Transitive)? {
+        let relation_type = relation_type?;
+        if relation_type.is_root() {
+            continue;
+        }
+        let supertype = relation_type.get_supertype(&tx).resolve()?.map(|supertype| supertype.label);
+        let mut type_def = thing_type(&tx, &relation_type, supertype)?;
+        type_def.relates = relates(&tx, &relation_type)?;
+        schema.add_type(type_def);
+    }
+
+    let attribute = root(tx.concept().get_attribute_type("attribute".to_owned()).resolve()?, "attribute")?;
+    for attribute_type in attribute.get_subtypes(&tx, Transitivity::Transitive)? {
+        let attribute_type = attribute_type?;
+        if attribute_type.is_root() {
+            continue;
+        }
+        let supertype = attribute_type.get_supertype(&tx).resolve()?.map(|supertype| supertype.label);
+        let mut type_def = thing_type(&tx, &attribute_type, supertype)?;
+        type_def.value_type = value_type(attribute_type.value_type());
+        schema.add_type(type_def);
+    }
+
+    for rule in tx.logic().get_rules()? {
+        let rule = rule?;
+        schema.add_rule(typeql::rule(&rule.label).when(rule.when).then(rule.then));
+    }
+    Ok(schema)
+}
+
+fn root<T>(root_type: Option<T>, label: &str) -> Result<T, AppError> {
+    root_type.ok_or_else(|| AppError::NotFound(format!("root type {}", label)))
+}
+
+/// The type with its abstractness and the attributes it owns and roles it plays itself.
+fn thing_type(
+    tx: &Transaction<'_>,
+    thing_type: &impl ThingTypeAPI,
+    supertype: Option<String>,
+) -> Result<TypeDef, AppError> {
+    let mut type_def = TypeDef::new(thing_type.label().to_owned());
+    type_def.supertype = supertype;
+    type_def.is_abstract = thing_type.is_abstract();
+
+    let annotated = |annotation| -> Result<BTreeSet<String>, AppError> {
+        let owned = thing_type.get_owns(tx, None, Transitivity::Explicit, vec![annotation])?;
+        owned.map(|attribute_type| Ok(attribute_type?.label)).collect()
+    };
+    let (keys, unique) = (annotated(Annotation::Key)?, annotated(Annotation::Unique)?);
+    for attribute_type in thing_type.get_owns(tx, None, Transitivity::Explicit, vec![])? {
+        let attribute_type: AttributeType = attribute_type?;
+        let overrides = thing_type.get_owns_overridden(tx, attribute_type.clone()).resolve()?;
+        type_def.owns.push(Owns {
+            key: keys.contains(&attribute_type.label),
+            unique: unique.contains(&attribute_type.label),
+            overrides: overrides.map(|overridden| overridden.label),
+            attribute: attribute_type.label,
+        });
+    }
+
+    for role_type in thing_type.get_plays(tx, Transitivity::Explicit)? {
+        let role_type: RoleType = role_type?;
+        let overrides = thing_type.get_plays_overridden(tx, role_type.clone()).resolve()?;
+        type_def.plays.push(Plays {
+            relation: role_type.label.scope,
+            role: role_type.label.name,
+            overrides: overrides.map(|overridden| overridden.label.name),
+        });
+    }
+    Ok(type_def)
+}
+
+fn relates(tx: &Transaction<'_>, relation_type: &RelationType) -> Result<Vec<Relates>, AppError> {
+    let mut relates = Vec::new();
+    for role_type in relation_type.get_relates(tx, Transitivity::Explicit)? {
+        let role_type = role_type?;
+        let overrides = relation_type.get_relates_overridden(tx, role_type.label.name.clone()).resolve()?;
+        relates
+            .push(Relates { role: role_type.label.name, overrides: overrides.map(|overridden| overridden.label.name) });
+    }
+    Ok(relates)
+}
+
+fn value_type(value_type: DriverValueType) -> Option<ValueType> {
+    match value_type {
+        DriverValueType::Object => None,
+        DriverValueType::Boolean => Some(ValueType::Boolean),
+        DriverValueType::Long => Some(ValueType::Long),
+        DriverValueType::Double => Some(ValueType::Double),
+        DriverValueType::String => Some(ValueType::String),
+        DriverValueType::DateTime => Some(ValueType::DateTime),
+    }
+}
+// end::introspect[]
@@ -0,0 +1,38 @@
+//! The IAM sample shared by the binaries in this workspace: configuration and connection, database lifecycle,
+//! schema loading, the IAM operations over TypeDB or an in-memory graph, and the models they return.
+#[cfg(any(feature = "tokio", test))]
+pub mod asynchronous;
+pub mod backup;
+pub mod codegen;
+pub mod config;
+pub mod connection;
+pub mod database;
+pub mod decision;
+pub mod diagram;
+pub mod error;
+pub mod explanation;
+pub mod graph;
+pub mod import;
+pub mod introspect;
+pub mod lint;
+pub mod loader;
+pub mod migration;
+pub mod models;
+pub mod query;
+pub mod rules;
+pub mod schema;
+pub mod schema_types;
+pub mod store;
+pub mod synthetic;
+
+pub use self::error::AppError;
+
+/// File name of the IAM schema, used in messages about [`IAM_SCHEMA`].
+pub static IAM_SCHEMA_FILE: &str = "iam-schema.tql";
+/// File name of the IAM dataset, used in messages about [`IAM_DATA`].
+pub static IAM_DATA_FILE: &str = "iam-data-single-query.tql";
+
+/// The IAM schema definition.
+pub static IAM_SCHEMA: &str = include_str!("../iam-schema.tql");
+/// The IAM sample dataset, as a single insert query.
+pub static IAM_DATA: &str = include_str!("../iam-data-single-query.tql");
@@ -0,0 +1,390 @@
+// tag::lint[]
+//! Offline checks of `.tql` files before they reach a server. Schema files, holding `define` queries, are parsed
+//! together into a [`Schema`]; their statements and rules, and the data files checked against them, are then
+//! scanned token by token so that every problem is reported at its line and column.
+use std::fmt;
+
+use crate::schema::{strip_keyword, Kind, Schema, SchemaError};
+
+const ROOT_TYPES: &[&str] = &["thing", "entity", "relation", "attribute"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}:{}:{}: {}: {}", self.file, self.line, self.column, severity, self.message)
+    }
+}
+
+/// A `.tql` file to check: its name for diagnostics and its contents.
+#[derive(Clone, Copy, Debug)]
+pub struct Source<'a> {
+    pub file: &'a str,
+    pub text: &'a str,
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// Splits TypeQL into words, variables, labels and punctuation, skipping comments. A string literal is one token.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let (token_line, token_column) = (line, column);
+        let mut end = start + c.len_utf8();
+        let advance = |c: char, line: &mut usize, column: &mut usize| {
+            if c == '\n' {
+                *line += 1;
+                *column = 1;
+            } else {
+                *column += 1;
+            }
+        };
+        advance(c, &mut line, &mut column);
+        if c.is_whitespace() {
+            continue;
+        } else if c == '#' {
+            while let Some(&(_, next)) = chars.peek() {
+                if next == '\n' {
+                    break;
+                }
+                chars.next();
+                advance(next, &mut line, &mut column);
+            }
+            continue;
+        } else if c == '"' || c == '\'' {
+            let mut escaped = false;
+            for (index, next) in chars.by_ref() {
+                advance(next, &mut line, &mut column);
+                end = index + next.len_utf8();
+                if next == c && !escaped {
+                    break;
+                }
+                escaped = next == '\\' && !escaped;
+            }
+        } else if is_word_char(c) {
+            while let Some(&(index, next)) = chars.peek() {
+                if !is_word_char(next) {
+                    break;
+                }
+                chars.next();
+                advance(next, &mut line, &mut column);
+                end = index + next.len_utf8();
+            }
+        }
+        tokens.push(Token { text: &text[start..end], line: token_line, column: token_column });
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '$' | '@' | '!' | ':' | '.')
+}
+
+fn is_label(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_alphabetic())
+}
+
+struct Linter<'a> {
+    schema: &'a Schema,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, file: &str, token: &Token, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: file.to_owned(),
+            line: token.line,
+            column: token.column,
+            severity,
+            message,
+        });
+    }
+
+    fn is_type(&self, label: &str) -> bool {
+        ROOT_TYPES.contains(&label) || self.schema.get(label).is_some()
+    }
+
+    fn is_role(&self, role: &str) -> bool {
+        self.schema.types().any(|type_def| type_def.relates.iter().any(|relates| relates.role == role))
+    }
+
+    /// Whether a type plays the role, or a role overriding it in a subtype of the relation type.
+    fn is_played(&self, relation: &str, role: &str) -> bool {
+        self.schema.types().any(|type_def| {
+            let plays = type_def
+                .plays
+                .iter()
+                .any(|plays| plays.role == role && self.schema.is_subtype(relation, &plays.relation));
+            let overridden = self.schema.is_subtype(&type_def.label, relation)
+                && type_def.relates.iter().any(|relates| relates.overrides.as_deref() == Some(role));
+            plays || overridden
+        })
+    }
+
+    /// Checks the statements of a schema file; rules are checked as patterns.
+    fn check_schema_file(&mut self, source: &Source) {
+        let tokens = tokenize(source.text);
+        let mut statement: Option<&str> = None;
+        let mut index = 0;
+        while index < tokens.len() {
+            let token = &tokens[index];
+            let next = tokens.get(index + 1).map(|token| token.text);
+            match token.text {
+                "define" => (),
+                "rule" => {
+                    let end = rule_end(&tokens, index);
+                    self.check_pattern(source.file, &tokens[index + 1..end], false);
+                    index = end;
+                    statement = None;
+                    continue;
+                }
+                ";" => statement = None,
+                label if statement.is_none() && is_label(label) => statement = Some(label),
+                "sub" => {
+                    if let Some(supertype) = next.filter(|label| !self.is_type(label)) {
+                        let message =
+                            format!("undeclared type {} is the supertype of {}", supertype, statement.unwrap_or("?"));
+                        self.report(source.file, &tokens[index + 1], Severity::Error, message);
+                    }
+                }
+                "owns" => {
+                    if let Some(attribute) = next.filter(|label| self.schema.kind(label) != Some(Kind::Attribute)) {
+                        let message = match self.schema.get(attribute) {
+                            Some(_) => format!(
+                                "{} owns {}, which is not an attribute type",
+                                statement.unwrap_or("?"),
+                                attribute
+                            ),
+                            None => format!("{} owns undefined attribute type {}", statement.unwrap_or("?"), attribute),
+                        };
+                        self.report(source.file, &tokens[index + 1], Severity::Error, message);
+                    }
+                }
+                "plays" => {
+                    if let Some(scoped) = next {
+                        let (relation, role) = scoped.split_once(':').unwrap_or((scoped, ""));
+                        let message = if self.schema.kind(relation) != Some(Kind::Relation) {
+                            Some(format!(
+                                "{} plays a role in undeclared relation type {}",
+                                statement.unwrap_or("?"),
+                                relation
+                            ))
+                        } else if !self.schema.relates(relation, role) {
+                            Some(format!("role {} is played but never related by {}", scoped, relation))
+                        } else {
+                            None
+                        };
+                        if let Some(message) = message {
+                            self.report(source.file, &tokens[index + 1], Severity::Error, message);
+                        }
+                    }
+                }
+                "relates" => {
+                    let relation = statement.unwrap_or("?");
+                    let abstract_relation = self.schema.get(relation).is_some_and(|type_def| type_def.is_abstract);
+                    if let Some(role) = next.filter(|role| !abstract_relation && !self.is_played(relation, role)) {
+                        let message = format!("role {}:{} is related but no type plays it", relation, role);
+                        self.report(source.file, &tokens[index + 1], Severity::Warning, message);
+                    }
+                }
+                "as" => {
+                    let relates = index >= 2 && tokens[index - 2].text == "relates";
+                    let supertype = statement.and_then(|label| self.schema.get(label)?.supertype.as_deref());
+                    if let (true, Some(overridden), Some(supertype)) = (relates, next, supertype) {
+                        if !self.schema.relates(supertype, overridden) {
+                            let message = format!(
+                                "{} overrides role {}, which {} does not relate",
+                                statement.unwrap_or("?"),
+                                overridden,
+                                supertype
+                            );
+                            self.report(source.file, &tokens[index + 1], Severity::Error, message);
+                        }
+                    }
+                }
+                _ => (),
+            }
+            index += 1;
+        }
+    }
+
+    /// Checks the types, attribute types and roles a pattern refers to. In insert patterns, abstract types cannot
+    /// be instantiated.
+    fn check_pattern(&mut self, file: &str, tokens: &[Token], mut inserting: bool) {
+        for (index, token) in tokens.iter().enumerate() {
+            let next = tokens.get(index + 1).filter(|next| is_label(next.text));
+            match token.text {
+                "insert" => inserting = true,
+                "match" | "delete" => inserting = false,
+                "isa" | "isa!" => {
+                    let Some(next) = next else { continue };
+                    if !self.is_type(next.text) {
+                        self.report(file, next, Severity::Error, format!("unknown type {}", next.text));
+                    } else if inserting && self.schema.get(next.text).is_some_and(|type_def| type_def.is_abstract) {
+                        let message = format!("abstract type {} cannot be instantiated", next.text);
+                        self.report(file, next, Severity::Error, message);
+                    }
+                }
+                "has" => {
+                    let Some(next) = next else { continue };
+                    if self.schema.kind(next.text) != Some(Kind::Attribute) {
+                        self.report(file, next, Severity::Error, format!("unknown attribute type {}", next.text));
+                    }
+                }
+                role if role.len() > 1 && role.ends_with(':') && is_label(role) => {
+                    let role = &role[..role.len() - 1];
+                    let player = tokens.get(index + 1).is_some_and(|next| next.text.starts_with('$'));
+                    if player && !self.is_role(role) {
+                        self.report(file, token, Severity::Error, format!("unknown role {}", role));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// The index just past the `;` that ends the rule starting at `start`.
+fn rule_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    let mut then = false;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token.text {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            "then" => then = true,
+            ";" if depth == 0 && then => return index + 1,
+            _ => (),
+        }
+    }
+    tokens.len()
+}
+
+/// The line of a syntax error reported by the `typeql` parser for text that starts `offset` lines into the file.
+fn parse_error(file: &str, offset: usize, message: String) -> Diagnostic {
+    let line = message
+        .split_once("near line ")
+        .and_then(|(_, rest)| rest.split(|c: char| !c.is_ascii_digit()).next()?.parse::<usize>().ok())
+        .map_or(1, |line| line + offset);
+    let message = message.lines().next().unwrap_or_default().to_owned();
+    Diagnostic { file: file.to_owned(), line, column: 1, severity: Severity::Error, message }
+}
+
+/// Checks schema files and data files against the schema they define together. Syntax errors in a schema file
+/// stop the other checks, since the schema is then unknown.
+pub fn lint(schema_files: &[Source], data_files: &[Source]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut bodies = Vec::new();
+    for source in schema_files {
+        let Some(body) = strip_keyword(source.text, "define") else {
+            diagnostics.push(Diagnostic {
+                file: source.file.to_owned(),
+                line: 1,
+                column: 1,
+                severity: Severity::Error,
+                message: "a schema file must hold a define query".to_owned(),
+            });
+            continue;
+        };
+        let offset = source.text[..source.text.len() - body.len()].matches('\n').count();
+        match Schema::parse(body) {
+            Err(SchemaError::Parse(message)) => diagnostics.push(parse_error(source.file, offset, message)),
+            Err(error) => diagnostics.push(parse_error(source.file, offset, error.to_string())),
+            Ok(_) => bodies.push(body),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+    let schema = match Schema::parse(&bodies.join("\n")) {
+        Ok(schema) => schema,
+        Err(error) => return vec![parse_error(schema_files[0].file, 0, error.to_string())],
+    };
+
+    let mut linter = Linter { schema: &schema, diagnostics };
+    for source in schema_files {
+        linter.check_schema_file(source);
+    }
+    for source in data_files {
+        if let Err(error) = typeql::parse_query(source.text) {
+            linter.diagnostics.push(parse_error(source.file, 0, error.to_string()));
+            continue;
+        }
+        linter.check_pattern(source.file, &tokenize(source.text), false);
+    }
+    linter.diagnostics
+}
+// end::lint[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IAM_DATA, IAM_DATA_FILE, IAM_SCHEMA, IAM_SCHEMA_FILE};
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn iam_files_are_clean() {
+        let schema = Source { file: IAM_SCHEMA_FILE, text: IAM_SCHEMA };
+        let data = Source { file: IAM_DATA_FILE, text: IAM_DATA };
+        assert_eq!(messages(&lint(&[schema], &[data])), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_problems_at_their_position() {
+        let schema = "# test schema\ndefine\n\
+            name sub attribute, value string;\n\
+            person sub agent, owns name, owns nickname,\n    plays friendship:buddy;\n\
+            friendship sub relation, relates friend;\n\
+            group sub entity, abstract, owns name;\n\
+            rule named: when { $p isa persn; } then { $p has name \"x\"; };\n";
+        let data = "insert $g isa group, has name \"g\";\n$f (friend: $g, pal: $g) isa friendship;";
+        let diagnostics = lint(&[Source { file: "s.tql", text: schema }], &[Source { file: "d.tql", text: data }]);
+        assert_eq!(
+            messages(&diagnostics),
+            [
+                "s.tql:4:12: error: undeclared type agent is the supertype of person",
+                "s.tql:4:35: error: person owns undefined attribute type nickname",
+                "s.tql:5:11: error: role friendship:buddy is played but never related by friendship",
+                "s.tql:6:34: warning: role friendship:friend is related but no type plays it",
+                "s.tql:8:27: error: unknown type persn",
+                "d.tql:1:15: error: abstract type group cannot be instantiated",
+                "d.tql:2:17: error: unknown role pal",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_the_file_line() {
+        let schema = "# header\n\ndefine\nperson sub entity;\nperson owns ;\n";
+        let diagnostics = lint(&[Source { file: "s.tql", text: schema }], &[]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].severity), (5, Severity::Error));
+    }
+}
@@ -0,0 +1,826 @@
+// tag::loader[]
+//! Bulk loading of insert-only data files such as `iam-data-single-query.tql`. The file is read as a stream of
+//! statements, which are committed in batches by several sessions in parallel, with progress reported after every
+//! commit and an optional checkpoint file to resume from after a failure.
+//!
+//! A statement that refers to a variable bound in an earlier batch matches the thing again with the statements
+//! that inserted it, so those must identify it uniquely, as they do in the IAM dataset. A batch only starts once
+//! the batches it matches from have been committed.
+//!
+//! Any later statement may refer to any variable, so the statement that binds each one is kept until the load
+//! ends: the most recent ones in memory, and older ones spilled to a temporary file, of which a batch only reads
+//! the lines of the variables it refers to. Memory then holds the statements of a bounded number of bindings and an
+//! offset for each spilled one, however long the statements are.
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+use typedb_driver::{Connection, Promise, SessionType, TransactionType};
+
+use crate::{database, error::AppError, schema::strip_keyword};
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// A statement that is not part of an insert query, e.g. a `match` clause.
+    Unsupported {
+        file: String,
+        line: usize,
+        statement: String,
+    },
+    /// A statement refers to a variable that no earlier statement binds with `isa`.
+    UnboundVariable {
+        file: String,
+        line: usize,
+        var: String,
+    },
+    /// The checkpoint file belongs to another file or batch size, or cannot be read.
+    Checkpoint {
+        path: PathBuf,
+        message: String,
+    },
+    /// The temporary files holding the statements that bind variables cannot be written or read.
+    Spill {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Io {
+        file: String,
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Unsupported { file, line, statement } => {
+                write!(f, "{}:{}: only insert statements can be bulk loaded, found: {}", file, line, statement)
+            }
+            LoadError::UnboundVariable { file, line, var } => {
+                write!(f, "{}:{}: ${} is not bound by an earlier statement", file, line, var)
+            }
+            LoadError::Checkpoint { path, message } => write!(f, "Checkpoint {}: {}", path.display(), message),
+            LoadError::Spill { path, source } => write!(f, "Spill file {}: {}", path.display(), source),
+            LoadError::Io { file, source } => write!(f, "Failed to read {}: {}", file, source),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } | LoadError::Spill { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// One statement of an insert query, without its trailing `;`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub text: String,
+    /// The line of the file that the statement starts on.
+    pub line: usize,
+    /// The variable that the statement inserts a thing for with `$x isa ...` or `$x (...) isa ...`.
+    pub binds: Option<String>,
+    /// The other variables the statement refers to.
+    pub uses: Vec<String>,
+    /// Whether the statement matches a thing already in the database instead of inserting one. It only becomes
+    /// part of the batches that refer to its variable.
+    pub existing: bool,
+}
+
+impl Statement {
+    /// A statement matching a thing that is already in the database, such as `$v isa operation, has name "view"`.
+    pub fn existing(text: String) -> Self {
+        Self { existing: true, ..Self::new(text, 0) }
+    }
+
+    pub fn new(text: String, line: usize) -> Self {
+        let mut vars = Vec::new();
+        let mut words = Vec::new();
+        let mut chars = text.char_indices().peekable();
+        let mut quote = None;
+        while let Some((start, c)) = chars.next() {
+            match (quote, c) {
+                (Some(open), _) if c == open => quote = None,
+                (Some(_), '\\') => {
+                    chars.next();
+                }
+                (Some(_), _) => (),
+                (None, '"' | '\'') => quote = Some(c),
+                (None, _) if c == '$' || c.is_alphanumeric() => {
+                    let mut end = start + c.len_utf8();
+                    while let Some(&(index, next)) = chars.peek() {
+                        if !(next.is_alphanumeric() || next == '-' || next == '_' || next == '!') {
+                            break;
+                        }
+                        end = index + next.len_utf8();
+                        chars.next();
+                    }
+                    match text[start..end].strip_prefix('$') {
+                        Some(var) => vars.push(var.to_owned()),
+                        None => words.push(&text[start..end]),
+                    }
+                }
+                (None, _) => (),
+            }
+        }
+        let binds = Some(vars.first().cloned())
+            .filter(|_| text.starts_with('$') && words.iter().any(|word| matches!(*word, "isa" | "isa!")))
+            .flatten();
+        let mut uses = Vec::new();
+        for var in vars {
+            if Some(&var) != binds.as_ref() && !uses.contains(&var) {
+                uses.push(var);
+            }
+        }
+        Self { text, line, binds, uses, existing: false }
+    }
+}
+
+/// Reads the statements of an insert-only file one at a time. Comments are skipped and `insert` keywords dropped.
+pub struct Statements<R> {
+    file: String,
+    reader: R,
+    line: usize,
+    buffer: String,
+    start: Option<usize>,
+    quote: Option<char>,
+    pending: Vec<(String, usize)>,
+}
+
+impl<R: BufRead> Statements<R> {
+    pub fn new(file: impl Into<String>, reader: R) -> Self {
+        Self {
+            file: file.into(),
+            reader,
+            line: 0,
+            buffer: String::new(),
+            start: None,
+            quote: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Scans one line into the buffer, queueing every statement that it completes.
+    fn scan(&mut self, line: &str) {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (self.quote, c) {
+                (None, '#') => {
+                    self.buffer.push('\n');
+                    return;
+                }
+                (None, ';') => {
+                    let text = std::mem::take(&mut self.buffer);
+                    self.pending.push((text, self.start.take().unwrap_or(self.line)));
+                    continue;
+                }
+                (None, '"' | '\'') => self.quote = Some(c),
+                (Some(open), _) if c == open => self.quote = None,
+                (Some(_), '\\') => {
+                    self.buffer.push(c);
+                    if let Some(escaped) = chars.next() {
+                        self.buffer.push(escaped);
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+            if self.start.is_none() && !c.is_whitespace() {
+                self.start = Some(self.line);
+            }
+            self.buffer.push(c);
+        }
+    }
+
+    fn statement(&self, text: String, line: usize) -> Option<Result<Statement, LoadError>> {
+        let (mut text, mut line) = (text.trim(), line);
+        while let Some(rest) = strip_keyword(text, "insert") {
+            let rest = rest.trim_start();
+            line += text[..text.len() - rest.len()].matches('\n').count();
+            text = rest;
+        }
+        if text.is_empty() {
+            return None;
+        }
+        if !text.starts_with(['$', '(']) {
+            let statement = text.lines().next().unwrap_or_default().to_owned();
+            return Some(Err(LoadError::Unsupported { file: self.file.clone(), line, statement }));
+        }
+        Some(Ok(Statement::new(text.to_owned(), line)))
+    }
+}
+
+impl<R: BufRead> Iterator for Statements<R> {
+    type Item = Result<Statement, LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while !self.pending.is_empty() {
+                let (text, line) = self.pending.remove(0);
+                if let Some(statement) = self.statement(text, line) {
+                    return Some(statement);
+                }
+            }
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    let text = std::mem::take(&mut self.buffer);
+                    let start = self.start.take()?;
+                    return self.statement(text, start);
+                }
+                Ok(_) => {
+                    self.line += 1;
+                    self.scan(&line);
+                }
+                Err(source) => return Some(Err(LoadError::Io { file: self.file.clone(), source })),
+            }
+        }
+    }
+}
+
+/// A batch of statements as one `match`-`insert` or `insert` query.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pub index: usize,
+    pub first_line: usize,
+    pub statements: usize,
+    pub query: String,
+    /// The earlier batches that inserted the things this batch matches.
+    pub depends_on: BTreeSet<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Binding {
+    /// The batch that inserts the thing, unless it is already in the database.
+    batch: Option<usize>,
+    text: String,
+    uses: Vec<String>,
+}
+
+static NEXT_SPILL: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file holding one binding per line as JSON, removed when dropped, with the offset of the line of each
+/// variable kept in memory so that a lookup reads only that line. A variable bound again is appended again and its
+/// offset replaced.
+struct Spill {
+    path: PathBuf,
+    writer: BufWriter<fs::File>,
+    reader: fs::File,
+    len: u64,
+    offsets: HashMap<String, u64>,
+}
+
+impl Spill {
+    fn create() -> Result<Self, LoadError> {
+        let name = format!("iam-loader-{}-{}.jsonl", process::id(), NEXT_SPILL.fetch_add(1, Ordering::Relaxed));
+        let path = env::temp_dir().join(name);
+        let error = |source| LoadError::Spill { path: path.clone(), source };
+        let writer = BufWriter::new(fs::File::create(&path).map_err(error)?);
+        let reader = fs::File::open(&path).map_err(error)?;
+        Ok(Self { path, writer, reader, len: 0, offsets: HashMap::new() })
+    }
+
+    fn contains(&self, var: &str) -> bool {
+        self.offsets.contains_key(var)
+    }
+
+    fn write(&mut self, var: &str, binding: &Binding) -> Result<(), LoadError> {
+        let json = serde_json::to_string(&(binding.batch, &binding.uses, &binding.text)).map_err(io::Error::from);
+        json.and_then(|json| writeln!(self.writer, "{}", json).map(|()| json.len() as u64 + 1))
+            .map(|written| {
+                self.offsets.insert(var.to_owned(), self.len);
+                self.len += written;
+            })
+            .map_err(|source| LoadError::Spill { path: self.path.clone(), source })
+    }
+
+    /// Reads the bindings of the spilled variables, in the order of their lines, adding them to `found`.
+    fn read(&mut self, vars: &[&str], found: &mut HashMap<String, Binding>) -> Result<(), LoadError> {
+        let error = |source| LoadError::Spill { path: self.path.clone(), source };
+        self.writer.flush().map_err(error)?;
+        let mut lines = vars.iter().filter_map(|var| Some((self.offsets.get(*var)?, *var))).collect::<Vec<_>>();
+        lines.sort_unstable();
+        let mut line = String::new();
+        for (&offset, var) in lines {
+            self.reader.seek(SeekFrom::Start(offset)).map_err(error)?;
+            line.clear();
+            BufReader::new(&self.reader).read_line(&mut line).map_err(error)?;
+            let (batch, uses, text) = serde_json::from_str(&line).map_err(|source| error(source.into()))?;
+            found.insert(var.to_owned(), Binding { batch, text, uses });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The statement that binds each variable: the most recently bound ones in memory, the others in a [`Spill`].
+struct Bindings {
+    max_in_memory: usize,
+    /// The bindings in memory, with the sequence number of their insertion.
+    recent: HashMap<String, (u64, Binding)>,
+    /// The variables in memory in insertion order, including ones bound again since, whose sequence number differs.
+    order: VecDeque<(String, u64)>,
+    next: u64,
+    spill: Option<Spill>,
+}
+
+impl Bindings {
+    fn new(max_in_memory: usize) -> Self {
+        Self { max_in_memory, recent: HashMap::new(), order: VecDeque::new(), next: 0, spill: None }
+    }
+
+    fn insert(&mut self, var: String, binding: Binding) -> Result<(), LoadError> {
+        self.next += 1;
+        self.order.push_back((var.clone(), self.next));
+        self.recent.insert(var, (self.next, binding));
+        while self.recent.len() > self.max_in_memory {
+            let Some((var, sequence)) = self.order.pop_front() else { break };
+            if self.recent.get(&var).is_some_and(|(current, _)| *current == sequence) {
+                let (_, binding) = self.recent.remove(&var).expect("binding in memory");
+                let spill = match &mut self.spill {
+                    Some(spill) => spill,
+                    None => self.spill.insert(Spill::create()?),
+                };
+                spill.write(&var, &binding)?;
+            }
+        }
+        if self.order.len() > 2 * self.max_in_memory {
+            let recent = &self.recent;
+            self.order.retain(|(var, sequence)| recent.get(var).is_some_and(|(current, _)| current == sequence));
+        }
+        Ok(())
+    }
+
+    /// The bindings of those of the variables that are bound, reading only the lines of the spilled ones.
+    fn get_all<'a>(&mut self, vars: impl IntoIterator<Item = &'a str>) -> Result<HashMap<String, Binding>, LoadError> {
+        let mut found = HashMap::new();
+        let mut spilled = Vec::new();
+        for var in vars {
+            match self.recent.get(var) {
+                Some((_, binding)) => {
+                    found.insert(var.to_owned(), binding.clone());
+                }
+                None if self.spill.as_ref().is_some_and(|spill| spill.contains(var)) => spilled.push(var),
+                None => (),
+            }
+        }
+        if let Some(spill) = &mut self.spill {
+            spill.read(&spilled, &mut found)?;
+        }
+        Ok(found)
+    }
+}
+
+/// Groups statements into batches, remembering the statement that binds each variable so that later batches can
+/// match the thing again. At most [`Planner::MAX_BINDINGS`] of those statements are kept in memory, see the
+/// [module](self) docs.
+pub struct Planner {
+    file: String,
+    batch_size: usize,
+    bindings: Bindings,
+    current: Vec<Statement>,
+    next_index: usize,
+}
+
+impl Planner {
+    /// The number of statements binding variables that a planner keeps in memory unless told otherwise.
+    pub const MAX_BINDINGS: usize = 100_000;
+
+    pub fn new(file: impl Into<String>, batch_size: usize) -> Self {
+        Self {
+            file: file.into(),
+            batch_size: batch_size.max(1),
+            bindings: Bindings::new(Self::MAX_BINDINGS),
+            current: Vec::new(),
+            next_index: 0,
+        }
+    }
+
+    /// Keeps at most `max` statements binding variables in memory, and spills the others to a temporary file.
+    pub fn max_bindings(mut self, max: usize) -> Self {
+        self.bindings.max_in_memory = max.max(1);
+        self
+    }
+
+    /// Adds a statement and returns the batch that it completes.
+    pub fn push(&mut self, statement: Statement) -> Result<Option<Batch>, LoadError> {
+        if statement.existing {
+            if let Some(var) = statement.binds {
+                self.bindings.insert(var, Binding { batch: None, text: statement.text, uses: statement.uses })?;
+            }
+            return Ok(None);
+        }
+        self.current.push(statement);
+        if self.current.len() < self.batch_size {
+            return Ok(None);
+        }
+        self.finish().map(Some)
+    }
+
+    /// The last, partly filled batch.
+    pub fn flush(&mut self) -> Result<Option<Batch>, LoadError> {
+        if self.current.is_empty() {
+            Ok(None)
+        } else {
+            self.finish().map(Some)
+        }
+    }
+
+    fn finish(&mut self) -> Result<Batch, LoadError> {
+        let index = self.next_index;
+        self.next_index += 1;
+        let statements = std::mem::take(&mut self.current);
+        let bound_here = statements.iter().filter_map(|statement| statement.binds.as_deref()).collect::<HashSet<_>>();
+
+        // The bindings of the variables the statements use, then of the variables those use, and so on.
+        let mut wanted = Vec::new();
+        for statement in &statements {
+            for var in statement.uses.iter().filter(|var| !bound_here.contains(var.as_str())) {
+                wanted.push((statement.line, var.clone()));
+            }
+        }
+        let mut resolved = HashMap::<String, Binding>::new();
+        while !wanted.is_empty() {
+            wanted.retain(|(_, var)| !resolved.contains_key(var));
+            let found = self.bindings.get_all(wanted.iter().map(|(_, var)| var.as_str()))?;
+            if let Some((line, var)) = wanted.iter().find(|(_, var)| !found.contains_key(var)) {
+                let (file, line, var) = (self.file.clone(), *line, var.clone());
+                return Err(LoadError::UnboundVariable { file, line, var });
+            }
+            wanted = wanted
+                .iter()
+                .flat_map(|(line, var)| found[var].uses.iter().map(|used| (*line, used.clone())))
+                .collect();
+            resolved.extend(found);
+        }
+        let matched = resolved.into_values().map(|binding| (binding.batch, binding.text)).collect::<BTreeSet<_>>();
+        let depends_on = matched.iter().filter_map(|(batch, _)| *batch).collect::<BTreeSet<_>>();
+
+        let mut query = String::new();
+        if !matched.is_empty() {
+            query.push_str("match\n");
+            for (_, text) in &matched {
+                query.push_str(text);
+                query.push_str(";\n");
+            }
+        }
+        query.push_str("insert\n");
+        for statement in &statements {
+            query.push_str(&statement.text);
+            query.push_str(";\n");
+        }
+        let batch = Batch {
+            index,
+            first_line: statements.first().map_or(0, |statement| statement.line),
+            statements: statements.len(),
+            query,
+            depends_on,
+        };
+        for statement in statements {
+            if let Some(var) = statement.binds {
+                self.bindings
+                    .insert(var, Binding { batch: Some(index), text: statement.text, uses: statement.uses })?;
+            }
+        }
+        Ok(batch)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// The number of statements committed in one transaction.
+    pub batch_size: usize,
+    /// The number of data sessions that run batches in parallel.
+    pub sessions: usize,
+    /// A file recording the committed batches. Loading again with the same file and batch size skips them.
+    pub checkpoint: Option<PathBuf>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self { batch_size: 500, sessions: 4, checkpoint: None }
+    }
+}
+
+/// How far a load has got, reported after every committed batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub batches: usize,
+    pub statements: usize,
+    /// Batches that an earlier run had committed according to the checkpoint.
+    pub skipped: usize,
+}
+
+/// The committed batches recorded in a checkpoint file: a header naming the data file and the batch size, then one
+/// batch index per line.
+struct Checkpoint {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl Checkpoint {
+    fn header(data_file: &str, batch_size: usize) -> String {
+        format!("# checkpoint of {} with batch size {}", data_file, batch_size)
+    }
+
+    /// Opens or creates the checkpoint and returns the batches that it records as committed.
+    fn open(path: &Path, data_file: &str, batch_size: usize) -> Result<(Self, HashSet<usize>), LoadError> {
+        let error = |message: String| LoadError::Checkpoint { path: path.to_owned(), message };
+        let header = Self::header(data_file, batch_size);
+        let mut committed = HashSet::new();
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let mut lines = text.lines();
+                if lines.next() != Some(header.as_str()) {
+                    return Err(error(format!("expected the header \"{}\"", header)));
+                }
+                for line in lines.filter(|line| !line.trim().is_empty()) {
+                    committed.insert(line.trim().parse().map_err(|_| error(format!("invalid batch {:?}", line)))?);
+                }
+            }
+            Err(source) if source.kind() == io::ErrorKind::NotFound => {
+                fs::write(path, format!("{}\n", header)).map_err(|source| error(source.to_string()))?
+            }
+            Err(source) => return Err(error(source.to_string())),
+        }
+        let file = fs::OpenOptions::new().append(true).open(path).map_err(|source| error(source.to_string()))?;
+        Ok((Self { path: path.to_owned(), file }, committed))
+    }
+
+    fn record(&mut self, batch: usize) -> Result<(), LoadError> {
+        writeln!(self.file, "{}", batch)
+            .and_then(|()| self.file.flush())
+            .map_err(|source| LoadError::Checkpoint { path: self.path.clone(), message: source.to_string() })
+    }
+}
+
+struct State {
+    committed: HashSet<usize>,
+    progress: Progress,
+    checkpoint: Option<Checkpoint>,
+    error: Option<AppError>,
+}
+
+/// Runs one batch in its own transaction. A batch whose match finds nothing inserts nothing, which is a conflict.
+fn run_batch(session: &typedb_driver::Session, file: &str, batch: &Batch) -> Result<(), AppError> {
+    let tx = session.transaction(TransactionType::Write)?;
+    let answers = tx.query().insert(&batch.query).and_then(|answers| answers.collect::<Result<Vec<_>, _>>()).map_err(
+        |error| {
+            AppError::schema_load(format!("{} (batch {} from line {})", file, batch.index, batch.first_line), error)
+        },
+    )?;
+    if answers.is_empty() {
+        return Err(AppError::Conflict(format!(
+            "batch {} from line {} of {} matched none of the things it refers to",
+            batch.index, batch.first_line, file
+        )));
+    }
+    Ok(tx.commit().resolve()?)
+}
+
+/// Loads the insert statements read from `reader`, the contents of the file called `file`, and returns the final
+/// progress. `progress` is called after every committed batch.
+pub fn load(
+    driver: &Connection,
+    db_name: &str,
+    file: &str,
+    reader: impl BufRead,
+    options: &LoadOptions,
+    progress: &(dyn Fn(&Progress) + Sync),
+) -> Result<Progress, AppError> {
+    load_statements(driver, db_name, file, Statements::new(file, reader), options, progress)
+}
+
+/// Loads statements produced by other means than reading TypeQL, such as [`crate::import`], in the same way as
+/// [`load`]. `file` names the source in messages and in the checkpoint.
+pub fn load_statements(
+    driver: &Connection,
+    db_name: &str,
+    file: &str,
+    mut statements: impl Iterator<Item = Result<Statement, LoadError>>,
+    options: &LoadOptions,
+    progress: &(dyn Fn(&Progress) + Sync),
+) -> Result<Progress, AppError> {
+    let batch_size = options.batch_size.max(1);
+    let (checkpoint, committed) = match &options.checkpoint {
+        Some(path) => {
+            let (checkpoint, committed) = Checkpoint::open(path, file, batch_size)?;
+            (Some(checkpoint), committed)
+        }
+        None => (None, HashSet::new()),
+    };
+    let state = Mutex::new(State { committed, progress: Progress::default(), checkpoint, error: None });
+    let changed = Condvar::new();
+    let (sender, receiver) = mpsc::sync_channel::<Batch>(options.sessions.max(1) * 2);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let fail = |error: AppError| {
+        let mut state = state.lock().unwrap();
+        state.error.get_or_insert(error);
+        changed.notify_all();
+    };
+    let worker = |receiver: Arc<Mutex<mpsc::Receiver<Batch>>>| {
+        let session = match database::session(driver, db_name, SessionType::Data) {
+            Ok(session) => session,
+            Err(error) => return fail(error),
+        };
+        loop {
+            let Ok(batch) = receiver.lock().unwrap().recv() else { return };
+            {
+                let mut state = state.lock().unwrap();
+                while state.error.is_none() && !batch.depends_on.iter().all(|index| state.committed.contains(index)) {
+                    state = changed.wait(state).unwrap();
+                }
+                if state.error.is_some() {
+                    return;
+                }
+            }
+            if let Err(error) = run_batch(&session, file, &batch) {
+                return fail(error);
+            }
+            let mut state = state.lock().unwrap();
+            state.committed.insert(batch.index);
+            if let Some(Err(error)) = state.checkpoint.as_mut().map(|checkpoint| checkpoint.record(batch.index)) {
+                state.error.get_or_insert(error.into());
+            }
+            state.progress.batches += 1;
+            state.progress.statements += batch.statements;
+            progress(&state.progress);
+            changed.notify_all();
+        }
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..options.sessions.max(1) {
+            let receiver = receiver.clone();
+            scope.spawn(move || worker(receiver));
+        }
+        drop(receiver);
+        let mut planner = Planner::new(file, batch_size);
+        let result = (|| -> Result<(), AppError> {
+            loop {
+                let batch = match statements.next().transpose()? {
+                    Some(statement) => planner.push(statement)?,
+                    None => match planner.flush()? {
+                        Some(batch) => Some(batch),
+                        None => return Ok(()),
+                    },
+                };
+                let Some(batch) = batch else { continue };
+                if state.lock().unwrap().error.is_some() {
+                    return Ok(());
+                }
+                let mut skipped = false;
+                {
+                    let mut state = state.lock().unwrap();
+                    if state.committed.contains(&batch.index) {
+                        state.progress.skipped += 1;
+                        skipped = true;
+                    }
+                }
+                if !skipped && sender.send(batch).is_err() {
+                    return Ok(());
+                }
+            }
+        })();
+        drop(sender);
+        if let Err(error) = result {
+            fail(error);
+        }
+    });
+
+    let state = state.into_inner().unwrap();
+    match state.error {
+        Some(error) => Err(error),
+        None => Ok(state.progress),
+    }
+}
+// end::loader[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IAM_DATA, IAM_DATA_FILE};
+
+    fn statements(text: &str) -> Vec<Statement> {
+        Statements::new("data.tql", text.as_bytes()).collect::<Result<_, _>>().unwrap()
+    }
+
+    fn plan(text: &str, batch_size: usize) -> Result<Vec<Batch>, LoadError> {
+        let mut planner = Planner::new("data.tql", batch_size);
+        let mut batches = Vec::new();
+        for statement in statements(text) {
+            batches.extend(planner.push(statement)?);
+        }
+        batches.extend(planner.flush()?);
+        Ok(batches)
+    }
+
+    #[test]
+    fn reads_statements_with_their_lines_and_variables() {
+        let statements = statements(
+            "# a comment; not a statement\ninsert\n$p isa person,\n    has full-name \"A; B\"; # trailing\n\
+             $m (member: $p, parent: $g) isa membership;\n(subject: $p) isa permission;",
+        );
+        assert_eq!(statements.len(), 3);
+        assert_eq!(statements[0].text, "$p isa person,\n    has full-name \"A; B\"");
+        assert_eq!((statements[0].line, statements[0].binds.as_deref()), (3, Some("p")));
+        assert_eq!((statements[1].line, statements[1].binds.as_deref()), (5, Some("m")));
+        assert_eq!(statements[1].uses, ["p", "g"]);
+        assert_eq!((statements[2].binds.as_deref(), statements[2].uses.as_slice()), (None, &["p".to_owned()][..]));
+        let error = Statements::new("data.tql", "match $p isa person;".as_bytes()).next().unwrap();
+        assert!(matches!(error, Err(LoadError::Unsupported { line: 1, .. })));
+    }
+
+    #[test]
+    fn batches_of_the_iam_dataset_match_what_earlier_batches_inserted() {
+        let all = statements(IAM_DATA);
+        let batches = plan(IAM_DATA, 5).unwrap();
+        assert_eq!(batches.len(), all.len().div_ceil(5));
+        assert_eq!(batches.iter().map(|batch| batch.statements).sum::<usize>(), all.len());
+        assert!(batches[0].depends_on.is_empty() && batches[0].query.starts_with("insert\n$p1 isa person"));
+        for batch in &batches {
+            assert!(typeql::parse_query(&batch.query).is_ok(), "{}", batch.query);
+            assert!(batch.depends_on.iter().all(|index| *index < batch.index));
+        }
+        let permission = batches.iter().find(|batch| batch.query.contains("$permission1 (subject: $p3")).unwrap();
+        assert!(permission.query.starts_with("match\n"));
+        assert!(permission.query.contains("$p3 isa person,\n    has full-name \"Kevin Morrison\""));
+        let whole = plan(IAM_DATA, usize::MAX).unwrap();
+        assert_eq!(whole.len(), 1);
+        assert!(whole[0].depends_on.is_empty());
+        assert!(typeql::parse_query(&whole[0].query).is_ok(), "{}", IAM_DATA_FILE);
+    }
+
+    #[test]
+    fn spilled_bindings_plan_the_same_batches() {
+        let mut planner = Planner::new("data.tql", 5).max_bindings(3);
+        let mut batches = Vec::new();
+        for statement in statements(IAM_DATA) {
+            batches.extend(planner.push(statement).unwrap());
+            assert!(planner.bindings.recent.len() <= 3 && planner.bindings.order.len() <= 6);
+        }
+        batches.extend(planner.flush().unwrap());
+        let spill = planner.bindings.spill.as_ref().unwrap().path.clone();
+        assert!(spill.is_file());
+        let expected = plan(IAM_DATA, 5).unwrap();
+        assert_eq!(batches.len(), expected.len());
+        for (batch, expected) in batches.iter().zip(&expected) {
+            assert_eq!((&batch.query, &batch.depends_on), (&expected.query, &expected.depends_on));
+        }
+        drop(planner);
+        assert!(!spill.exists());
+    }
+
+    #[test]
+    fn existing_things_are_matched_without_dependencies() {
+        let mut planner = Planner::new("data.tql", 10);
+        let operation = "$v isa operation, has name \"view_file\"".to_owned();
+        assert!(planner.push(Statement::existing(operation.clone())).unwrap().is_none());
+        for statement in statements("$f isa file, has path \"a\";\n$a (object: $f, action: $v) isa access;") {
+            assert!(planner.push(statement).unwrap().is_none());
+        }
+        let batch = planner.flush().unwrap().unwrap();
+        assert_eq!(batch.statements, 2);
+        assert!(batch.depends_on.is_empty());
+        assert!(batch.query.starts_with(&format!("match\n{};\ninsert\n", operation)));
+    }
+
+    #[test]
+    fn rejects_variables_bound_later() {
+        let error = plan("insert $m (member: $p) isa membership;\n$p isa person;", 1).unwrap_err();
+        assert!(matches!(error, LoadError::UnboundVariable { line: 1, ref var, .. } if var == "p"));
+    }
+
+    #[test]
+    fn checkpoints_belong_to_one_file_and_batch_size() {
+        let path = std::env::temp_dir().join(format!("iam-loader-checkpoint-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let (mut checkpoint, committed) = Checkpoint::open(&path, "data.tql", 10).unwrap();
+        assert!(committed.is_empty());
+        checkpoint.record(0).unwrap();
+        checkpoint.record(2).unwrap();
+        let (_, committed) = Checkpoint::open(&path, "data.tql", 10).unwrap();
+        assert_eq!(committed, HashSet::from([0, 2]));
+        assert!(matches!(Checkpoint::open(&path, "data.tql", 20), Err(LoadError::Checkpoint { .. })));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,396 @@
+// tag::migration[]
+//! Versioned schema and data migrations. A migration is either a `.tql` file holding one `define` or `undefine`
+//! query, named `<version>_<name>.tql`, or a data migration written in Rust. Each applied migration is recorded in
+//! the database as a `migration` entity, so the database itself knows its version. A data migration is recorded in
+//! the same transaction as its changes; a schema migration is recorded in a data transaction after its schema
+//! transaction commits, since TypeDB does not write data in a schema session.
+use std::{collections::BTreeMap, error::Error, fmt, fs, io, path::Path};
+
+use typedb_driver::{Connection, Promise, SessionType, Transaction, TransactionType};
+use typeql_derive::TypeQLEntity;
+
+use crate::{
+    database,
+    error::AppError,
+    models::{Model, TypeQLEntity},
+    schema::strip_keyword,
+    IAM_DATA, IAM_DATA_FILE,
+};
+
+/// The types that record applied migrations. They are not part of the application schema.
+pub const MIGRATION_TYPES: &[&str] = &["migration", "migration-version", "migration-name"];
+
+/// `add-view-permission` as it only concludes from valid permissions, migration 3 of [`Migrations::iam`].
+pub const VALID_VIEW_PERMISSION: &str = include_str!("../migrations/0003_valid-view-permission.tql");
+
+/// The rules that let members inherit the groups and valid permissions of their groups, migration 4 of
+/// [`Migrations::iam`].
+pub const GROUP_INHERITANCE: &str = include_str!("../migrations/0004_group-inheritance.tql");
+
+const MIGRATION_SCHEMA: &str = "define
+    migration-version sub attribute, value long;
+    migration-name sub attribute, value string;
+    migration sub entity, owns migration-version @key, owns migration-name;";
+
+/// A data migration. It runs in a write transaction of a data session, which is committed after it returns.
+pub type DataMigration = fn(&Transaction<'_>) -> Result<(), AppError>;
+
+#[derive(Clone, Debug)]
+pub enum Change {
+    Define(String),
+    Undefine(String),
+    Data(DataMigration),
+}
+
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub change: Change,
+}
+
+impl Migration {
+    /// Reads a `<version>_<name>.tql` file, which must hold a single `define` or `undefine` query.
+    pub fn from_file(path: &Path) -> Result<Self, MigrationError> {
+        let file = path.display().to_string();
+        let (version, name) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split_once('_'))
+            .and_then(|(version, name)| Some((version.parse::<i64>().ok()?, name)))
+            .filter(|(version, name)| *version > 0 && !name.is_empty())
+            .ok_or_else(|| MigrationError::InvalidName { file: file.clone() })?;
+        let text = fs::read_to_string(path).map_err(|source| MigrationError::Io { file: file.clone(), source })?;
+        let change = if strip_keyword(&text, "define").is_some() {
+            Change::Define(text)
+        } else if strip_keyword(&text, "undefine").is_some() {
+            Change::Undefine(text)
+        } else {
+            return Err(MigrationError::NotSchemaQuery { file });
+        };
+        Ok(Self { version, name: name.to_owned(), change })
+    }
+
+    /// Whether the migration has the same version, name and query as `other`. Data migrations are never the same
+    /// as another migration.
+    fn same_as(&self, other: &Migration) -> bool {
+        let query = |migration: &Migration| match &migration.change {
+            Change::Define(query) | Change::Undefine(query) => Some((migration.kind(), query.clone())),
+            Change::Data(_) => None,
+        };
+        (self.version, &self.name) == (other.version, &other.name)
+            && query(self).is_some()
+            && query(self) == query(other)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self.change {
+            Change::Define(_) => "define",
+            Change::Undefine(_) => "undefine",
+            Change::Data(_) => "data",
+        }
+    }
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04} {} ({})", self.version, self.name, self.kind())
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    InvalidName { file: String },
+    NotSchemaQuery { file: String },
+    DuplicateVersion { version: i64 },
+    Io { file: String, source: io::Error },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::InvalidName { file } => {
+                write!(f, "Migration file {} is not named <version>_<name>.tql with a positive version", file)
+            }
+            MigrationError::NotSchemaQuery { file } => {
+                write!(f, "Migration file {} must hold a single define or undefine query", file)
+            }
+            MigrationError::DuplicateVersion { version } => {
+                write!(f, "More than one migration has version {}", version)
+            }
+            MigrationError::Io { file, source } => write!(f, "Failed to read migration {}: {}", file, source),
+        }
+    }
+}
+
+impl Error for MigrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MigrationError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The record of an applied migration.
+#[derive(Clone, Debug, PartialEq, TypeQLEntity)]
+#[typeql(type = "migration")]
+pub struct Applied {
+    #[typeql(attribute = "migration-version")]
+    pub version: i64,
+    #[typeql(attribute = "migration-name")]
+    pub name: String,
+}
+
+/// Migrations ordered by version.
+#[derive(Clone, Debug, Default)]
+pub struct Migrations {
+    migrations: BTreeMap<i64, Migration>,
+}
+
+impl Migrations {
+    /// The IAM schema as it was first released as version 1, the IAM dataset as version 2, `add-view-permission`
+    /// limited to valid permissions as version 3, and the group inheritance rules as version 4. Later changes to the
+    /// schema are further migrations, so that the history of an applied database never changes.
+    pub fn iam() -> Self {
+        let mut migrations = Self::default();
+        let schema = include_str!("../migrations/0001_iam-schema.tql");
+        let schema = Migration { version: 1, name: "iam-schema".to_owned(), change: Change::Define(schema.into()) };
+        let data = Migration { version: 2, name: "iam-data".to_owned(), change: Change::Data(load_iam_data) };
+        let view = Change::Define(VALID_VIEW_PERMISSION.into());
+        let view = Migration { version: 3, name: "valid-view-permission".to_owned(), change: view };
+        let rules = Change::Define(GROUP_INHERITANCE.into());
+        let rules = Migration { version: 4, name: "group-inheritance".to_owned(), change: rules };
+        migrations.migrations.extend([(1, schema), (2, data), (3, view), (4, rules)]);
+        migrations
+    }
+
+    pub fn add(&mut self, migration: Migration) -> Result<(), MigrationError> {
+        if self.migrations.contains_key(&migration.version) {
+            return Err(MigrationError::DuplicateVersion { version: migration.version });
+        }
+        self.migrations.insert(migration.version, migration);
+        Ok(())
+    }
+
+    /// Adds every `.tql` file in the directory, skipping the files of migrations that are already added as they
+    /// are, such as those of [`Migrations::iam`] in the `migrations` directory of this crate. A missing directory
+    /// is an error.
+    pub fn add_dir(&mut self, dir: &Path) -> Result<(), MigrationError> {
+        let io_error = |source| MigrationError::Io { file: dir.display().to_string(), source };
+        for entry in fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.is_file() && path.extension().is_some_and(|extension| extension == "tql") {
+                let migration = Migration::from_file(&path)?;
+                if !self.migrations.get(&migration.version).is_some_and(|added| added.same_as(&migration)) {
+                    self.add(migration)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Migration> {
+        self.migrations.values()
+    }
+
+    /// The migrations to apply, in order, to bring a database with the `applied` migrations up to `target`, or
+    /// to the latest version. Migrations are only applied on top of the latest applied version, so a migration
+    /// that is older than it and was never applied is an [`AppError::Conflict`].
+    pub fn plan(&self, applied: &[Applied], target: Option<i64>) -> Result<Vec<&Migration>, AppError> {
+        let current = applied.iter().map(|applied| applied.version).max().unwrap_or(0);
+        let skipped = self
+            .iter()
+            .filter(|migration| migration.version < current)
+            .find(|migration| !applied.iter().any(|applied| applied.version == migration.version));
+        if let Some(migration) = skipped {
+            return Err(AppError::Conflict(format!(
+                "migration {} was never applied but the database is already at version {}",
+                migration, current
+            )));
+        }
+        let target = target.unwrap_or(i64::MAX);
+        Ok(self.iter().filter(|migration| migration.version > current && migration.version <= target).collect())
+    }
+}
+
+fn load_iam_data(tx: &Transaction<'_>) -> Result<(), AppError> {
+    tx.query()
+        .insert(IAM_DATA)
+        .and_then(|answers| answers.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| AppError::schema_load(IAM_DATA_FILE, error))?;
+    Ok(())
+}
+
+/// The migrations applied to the database, ordered by version. A database that never had migrations applied has
+/// none.
+pub fn applied(driver: &Connection, db_name: &str) -> Result<Vec<Applied>, AppError> {
+    let session = database::session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Read)?;
+    if tx.concept().get_entity_type(Applied::TYPE.to_owned()).resolve()?.is_none() {
+        return Ok(Vec::new());
+    }
+    let query = format!("match $m isa {}; fetch {};", Applied::TYPE, Applied::fetch_projection("m"));
+    let mut applied = Vec::new();
+    for answer in tx.query().fetch(&query)? {
+        applied.push(Applied::from_json(&answer?, "m")?);
+    }
+    applied.sort_by_key(|applied| applied.version);
+    Ok(applied)
+}
+
+/// Applies the migration and records it. A data migration runs and is recorded in one transaction of a data
+/// session. A schema migration is committed in a schema session first, as [`database::define_schema`] does, and
+/// then recorded in a data session, so a failure in between leaves it applied but pending; running a `define`
+/// again is harmless, while an `undefine` then has to be recorded with [`baseline`].
+fn apply(driver: &Connection, db_name: &str, migration: &Migration) -> Result<(), AppError> {
+    let file = format!("migration {}", migration);
+    let query = match &migration.change {
+        Change::Define(query) | Change::Undefine(query) => query,
+        Change::Data(migrate) => {
+            let session = database::session(driver, db_name, SessionType::Data)?;
+            let tx = session.transaction(TransactionType::Write)?;
+            migrate(&tx)?;
+            record(&tx, migration)?;
+            return Ok(tx.commit().resolve()?);
+        }
+    };
+    let session = database::session(driver, db_name, SessionType::Schema)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    let defined = match migration.change {
+        Change::Undefine(_) => tx.query().undefine(query).resolve(),
+        _ => tx.query().define(query).resolve(),
+    };
+    defined.and_then(|()| tx.commit().resolve()).map_err(|error| AppError::schema_load(file, error))?;
+    let session = database::session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    record(&tx, migration)?;
+    Ok(tx.commit().resolve()?)
+}
+
+fn record(tx: &Transaction<'_>, migration: &Migration) -> Result<(), AppError> {
+    let applied = Applied { version: migration.version, name: migration.name.clone() };
+    tx.query().insert(&format!("insert {};", applied.insert_pattern("m")?))?.collect::<Result<Vec<_>, _>>()?;
+    Ok(())
+}
+
+/// Records the migrations up to `version` as applied without running them, for a database that was set up
+/// another way, such as with [`database::bootstrap`]. Only a database without applied migrations can be given a
+/// baseline.
+pub fn baseline(
+    driver: &Connection,
+    db_name: &str,
+    migrations: &Migrations,
+    version: i64,
+) -> Result<Vec<Migration>, AppError> {
+    if !applied(driver, db_name)?.is_empty() {
+        return Err(AppError::Conflict(format!("database {} already has applied migrations", db_name)));
+    }
+    let recorded = migrations.plan(&[], Some(version))?.into_iter().cloned().collect::<Vec<_>>();
+    database::define_schema(driver, db_name, "migration schema", MIGRATION_SCHEMA)?;
+    let session = database::session(driver, db_name, SessionType::Data)?;
+    let tx = session.transaction(TransactionType::Write)?;
+    for migration in &recorded {
+        record(&tx, migration)?;
+    }
+    tx.commit().resolve()?;
+    Ok(recorded)
+}
+
+/// Applies the planned migrations in order, stopping at the first failure, and returns the ones applied. The
+/// types that record migrations are defined first if the database does not have them yet.
+pub fn up(
+    driver: &Connection,
+    db_name: &str,
+    migrations: &Migrations,
+    target: Option<i64>,
+) -> Result<Vec<Migration>, AppError> {
+    let plan = migrations.plan(&applied(driver, db_name)?, target)?;
+    if !plan.is_empty() {
+        database::define_schema(driver, db_name, "migration schema", MIGRATION_SCHEMA)?;
+    }
+    for migration in &plan {
+        apply(driver, db_name, migration)?;
+    }
+    Ok(plan.into_iter().cloned().collect())
+}
+// end::migration[]
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::schema::Schema;
+
+    fn applied(versions: &[i64]) -> Vec<Applied> {
+        versions.iter().map(|&version| Applied { version, name: format!("m{}", version) }).collect()
+    }
+
+    fn versions(plan: Vec<&Migration>) -> Vec<i64> {
+        plan.into_iter().map(|migration| migration.version).collect()
+    }
+
+    #[test]
+    fn plans_pending_migrations_in_order() {
+        let migrations = Migrations::iam();
+        assert_eq!(versions(migrations.plan(&[], None).unwrap()), [1, 2, 3, 4]);
+        assert_eq!(versions(migrations.plan(&[], Some(1)).unwrap()), [1]);
+        assert_eq!(versions(migrations.plan(&applied(&[1]), None).unwrap()), [2, 3, 4]);
+        assert!(migrations.plan(&applied(&[1, 2, 3, 4]), None).unwrap().is_empty());
+        assert!(matches!(migrations.plan(&applied(&[2]), None), Err(AppError::Conflict(_))));
+    }
+
+    #[test]
+    fn reads_migration_files() {
+        let dir = env::temp_dir().join(format!("iam-migrations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("0010_review-date.tql"), "define access owns review-date;").unwrap();
+        fs::write(dir.join("0011_drop-review-date.tql"), "# comment\nundefine access owns review-date;").unwrap();
+        fs::write(dir.join("notes.md"), "not a migration").unwrap();
+        let mut migrations = Migrations::iam();
+        migrations.add_dir(&dir).unwrap();
+        let listed = migrations.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            [
+                "0001 iam-schema (define)",
+                "0002 iam-data (data)",
+                "0003 valid-view-permission (define)",
+                "0004 group-inheritance (define)",
+                "0010 review-date (define)",
+                "0011 drop-review-date (undefine)"
+            ]
+        );
+
+        fs::write(dir.join("0002_again.tql"), "define person owns name;").unwrap();
+        assert!(matches!(migrations.add_dir(&dir), Err(MigrationError::DuplicateVersion { version: 2 })));
+        fs::write(dir.join("0012_insert.tql"), "insert $p isa person;").unwrap();
+        let error = Migration::from_file(&dir.join("0012_insert.tql")).unwrap_err();
+        assert!(matches!(error, MigrationError::NotSchemaQuery { .. }));
+        let error = Migration::from_file(&dir.join("notes.md")).unwrap_err();
+        assert!(matches!(error, MigrationError::InvalidName { .. }));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(Migrations::iam().add_dir(&dir), Err(MigrationError::Io { .. })));
+    }
+
+    #[test]
+    fn iam_migrations_match_their_files() {
+        let dir = &Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        for migration in Migrations::iam().iter().filter(|migration| migration.kind() != "data") {
+            let file = Migration::from_file(&dir.join(format!("{:04}_{}.tql", migration.version, migration.name)));
+            assert!(file.unwrap().same_as(migration), "{}", migration);
+        }
+        let mut migrations = Migrations::iam();
+        migrations.add_dir(dir).unwrap();
+        assert_eq!(migrations.iter().count(), Migrations::iam().iter().count());
+        let edited = env::temp_dir().join(format!("iam-migrations-edited-{}", std::process::id()));
+        fs::create_dir_all(&edited).unwrap();
+        fs::write(edited.join("0004_group-inheritance.tql"), format!("{}\n# edited", GROUP_INHERITANCE)).unwrap();
+        assert!(matches!(migrations.add_dir(&edited), Err(MigrationError::DuplicateVersion { version: 4 })));
+        fs::remove_dir_all(&edited).unwrap();
+        let rules = Schema::parse(GROUP_INHERITANCE).unwrap();
+        let rules = rules.rules().iter().map(|rule| rule.label.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(rules, ["transitive-group-membership", "inherit-group-permission"]);
+    }
+}
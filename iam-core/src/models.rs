@@ -0,0 +1,576 @@
+// tag::models[]
+//! Rust types for the entities and relations of `iam-schema.tql`, decoded from query answers. The entity types are
+//! the ones generated into [`crate::schema_types`]; this module adds enums over the subtypes of the abstract types
+//! and relations with their role players.
+//!
+//! A model is decoded from the concept bound to a variable in either kind of answer:
+//!
+//! * a fetch answer (JSON), as returned by `fetch $p: full-name, email;`, where the attributes are read from the
+//!   fetched projection of `$p`;
+//! * a [`ConceptMap`], as returned by `match $p isa person, has full-name $p-full-name; get;`, where the values of
+//!   an attribute owned by `$p` are bound to `$p-<attribute>`.
+//!
+//! The players of a relation bound to `$x` are bound to `$x-<role>`, e.g. `$pe-access-object` is the object of the
+//! access that is the `access` player of the permission `$pe`.
+use std::{error::Error, fmt};
+
+use crate::{
+    query::Literal,
+    schema_types::{self, AccessRole, PermissionRole},
+};
+pub use crate::{
+    query::QueryError,
+    schema_types::{
+        BusinessUnit, Database, Directory, File, Operation, OperationSet, Person, Record, UserAccount, UserRole,
+    },
+};
+use chrono::NaiveDateTime;
+use typedb_driver::{
+    answer::{ConceptMap, JSON},
+    concept::{Attribute, Concept, Value},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelError {
+    MissingVariable(String),
+    MissingAttribute { var: String, attribute: String },
+    MultipleValues { var: String, attribute: String },
+    ValueType { attribute: String, expected: &'static str, found: String },
+    UnexpectedType { var: String, type_: String },
+    Malformed(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::MissingVariable(var) => write!(f, "Answer has no variable ${}", var),
+            ModelError::MissingAttribute { var, attribute } => write!(f, "${} has no {}", var, attribute),
+            ModelError::MultipleValues { var, attribute } => write!(f, "${} has more than one {}", var, attribute),
+            ModelError::ValueType { attribute, expected, found } => {
+                write!(f, "Expected a {} value for {}, found {}", expected, attribute, found)
+            }
+            ModelError::UnexpectedType { var, type_ } => write!(f, "${} has unexpected type {}", var, type_),
+            ModelError::Malformed(message) => write!(f, "Malformed answer: {}", message),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+/// A query answer that models can be decoded from.
+pub trait Answer {
+    /// The type label of the concept bound to `var`.
+    fn type_label(&self, var: &str) -> Result<String, ModelError>;
+
+    /// The values of `attribute` owned by the concept bound to `var`.
+    fn values(&self, var: &str, attribute: &str) -> Result<Vec<Value>, ModelError>;
+}
+
+impl Answer for ConceptMap {
+    fn type_label(&self, var: &str) -> Result<String, ModelError> {
+        match self.get(var) {
+            Some(Concept::Entity(entity)) => Ok(entity.type_.label.clone()),
+            Some(Concept::Relation(relation)) => Ok(relation.type_.label.clone()),
+            Some(Concept::Attribute(attribute)) => Ok(attribute.type_.label.clone()),
+            Some(other) => Err(ModelError::Malformed(format!("${} is not a thing: {:?}", var, other))),
+            None => Err(ModelError::MissingVariable(var.to_owned())),
+        }
+    }
+
+    fn values(&self, var: &str, attribute: &str) -> Result<Vec<Value>, ModelError> {
+        let var = format!("{}-{}", var, attribute);
+        match self.get(&var) {
+            Some(Concept::Attribute(Attribute { value, .. }) | Concept::Value(value)) => Ok(vec![value.clone()]),
+            Some(other) => Err(ModelError::Malformed(format!("${} is not an attribute: {:?}", var, other))),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Answer for JSON {
+    fn type_label(&self, var: &str) -> Result<String, ModelError> {
+        match json_field(projection(self, var)?, "type").and_then(|type_| json_field(type_, "label")) {
+            Some(JSON::String(label)) => Ok(label.to_string()),
+            _ => Err(ModelError::Malformed(format!("${} has no type label", var))),
+        }
+    }
+
+    fn values(&self, var: &str, attribute: &str) -> Result<Vec<Value>, ModelError> {
+        match json_field(projection(self, var)?, attribute) {
+            Some(JSON::Array(values)) => values.iter().map(|value| json_value(attribute, value)).collect(),
+            Some(value) => Ok(vec![json_value(attribute, value)?]),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+fn json_field<'a>(json: &'a JSON, key: &str) -> Option<&'a JSON> {
+    match json {
+        JSON::Object(object) => object.get(key),
+        _ => None,
+    }
+}
+
+fn projection<'a>(json: &'a JSON, var: &str) -> Result<&'a JSON, ModelError> {
+    json_field(json, var).ok_or_else(|| ModelError::MissingVariable(var.to_owned()))
+}
+
+/// Decodes `{"type": {"value_type": "long", ...}, "value": 55}` into a typed value.
+fn json_value(attribute: &str, json: &JSON) -> Result<Value, ModelError> {
+    let value_type = json_field(json, "type").and_then(|type_| json_field(type_, "value_type"));
+    let malformed = || ModelError::Malformed(format!("{} value {}", attribute, json));
+    match (value_type, json_field(json, "value")) {
+        (Some(JSON::String(value_type)), Some(value)) => match (value_type.as_ref(), value) {
+            ("string", JSON::String(value)) => Ok(Value::String(value.to_string())),
+            ("boolean", JSON::Boolean(value)) => Ok(Value::Boolean(*value)),
+            ("double", JSON::Number(value)) => Ok(Value::Double(*value)),
+            ("long", JSON::Number(value)) if value.fract() == 0.0 => Ok(Value::Long(*value as i64)),
+            ("datetime", JSON::String(value)) => {
+                NaiveDateTime::parse_from_str(value, "%FT%T%.f").map(Value::DateTime).map_err(|_| malformed())
+            }
+            _ => Err(malformed()),
+        },
+        _ => Err(malformed()),
+    }
+}
+
+/// A Rust type that attribute values of one value type decode into.
+pub trait FromValue: Sized {
+    const VALUE_TYPE: &'static str;
+
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl FromValue for String {
+    const VALUE_TYPE: &'static str = "string";
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    const VALUE_TYPE: &'static str = "long";
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    const VALUE_TYPE: &'static str = "double";
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Double(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const VALUE_TYPE: &'static str = "boolean";
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for NaiveDateTime {
+    const VALUE_TYPE: &'static str = "datetime";
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::DateTime(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// All values of `attribute` owned by `var`.
+pub fn all<T: FromValue>(answer: &(impl Answer + ?Sized), var: &str, attribute: &str) -> Result<Vec<T>, ModelError> {
+    let values = answer.values(var, attribute)?;
+    values
+        .into_iter()
+        .map(|value| {
+            let found = format!("{:?}", value);
+            T::from_value(value).ok_or(ModelError::ValueType {
+                attribute: attribute.to_owned(),
+                expected: T::VALUE_TYPE,
+                found,
+            })
+        })
+        .collect()
+}
+
+/// The value of an attribute that `var` owns at most once.
+pub fn optional<T: FromValue>(
+    answer: &(impl Answer + ?Sized),
+    var: &str,
+    attribute: &str,
+) -> Result<Option<T>, ModelError> {
+    let mut values = all(answer, var, attribute)?;
+    match values.len() {
+        0 | 1 => Ok(values.pop()),
+        _ => Err(ModelError::MultipleValues { var: var.to_owned(), attribute: attribute.to_owned() }),
+    }
+}
+
+/// The value of an attribute that `var` owns exactly once.
+pub fn one<T: FromValue>(answer: &(impl Answer + ?Sized), var: &str, attribute: &str) -> Result<T, ModelError> {
+    optional(answer, var, attribute)?
+        .ok_or_else(|| ModelError::MissingAttribute { var: var.to_owned(), attribute: attribute.to_owned() })
+}
+
+/// The variable that the player of `role` in the relation bound to `var` is bound to.
+pub fn player_var(var: &str, role: &str) -> String {
+    format!("{}-{}", var, role)
+}
+
+/// A Rust type for one or more types of the schema.
+pub trait Model: Sized {
+    /// The labels of the schema types that this model decodes.
+    const TYPES: &'static [&'static str];
+
+    /// Decodes the concept bound to `var`, which must be of one of [`Model::TYPES`].
+    fn decode(answer: &(impl Answer + ?Sized), var: &str) -> Result<Self, ModelError>;
+
+    fn from_concept_map(concept_map: &ConceptMap, var: &str) -> Result<Self, ModelError> {
+        Self::decode(concept_map, var)
+    }
+
+    fn from_json(json: &JSON, var: &str) -> Result<Self, ModelError> {
+        Self::decode(json, var)
+    }
+}
+
+/// A model of a single entity type whose fields are its attributes, usually implemented with
+/// `#[derive(TypeQLEntity)]`.
+pub trait TypeQLEntity: Model {
+    const TYPE: &'static str;
+
+    /// The attributes of the entity, in field order.
+    const ATTRIBUTES: &'static [&'static str];
+
+    /// `$var isa <type>, has <attribute> <value>, ...` for every attribute value of `self`, failing for strings
+    /// that TypeQL cannot represent.
+    fn insert_pattern(&self, var: &str) -> Result<String, QueryError>;
+
+    /// `$var isa <type>, has <attribute> $var-<attribute>, ...` for the attributes owned exactly once, binding
+    /// them the way [`Model::from_concept_map`] reads them.
+    fn match_pattern(var: &str) -> String;
+
+    /// `$var: <attribute>, ...`, the fetch projection that [`Model::from_json`] reads.
+    fn fetch_projection(var: &str) -> String {
+        format!("${}: {}", var, Self::ATTRIBUTES.join(", "))
+    }
+}
+
+/// `, has <attribute> <value>` with the value rendered as a quoted TypeQL literal.
+pub fn has_clause<T: Clone + Into<Literal>>(attribute: &str, value: &T) -> Result<String, QueryError> {
+    Ok(format!(", has {} {}", attribute, value.clone().into().to_typeql()?))
+}
+
+/// Fails unless the concept bound to `var` has one of the given types, and returns its type otherwise.
+pub fn check_type(answer: &(impl Answer + ?Sized), var: &str, types: &[&str]) -> Result<String, ModelError> {
+    let type_ = answer.type_label(var)?;
+    if types.contains(&type_.as_str()) {
+        Ok(type_)
+    } else {
+        Err(ModelError::UnexpectedType { var: var.to_owned(), type_ })
+    }
+}
+
+/// Declares an enum over models, decoding whichever variant the concept's type belongs to.
+macro_rules! union_model {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident($model:ty),)* }) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum $name {
+            $($variant($model),)*
+        }
+
+        impl Model for $name {
+            const TYPES: &'static [&'static str] = &concat_types!($($model),*);
+
+            fn decode(answer: &(impl Answer + ?Sized), var: &str) -> Result<Self, ModelError> {
+                let type_ = check_type(answer, var, Self::TYPES)?;
+                $(
+                    if <$model>::TYPES.contains(&type_.as_str()) {
+                        return Ok(Self::$variant(<$model>::decode(answer, var)?));
+                    }
+                )*
+                unreachable!()
+            }
+        }
+    };
+}
+
+/// The concatenation of the `TYPES` of models, as an array expression.
+macro_rules! concat_types {
+    ($($model:ty),*) => {{
+        const LEN: usize = 0 $(+ <$model>::TYPES.len())*;
+        const TYPES: [&str; LEN] = {
+            let mut types = [""; LEN];
+            let mut i = 0;
+            $(
+                let mut j = 0;
+                while j < <$model>::TYPES.len() {
+                    types[i] = <$model>::TYPES[j];
+                    i += 1;
+                    j += 1;
+                }
+            )*
+            let _ = i;
+            types
+        };
+        TYPES
+    }};
+}
+
+impl fmt::Display for Person {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.full_name.as_deref().unwrap_or("(no full-name)"))?;
+        if let Some(email) = &self.email {
+            write!(f, " <{}>", email)?;
+        }
+        Ok(())
+    }
+}
+
+union_model! {
+    UserGroup {
+        BusinessUnit(BusinessUnit),
+        UserAccount(UserAccount),
+        UserRole(UserRole),
+    }
+}
+
+union_model! {
+    Subject {
+        Person(Person),
+        UserGroup(UserGroup),
+    }
+}
+
+union_model! {
+    Object {
+        File(File),
+        Directory(Directory),
+        Record(Record),
+        Database(Database),
+    }
+}
+
+union_model! {
+    Action {
+        Operation(Operation),
+        OperationSet(OperationSet),
+    }
+}
+
+/// An `access` relation: an action that can be performed on an object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Access {
+    pub action: Action,
+    pub object: Object,
+}
+
+impl Model for Access {
+    const TYPES: &'static [&'static str] = &["access"];
+
+    fn decode(answer: &(impl Answer + ?Sized), var: &str) -> Result<Self, ModelError> {
+        schema_types::Access::decode(answer, var)?;
+        Ok(Self {
+            action: Action::decode(answer, &AccessRole::Action.player_var(var))?,
+            object: Object::decode(answer, &AccessRole::Object.player_var(var))?,
+        })
+    }
+}
+
+/// A `permission` relation: a subject that is granted an access.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permission {
+    pub subject: Subject,
+    pub access: Access,
+    pub review_date: Option<NaiveDateTime>,
+    pub validity: Option<bool>,
+}
+
+impl Model for Permission {
+    const TYPES: &'static [&'static str] = &["permission"];
+
+    fn decode(answer: &(impl Answer + ?Sized), var: &str) -> Result<Self, ModelError> {
+        let schema_types::Permission { review_date, validity } = schema_types::Permission::decode(answer, var)?;
+        Ok(Self {
+            subject: Subject::decode(answer, &PermissionRole::Subject.player_var(var))?,
+            access: Access::decode(answer, &PermissionRole::Access.player_var(var))?,
+            review_date,
+            validity,
+        })
+    }
+}
+// end::models[]
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use chrono::NaiveDate;
+    use typedb_driver::{
+        concept::{AttributeType, Entity, EntityType, ValueType},
+        IID,
+    };
+
+    use super::*;
+
+    /// Converts JSON text into the driver's representation of a fetch answer.
+    fn fetch_answer(text: &str) -> JSON {
+        fn convert(value: serde_json::Value) -> JSON {
+            match value {
+                serde_json::Value::Null => JSON::Null,
+                serde_json::Value::Bool(value) => JSON::Boolean(value),
+                serde_json::Value::Number(value) => JSON::Number(value.as_f64().unwrap()),
+                serde_json::Value::String(value) => JSON::String(Cow::Owned(value)),
+                serde_json::Value::Array(values) => JSON::Array(values.into_iter().map(convert).collect()),
+                serde_json::Value::Object(object) => {
+                    JSON::Object(object.into_iter().map(|(key, value)| (Cow::Owned(key), convert(value))).collect())
+                }
+            }
+        }
+        convert(serde_json::from_str(text).unwrap())
+    }
+
+    fn attribute(label: &str, value_type: &str, value: &str) -> String {
+        format!(
+            r#"{{"type": {{"root": "attribute", "label": "{}", "value_type": "{}"}}, "value": {}}}"#,
+            label, value_type, value
+        )
+    }
+
+    #[test]
+    fn decodes_fetched_person_and_file() {
+        let json = fetch_answer(&format!(
+            r#"{{
+                "u": {{"type": {{"root": "entity", "label": "person"}}, "full-name": [{}], "email": [{}]}},
+                "f": {{"type": {{"root": "entity", "label": "file"}}, "path": [{}], "size-kb": [{}]}}
+            }}"#,
+            attribute("full-name", "string", r#""Kevin Morrison""#),
+            attribute("email", "string", r#""kevin.morrison@typedb.com""#),
+            attribute("path", "string", r#""iopvu.java""#),
+            attribute("size-kb", "long", "55"),
+        ));
+        let person = Person::from_json(&json, "u").unwrap();
+        assert_eq!(person.to_string(), "Kevin Morrison <kevin.morrison@typedb.com>");
+        assert_eq!(person.credential, None);
+        let file = File::from_json(&json, "f").unwrap();
+        assert_eq!((file.path.as_deref(), file.size_kb), (Some("iopvu.java"), Some(55)));
+        assert!(matches!(Object::from_json(&json, "f"), Ok(Object::File(_))));
+        assert!(matches!(Person::from_json(&json, "f"), Err(ModelError::UnexpectedType { .. })));
+        assert!(matches!(Person::from_json(&json, "x"), Err(ModelError::MissingVariable(_))));
+    }
+
+    #[test]
+    fn decodes_fetched_permission_with_boolean_and_datetime() {
+        let entity = |label: &str, attributes: &[String]| {
+            format!(r#"{{"type": {{"root": "entity", "label": "{}"}}, {}}}"#, label, attributes.join(", "))
+        };
+        let json = fetch_answer(&format!(
+            r#"{{
+                "pe": {{"type": {{"root": "relation", "label": "permission"}},
+                        "review-date": [{}], "validity": [{}]}},
+                "pe-subject": {},
+                "pe-access": {{"type": {{"root": "relation", "label": "access"}}}},
+                "pe-access-action": {},
+                "pe-access-object": {}
+            }}"#,
+            attribute("review-date", "datetime", r#""2024-03-01T12:30:00.000""#),
+            attribute("validity", "boolean", "true"),
+            entity("user-role", &[format!(r#""name": [{}]"#, attribute("name", "string", r#""admin""#))]),
+            entity("operation", &[format!(r#""name": [{}]"#, attribute("name", "string", r#""view_file""#))]),
+            entity("directory", &[format!(r#""path": [{}]"#, attribute("path", "string", r#""/src""#))]),
+        ));
+        let permission = Permission::from_json(&json, "pe").unwrap();
+        let review_date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(12, 30, 0).unwrap();
+        assert_eq!(permission.review_date, Some(review_date));
+        assert_eq!(permission.validity, Some(true));
+        assert!(
+            matches!(permission.subject, Subject::UserGroup(UserGroup::UserRole(UserRole { ref name, .. })) if name.as_deref() == Some("admin"))
+        );
+        assert!(
+            matches!(permission.access.action, Action::Operation(Operation { ref name, .. }) if name.as_deref() == Some("view_file"))
+        );
+        assert!(matches!(permission.access.object, Object::Directory(Directory { size_kb: None, .. })));
+    }
+
+    #[test]
+    fn reports_wrong_value_types() {
+        let json = fetch_answer(&format!(
+            r#"{{"f": {{"type": {{"root": "entity", "label": "file"}}, "path": [{}], "size-kb": [{}]}}}}"#,
+            attribute("path", "string", r#""a""#),
+            attribute("size-kb", "string", r#""big""#),
+        ));
+        assert!(matches!(File::from_json(&json, "f"), Err(ModelError::ValueType { expected: "long", .. })));
+    }
+
+    #[test]
+    fn derives_patterns_for_person_and_file() {
+        let person = Person {
+            full_name: Some("Jack \"JK\" Keeper".to_owned()),
+            email: Some("jk@typedb.com".to_owned()),
+            credential: None,
+        };
+        assert_eq!(Person::TYPES, ["person"]);
+        assert_eq!(
+            person.insert_pattern("p").unwrap(),
+            r#"$p isa person, has email "jk@typedb.com", has full-name 'Jack "JK" Keeper'"#
+        );
+        assert_eq!(Person::match_pattern("p"), "$p isa person");
+        assert_eq!(Person::fetch_projection("p"), "$p: credential, email, full-name");
+        assert_eq!(person.to_string(), r#"Jack "JK" Keeper <jk@typedb.com>"#);
+
+        let file = File { path: Some("README.md".to_owned()), size_kb: Some(7), object_type: None };
+        assert_eq!(File::ATTRIBUTES, ["object-type", "path", "size-kb"]);
+        assert_eq!(file.insert_pattern("f").unwrap(), r#"$f isa file, has path "README.md", has size-kb 7"#);
+        assert_eq!(File::match_pattern("f"), "$f isa file");
+
+        for query in [
+            format!("insert {}; {};", person.insert_pattern("p").unwrap(), file.insert_pattern("f").unwrap()),
+            format!("match {}; {}; get;", Person::match_pattern("p"), File::match_pattern("f")),
+            format!(
+                "match $p isa person; $f isa file; fetch {}; {};",
+                Person::fetch_projection("p"),
+                File::fetch_projection("f")
+            ),
+        ] {
+            assert!(typeql::parse_query(&query).is_ok(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn decodes_concept_maps() {
+        let attribute = |label: &str, value_type, value| {
+            let type_ = AttributeType { label: label.to_owned(), is_root: false, is_abstract: false, value_type };
+            Concept::Attribute(Attribute { iid: IID::from(vec![1]), type_, value, is_inferred: false })
+        };
+        let type_ = EntityType { label: "file".to_owned(), is_root: false, is_abstract: false };
+        let map = HashMap::from([
+            ("f".to_owned(), Concept::Entity(Entity { iid: IID::from(vec![0]), type_, is_inferred: false })),
+            ("f-path".to_owned(), attribute("path", ValueType::String, Value::String("README.md".to_owned()))),
+            ("f-size-kb".to_owned(), attribute("size-kb", ValueType::Long, Value::Long(7))),
+        ]);
+        let concept_map = ConceptMap { map, explainables: Default::default() };
+        let file = File::from_concept_map(&concept_map, "f").unwrap();
+        assert_eq!(file, File { path: Some("README.md".to_owned()), size_kb: Some(7), object_type: None });
+        assert!(matches!(Directory::from_concept_map(&concept_map, "f"), Err(ModelError::UnexpectedType { .. })));
+    }
+}
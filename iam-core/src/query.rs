@@ -0,0 +1,312 @@
+// tag::query-builder[]
+//! Parameterized TypeQL queries.
+//!
+//! Query text is a `&'static str` template with `{name}` placeholders, so user input can only ever reach the
+//! server through [`Query::bind`], which renders it as a quoted TypeQL literal. Literal braces in a template
+//! are written as `{{` and `}}`, the same way as in `format!`.
+use std::{error::Error, fmt};
+
+use chrono::NaiveDateTime;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    String(String),
+    Long(i64),
+    Double(f64),
+    Boolean(bool),
+    DateTime(NaiveDateTime),
+}
+
+impl Literal {
+    /// The literal as TypeQL, failing for strings that TypeQL cannot represent (see [`quote_string`]) and for
+    /// doubles that are not finite.
+    pub fn to_typeql(&self) -> Result<String, QueryError> {
+        Ok(match self {
+            Literal::String(value) => quote_string(value)?,
+            Literal::Long(value) => value.to_string(),
+            Literal::Double(value) if !value.is_finite() => return Err(QueryError::NonFinite(value.to_string())),
+            Literal::Double(value) if value.fract() == 0.0 => format!("{:.1}", value),
+            Literal::Double(value) => value.to_string(),
+            Literal::Boolean(value) => value.to_string(),
+            Literal::DateTime(value) => value.format("%FT%T%.3f").to_string(),
+        })
+    }
+}
+
+/// The literal for messages, with strings quoted the way Rust debug-formats them. Queries use
+/// [`Literal::to_typeql`] instead.
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::String(value) => write!(f, "{:?}", value),
+            Literal::Double(value) if !value.is_finite() => write!(f, "{}", value),
+            other => write!(f, "{}", other.to_typeql().map_err(|_| fmt::Error)?),
+        }
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Literal::String(value.to_owned())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Literal::String(value)
+    }
+}
+
+impl From<&String> for Literal {
+    fn from(value: &String) -> Self {
+        Literal::String(value.clone())
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Literal::Long(value)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(value: f64) -> Self {
+        Literal::Double(value)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Literal::Boolean(value)
+    }
+}
+
+impl From<NaiveDateTime> for Literal {
+    fn from(value: NaiveDateTime) -> Self {
+        Literal::DateTime(value)
+    }
+}
+
+/// Quotes a string as a TypeQL literal, between double quotes if it fits in them and single quotes otherwise.
+/// TypeDB strips the quotes without unescaping what is between them, and a backslash takes the character after it
+/// into the literal, so a string fits in a quote character unless it has that quote outside such a pair or ends
+/// in an unpaired backslash that would take the closing quote.
+pub fn quote_string(value: &str) -> Result<String, QueryError> {
+    let fits = |quote: char| {
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c == quote || (c == '\\' && chars.next().is_none()) {
+                return false;
+            }
+        }
+        true
+    };
+    if fits('"') {
+        Ok(format!("\"{}\"", value))
+    } else if fits('\'') {
+        Ok(format!("'{}'", value))
+    } else {
+        Err(QueryError::Unquotable(value.to_owned()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    Unbound(String),
+    Unused(String),
+    Duplicate(String),
+    Malformed(usize),
+    Unquotable(String),
+    NonFinite(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::Unbound(name) => write!(f, "Query placeholder {{{}}} has no bound value.", name),
+            QueryError::Unused(name) => write!(f, "Bound value '{}' is not used by the query.", name),
+            QueryError::Duplicate(name) => write!(f, "Value '{}' is bound more than once.", name),
+            QueryError::Malformed(position) => write!(f, "Unmatched brace in query template at byte {}.", position),
+            QueryError::Unquotable(value) => {
+                write!(f, "String {:?} fits in neither quote character, so TypeQL cannot store it.", value)
+            }
+            QueryError::NonFinite(value) => write!(f, "Double {} is not finite, so TypeQL cannot store it.", value),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Clone, Debug)]
+pub struct Query {
+    template: &'static str,
+    bindings: Vec<(&'static str, Literal)>,
+}
+
+impl Query {
+    pub fn new(template: &'static str) -> Self {
+        Self { template, bindings: Vec::new() }
+    }
+
+    pub fn bind(mut self, name: &'static str, value: impl Into<Literal>) -> Self {
+        self.bindings.push((name, value.into()));
+        self
+    }
+
+    pub fn build(&self) -> Result<String, QueryError> {
+        let mut used = vec![false; self.bindings.len()];
+        for (index, (name, _)) in self.bindings.iter().enumerate() {
+            if self.bindings[..index].iter().any(|(other, _)| other == name) {
+                return Err(QueryError::Duplicate(name.to_string()));
+            }
+        }
+        let mut query = String::with_capacity(self.template.len());
+        let mut chars = self.template.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, next)| next == '{').is_some() => query.push('{'),
+                '}' if chars.next_if(|&(_, next)| next == '}').is_some() => query.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) if !name.is_empty() => break,
+                            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+                            _ => return Err(QueryError::Malformed(position)),
+                        }
+                    }
+                    let index = self
+                        .bindings
+                        .iter()
+                        .position(|(bound, _)| *bound == name)
+                        .ok_or_else(|| QueryError::Unbound(name.clone()))?;
+                    used[index] = true;
+                    query.push_str(&self.bindings[index].1.to_typeql()?);
+                }
+                '}' => return Err(QueryError::Malformed(position)),
+                c => query.push(c),
+            }
+        }
+        match used.iter().position(|used| !used) {
+            Some(index) => Err(QueryError::Unused(self.bindings[index].0.to_string())),
+            None => Ok(query),
+        }
+    }
+}
+// end::query-builder[]
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use typeql::pattern::{Constant, HasConstraint, Predicate, Value};
+
+    use super::*;
+
+    #[test]
+    fn renders_typed_literals() {
+        let query = Query::new("match $f has size-kb {size}, has path {path}; $p has validity {valid};")
+            .bind("size", 55)
+            .bind("path", "\"notes\".txt")
+            .bind("valid", true)
+            .build()
+            .unwrap();
+        assert_eq!(query, r#"match $f has size-kb 55, has path '"notes".txt'; $p has validity true;"#);
+        for (value, quoted) in [
+            (r"C:\docs\a.txt", r#""C:\docs\a.txt""#),
+            (r"C:\docs\\", r#""C:\docs\\""#),
+            (r#"say \"hi\""#, r#""say \"hi\"""#),
+            (r#"O'Brien \"notes\""#, r#""O'Brien \"notes\"""#),
+        ] {
+            assert_eq!(quote_string(value).unwrap(), quoted);
+        }
+        let review_date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let query = Query::new("match $p has review-date {date}; get;").bind("date", review_date).build().unwrap();
+        assert_eq!(query, "match $p has review-date 2024-03-01T09:00:00.000; get;");
+        assert!(typeql::parse_query(&query).is_ok());
+    }
+
+    #[test]
+    fn reports_template_errors() {
+        assert_eq!(Query::new("match $u has name {name};").build(), Err(QueryError::Unbound("name".to_owned())));
+        assert_eq!(
+            Query::new("match $u isa user;").bind("name", "x").build(),
+            Err(QueryError::Unused("name".to_owned()))
+        );
+        assert_eq!(Query::new("{a}").bind("a", 1).bind("a", 2).build(), Err(QueryError::Duplicate("a".to_owned())));
+        assert_eq!(Query::new("match { $x isa user; };").build(), Err(QueryError::Malformed(6)));
+        assert_eq!(Query::new("match {{ $x isa user; }};").build().unwrap(), "match { $x isa user; };");
+        for value in ["O'Brien \"notes\"", "C:\\notes\\"] {
+            assert_eq!(
+                Query::new("match $f has path {path};").bind("path", value).build(),
+                Err(QueryError::Unquotable(value.to_owned()))
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn string_literals_round_trip(value in prop_oneof![any::<String>(), r#"[a-z'"\\]*"#]) {
+            match Query::new("insert $f isa file, has path {path};").bind("path", value.as_str()).build() {
+                Ok(query) => {
+                    let insert = typeql::parse_query(&query).unwrap().into_insert();
+                    let parsed = match &insert.statements[0].has[0] {
+                        HasConstraint::HasPredicate(_, Predicate { value: Value::Constant(Constant::String(parsed)), .. }) => parsed,
+                        other => panic!("unexpected attribute {}", other),
+                    };
+                    prop_assert_eq!(parsed, &value);
+                }
+                Err(error) => prop_assert_eq!(error, QueryError::Unquotable(value)),
+            }
+        }
+
+        #[test]
+        fn double_literals_round_trip(value in prop_oneof![
+            any::<f64>(),
+            Just(f64::NAN),
+            Just(f64::INFINITY),
+            Just(f64::NEG_INFINITY),
+        ]) {
+            match Query::new("insert $f isa file, has size-kb {size};").bind("size", value).build() {
+                Ok(query) => {
+                    let insert = typeql::parse_query(&query).unwrap().into_insert();
+                    let parsed = match &insert.statements[0].has[0] {
+                        HasConstraint::HasPredicate(_, Predicate { value: Value::Constant(Constant::Double(parsed)), .. }) => *parsed,
+                        other => panic!("unexpected attribute {}", other),
+                    };
+                    prop_assert_eq!(parsed, value);
+                }
+                Err(error) => {
+                    prop_assert!(!value.is_finite());
+                    prop_assert_eq!(error, QueryError::NonFinite(value.to_string()));
+                }
+            }
+        }
+
+        #[test]
+        fn windows_paths_are_quotable(path in r"[A-Z]:(\\[\w .']+)+") {
+            let quoted = quote_string(&path).unwrap();
+            prop_assert_eq!(&quoted[1..quoted.len() - 1], path.as_str());
+        }
+
+        #[test]
+        fn bound_strings_cannot_inject_clauses(
+            name in r#"[\PC'"\\;$ ]*"#,
+            email in any::<String>(),
+        ) {
+            let query = Query::new("insert $p isa person, has full-name $fn, has email $e; $fn == {name}; $e == {email};")
+                .bind("name", name)
+                .bind("email", email)
+                .build();
+            let query = match query {
+                Ok(query) => query,
+                Err(error) => {
+                    prop_assert!(matches!(error, QueryError::Unquotable(_)));
+                    return Ok(());
+                }
+            };
+            let insert = typeql::parse_query(&query).unwrap().into_insert();
+            prop_assert!(insert.match_clause.is_none());
+            prop_assert_eq!(insert.statements.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,612 @@
+// tag::schema-model[]
+//! In-process model of a TypeQL `define` file such as `iam-schema.tql`, built from the `typeql` parser's AST.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt, fs,
+    path::Path,
+};
+
+use typeql::{
+    common::token,
+    pattern::{Annotation, Definable, Label, Rule, TypeStatement},
+    variable::TypeReference,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    Entity,
+    Relation,
+    Attribute,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueType {
+    Boolean,
+    DateTime,
+    Double,
+    Long,
+    String,
+}
+
+impl From<token::ValueType> for ValueType {
+    fn from(value_type: token::ValueType) -> Self {
+        match value_type {
+            token::ValueType::Boolean => ValueType::Boolean,
+            token::ValueType::DateTime => ValueType::DateTime,
+            token::ValueType::Double => ValueType::Double,
+            token::ValueType::Long => ValueType::Long,
+            token::ValueType::String => ValueType::String,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            ValueType::Boolean => token::ValueType::Boolean,
+            ValueType::DateTime => token::ValueType::DateTime,
+            ValueType::Double => token::ValueType::Double,
+            ValueType::Long => token::ValueType::Long,
+            ValueType::String => token::ValueType::String,
+        };
+        write!(f, "{}", token.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Owns {
+    pub attribute: String,
+    pub overrides: Option<String>,
+    pub key: bool,
+    pub unique: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relates {
+    pub role: String,
+    pub overrides: Option<String>,
+}
+
+/// A role played by a type, as a `relation:role` pair.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Plays {
+    pub relation: String,
+    pub role: String,
+    pub overrides: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeDef {
+    pub label: String,
+    pub supertype: Option<String>,
+    pub is_abstract: bool,
+    pub value_type: Option<ValueType>,
+    pub owns: Vec<Owns>,
+    pub plays: Vec<Plays>,
+    pub relates: Vec<Relates>,
+}
+
+impl TypeDef {
+    pub fn new(label: String) -> Self {
+        Self {
+            label,
+            supertype: None,
+            is_abstract: false,
+            value_type: None,
+            owns: Vec::new(),
+            plays: Vec::new(),
+            relates: Vec::new(),
+        }
+    }
+
+    /// The type as a single TypeQL statement. Attribute types fit on one line; other types put each property on
+    /// its own line and are separated by blank lines in [`Schema::to_typeql`].
+    fn to_typeql(&self) -> String {
+        let mut properties = Vec::new();
+        if self.is_abstract {
+            properties.push("abstract".to_owned());
+        }
+        if let Some(value_type) = self.value_type {
+            properties.push(format!("value {}", value_type));
+        }
+        let mut owns = self.owns.iter().collect::<Vec<_>>();
+        owns.sort_by(|a, b| a.attribute.cmp(&b.attribute));
+        properties.extend(owns.into_iter().map(ToString::to_string));
+        let mut relates = self.relates.iter().collect::<Vec<_>>();
+        relates.sort_by(|a, b| a.role.cmp(&b.role));
+        properties.extend(relates.into_iter().map(ToString::to_string));
+        let mut plays = self.plays.iter().collect::<Vec<_>>();
+        plays.sort();
+        properties.extend(plays.into_iter().map(ToString::to_string));
+
+        let header = format!("{} sub {}", self.label, self.supertype.as_deref().unwrap_or("thing"));
+        if properties.is_empty() {
+            format!("{};", header)
+        } else if self.value_type.is_some() {
+            format!("{}, {};", header, properties.join(", "))
+        } else {
+            format!("{},\n    {};", header, properties.join(",\n    "))
+        }
+    }
+}
+
+impl fmt::Display for Owns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "owns {}", self.attribute)?;
+        if let Some(overridden) = &self.overrides {
+            write!(f, " as {}", overridden)?;
+        }
+        if self.key {
+            write!(f, " @key")?;
+        }
+        if self.unique {
+            write!(f, " @unique")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Plays {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "plays {}:{}", self.relation, self.role)?;
+        match &self.overrides {
+            Some(overridden) => write!(f, " as {}", overridden),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Relates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "relates {}", self.role)?;
+        match &self.overrides {
+            Some(overridden) => write!(f, " as {}", overridden),
+            None => Ok(()),
+        }
+    }
+}
+
+/// One difference between two schemas, named by the item it concerns, such as `type person`,
+/// `person owns email` or `rule add-view-permission`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaChange {
+    Added(String),
+    Removed(String),
+    Changed { item: String, from: String, to: String },
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::Added(item) => write!(f, "+ {}", item),
+            SchemaChange::Removed(item) => write!(f, "- {}", item),
+            SchemaChange::Changed { item, from, to } => write!(f, "~ {}: {} -> {}", item, from, to),
+        }
+    }
+}
+
+/// Adds the changes between two maps from item keys to their rendered definitions.
+fn diff_items(
+    old: BTreeMap<String, String>,
+    mut new: BTreeMap<String, String>,
+    item: impl Fn(&str) -> String,
+    changes: &mut Vec<SchemaChange>,
+) {
+    for (key, from) in old {
+        match new.remove(&key) {
+            None => changes.push(SchemaChange::Removed(item(&key))),
+            Some(to) if to != from => changes.push(SchemaChange::Changed { item: item(&key), from, to }),
+            Some(_) => (),
+        }
+    }
+    changes.extend(new.into_keys().map(|key| SchemaChange::Added(item(&key))));
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(String),
+    Parse(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Io(message) => write!(f, "Failed to read schema: {}", message),
+            SchemaError::Parse(message) => write!(f, "Failed to parse schema: {}", message),
+            SchemaError::Unsupported(message) => write!(f, "Unsupported schema statement: {}", message),
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    types: BTreeMap<String, TypeDef>,
+    rules: Vec<Rule>,
+}
+
+impl Schema {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SchemaError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|error| SchemaError::Io(format!("{}: {}", path.display(), error)))?;
+        Self::parse(&text)
+    }
+
+    /// Parses the body of a `define` query; the leading `define` keyword is optional.
+    pub fn parse(text: &str) -> Result<Self, SchemaError> {
+        let body = strip_keyword(text, "define").unwrap_or(text);
+        let definables = typeql::parse_definables(body).map_err(|error| SchemaError::Parse(error.to_string()))?;
+        let mut schema = Schema::default();
+        for definable in definables {
+            match definable {
+                Definable::TypeStatement(statement) => schema.add_statement(statement)?,
+                Definable::RuleDefinition(rule) => schema.rules.push(rule),
+                Definable::RuleDeclaration(rule) => {
+                    return Err(SchemaError::Unsupported(format!("rule {} has no body", rule.label)))
+                }
+            }
+        }
+        Ok(schema)
+    }
+
+    fn add_statement(&mut self, statement: TypeStatement) -> Result<(), SchemaError> {
+        let label = match &statement.label {
+            Some(label) => label.label.name.clone(),
+            None => return Err(SchemaError::Unsupported(statement.to_string())),
+        };
+        let type_def = self.types.entry(label.clone()).or_insert_with(|| TypeDef::new(label));
+        if let Some(sub) = &statement.sub {
+            type_def.supertype = Some(type_reference_name(&sub.type_)?);
+        }
+        type_def.is_abstract |= statement.abstract_.is_some();
+        if let Some(value_type) = &statement.value_type {
+            type_def.value_type = Some(value_type.value_type.into());
+        }
+        for owns in &statement.owns {
+            type_def.owns.push(Owns {
+                attribute: type_reference_name(&owns.attribute_type)?,
+                overrides: owns.overridden_attribute_type.as_ref().map(type_reference_name).transpose()?,
+                key: owns.annotations.contains(&Annotation::Key),
+                unique: owns.annotations.contains(&Annotation::Unique),
+            });
+        }
+        for plays in &statement.plays {
+            let (relation, role) = match &plays.role_type {
+                TypeReference::Label(Label { scope: Some(scope), name }) => (scope.clone(), name.clone()),
+                other => return Err(SchemaError::Unsupported(format!("plays {}", other))),
+            };
+            let overrides = plays.overridden_role_type.as_ref().map(type_reference_name).transpose()?;
+            type_def.plays.push(Plays { relation, role, overrides });
+        }
+        for relates in &statement.relates {
+            type_def.relates.push(Relates {
+                role: type_reference_name(&relates.role_type)?,
+                overrides: relates.overridden_role_type.as_ref().map(type_reference_name).transpose()?,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, label: &str) -> Option<&TypeDef> {
+        self.types.get(label)
+    }
+
+    /// Adds a type, replacing any type with the same label.
+    pub fn add_type(&mut self, type_def: TypeDef) {
+        self.types.insert(type_def.label.clone(), type_def);
+    }
+
+    /// Adds a rule, replacing any rule with the same label, as defining it again does in TypeDB.
+    pub fn add_rule(&mut self, rule: Rule) {
+        match self.rules.iter_mut().find(|existing| existing.label == rule.label) {
+            Some(existing) => *existing = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// The types ordered by label.
+    pub fn types(&self) -> impl Iterator<Item = &TypeDef> {
+        self.types.values()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The schema without the given types.
+    pub fn without(mut self, labels: &[&str]) -> Self {
+        self.types.retain(|label, _| !labels.contains(&label.as_str()));
+        self
+    }
+
+    /// Whether both schemas define the same types and rules, regardless of the order of statements and of
+    /// value types that are only inherited, as in the schema TypeDB reports for a database.
+    pub fn same_definitions(&self, other: &Schema) -> bool {
+        self.diff(other).is_empty()
+    }
+
+    // tag::schema-diff[]
+    /// The changes that turn this schema into `other`, ordered by type label and then by rule label. Statement
+    /// order and value types that are only inherited make no difference.
+    pub fn diff(&self, other: &Schema) -> Vec<SchemaChange> {
+        let mut changes = Vec::new();
+        let labels = self.types.keys().chain(other.types.keys()).collect::<BTreeSet<_>>();
+        for label in labels {
+            match (self.types.get(label), other.types.get(label)) {
+                (Some(_), None) => changes.push(SchemaChange::Removed(format!("type {}", label))),
+                (None, Some(_)) => changes.push(SchemaChange::Added(format!("type {}", label))),
+                (Some(old), Some(new)) => self.diff_type(old, other, new, &mut changes),
+                (None, None) => unreachable!(),
+            }
+        }
+        let rules = |schema: &Schema| {
+            schema.rules.iter().map(|rule| (rule.label.name.clone(), rule.to_string())).collect::<BTreeMap<_, _>>()
+        };
+        diff_items(rules(self), rules(other), |label| format!("rule {}", label), &mut changes);
+        changes
+    }
+
+    fn diff_type(&self, old: &TypeDef, other: &Schema, new: &TypeDef, changes: &mut Vec<SchemaChange>) {
+        let label = &old.label;
+        let mut changed = |property: &str, from: Option<String>, to: Option<String>| {
+            if from != to {
+                let (from, to) = (from.unwrap_or_else(|| "none".to_owned()), to.unwrap_or_else(|| "none".to_owned()));
+                changes.push(SchemaChange::Changed { item: format!("{} {}", label, property), from, to });
+            }
+        };
+        changed("sub", old.supertype.clone(), new.supertype.clone());
+        changed("abstract", Some(old.is_abstract.to_string()), Some(new.is_abstract.to_string()));
+        let value_type = |schema: &Schema| {
+            schema
+                .supertypes(label)
+                .find_map(|label| schema.types[label].value_type)
+                .map(|value_type| value_type.to_string())
+        };
+        changed("value", value_type(self), value_type(other));
+
+        let owns = |type_def: &TypeDef| {
+            type_def.owns.iter().map(|owns| (owns.attribute.clone(), owns.to_string())).collect::<BTreeMap<_, _>>()
+        };
+        diff_items(owns(old), owns(new), |attribute| format!("{} owns {}", label, attribute), changes);
+        let plays = |type_def: &TypeDef| {
+            let plays =
+                type_def.plays.iter().map(|plays| (format!("{}:{}", plays.relation, plays.role), plays.to_string()));
+            plays.collect::<BTreeMap<_, _>>()
+        };
+        diff_items(plays(old), plays(new), |role| format!("{} plays {}", label, role), changes);
+        let relates = |type_def: &TypeDef| {
+            type_def
+                .relates
+                .iter()
+                .map(|relates| (relates.role.clone(), relates.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        diff_items(relates(old), relates(new), |role| format!("{} relates {}", label, role), changes);
+    }
+    // end::schema-diff[]
+
+    // tag::schema-export[]
+    /// The schema as a `define` query in a stable layout: attribute types, then relation types, then entity types,
+    /// each type followed by its subtypes in label order, and then the rules in label order. A type lists whether
+    /// it is abstract, its value type, and the attributes it owns, roles it relates and roles it plays, each sorted.
+    pub fn to_typeql(&self) -> String {
+        let mut groups = Vec::new();
+        for root in ["attribute", "relation", "entity"] {
+            let mut statements = Vec::new();
+            self.write_subtypes(root, &mut statements);
+            let separator = if root == "attribute" { "\n" } else { "\n\n" };
+            if !statements.is_empty() {
+                groups.push(format!("{}\n", statements.join(separator)));
+            }
+        }
+        let mut rules = self.rules.iter().collect::<Vec<_>>();
+        rules.sort_by(|a, b| a.label.name.cmp(&b.label.name));
+        groups.extend(rules.into_iter().map(|rule| format!("{};\n", rule)));
+        format!("define\n\n{}", groups.join("\n"))
+    }
+
+    fn write_subtypes(&self, supertype: &str, statements: &mut Vec<String>) {
+        let subtypes = self.types.values().filter(|type_def| type_def.supertype.as_deref() == Some(supertype));
+        for type_def in subtypes {
+            statements.push(type_def.to_typeql());
+            self.write_subtypes(&type_def.label, statements);
+        }
+    }
+    // end::schema-export[]
+
+    /// The type itself followed by its supertypes, nearest first, stopping before the root types. A cyclic
+    /// hierarchy is cut off after every type has been visited once.
+    pub fn supertypes<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        let mut next = Some(label).filter(|label| self.types.contains_key(*label));
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = self.types.get(current).and_then(|type_def| type_def.supertype.as_deref());
+            next = next.filter(|label| self.types.contains_key(*label));
+            Some(current)
+        })
+        .take(self.types.len())
+    }
+
+    /// Whether `label` is `ancestor` or one of its subtypes; the root types `thing`, `entity`, `relation` and
+    /// `attribute` are accepted as ancestors too.
+    pub fn is_subtype(&self, label: &str, ancestor: &str) -> bool {
+        match ancestor {
+            "thing" => self.kind(label).is_some(),
+            "entity" | "relation" | "attribute" => self.kind(label).is_some_and(|kind| root_label(kind) == ancestor),
+            _ => self.supertypes(label).any(|supertype| supertype == ancestor),
+        }
+    }
+
+    pub fn kind(&self, label: &str) -> Option<Kind> {
+        let root = self.supertypes(label).last()?;
+        match self.types.get(root)?.supertype.as_deref()? {
+            "entity" => Some(Kind::Entity),
+            "relation" => Some(Kind::Relation),
+            "attribute" => Some(Kind::Attribute),
+            _ => None,
+        }
+    }
+
+    /// The value type of an attribute type, declared by it or one of its supertypes.
+    pub fn value_type(&self, attribute: &str) -> Option<ValueType> {
+        self.supertypes(attribute).find_map(|label| self.types[label].value_type)
+    }
+
+    /// Whether the type or one of its supertypes owns the attribute type.
+    pub fn owns(&self, label: &str, attribute: &str) -> bool {
+        self.supertypes(label)
+            .any(|supertype| self.types[supertype].owns.iter().any(|owns| owns.attribute == attribute))
+    }
+
+    /// Whether the relation type or one of its supertypes relates the role, directly or through an override.
+    pub fn relates(&self, relation: &str, role: &str) -> bool {
+        self.supertypes(relation).any(|supertype| {
+            self.types[supertype]
+                .relates
+                .iter()
+                .any(|relates| relates.role == role || relates.overrides.as_deref() == Some(role))
+        })
+    }
+
+    /// Whether `role` on `relation` is `ancestor` or specialises it via `relates ... as ...`.
+    pub fn is_role_subtype(&self, relation: &str, role: &str, ancestor: &str) -> bool {
+        role == ancestor
+            || self.supertypes(relation).any(|supertype| {
+                self.types[supertype]
+                    .relates
+                    .iter()
+                    .any(|relates| relates.role == role && relates.overrides.as_deref() == Some(ancestor))
+            })
+    }
+}
+
+pub fn root_label(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Entity => "entity",
+        Kind::Relation => "relation",
+        Kind::Attribute => "attribute",
+    }
+}
+
+fn type_reference_name(reference: &TypeReference) -> Result<String, SchemaError> {
+    match reference {
+        TypeReference::Label(label) => Ok(label.name.clone()),
+        TypeReference::Variable(variable) => Err(SchemaError::Unsupported(format!("type variable {}", variable))),
+    }
+}
+
+/// Returns the text after a leading query keyword, skipping whitespace and `#` comments before it.
+pub fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with('#') {
+            rest = rest.split_once('\n').map_or("", |(_, tail)| tail);
+        } else {
+            break;
+        }
+    }
+    rest.strip_prefix(keyword).filter(|tail| tail.starts_with(char::is_whitespace))
+}
+// end::schema-model[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iam_schema() -> Schema {
+        Schema::from_file(Path::new(env!("CARGO_MANIFEST_DIR")).join("iam-schema.tql")).unwrap()
+    }
+
+    #[test]
+    fn parses_iam_schema() {
+        let schema = iam_schema();
+        let person = schema.get("person").unwrap();
+        assert_eq!(person.supertype.as_deref(), Some("user"));
+        assert_eq!(schema.supertypes("person").collect::<Vec<_>>(), ["person", "user", "subject"]);
+        assert_eq!(schema.kind("person"), Some(Kind::Entity));
+        assert_eq!(schema.kind("group-membership"), Some(Kind::Relation));
+        assert_eq!(schema.kind("email"), Some(Kind::Attribute));
+        assert_eq!(schema.get("size-kb").unwrap().value_type, Some(ValueType::Long));
+        assert!(schema.get("user").unwrap().is_abstract);
+        assert!(schema.is_subtype("file", "object"));
+        assert!(schema.is_subtype("file", "entity"));
+        assert!(!schema.is_subtype("file", "subject"));
+        assert!(schema.owns("person", "credential"));
+        assert!(!schema.owns("file", "email"));
+        assert!(schema.relates("group-membership", "member"));
+        assert!(schema.relates("group-membership", "group"));
+        assert!(schema.is_role_subtype("group-membership", "group", "parent"));
+        assert_eq!(schema.rules.len(), 1);
+    }
+
+    #[test]
+    fn compares_definitions_regardless_of_order() {
+        let schema = Schema::parse(
+            "define name sub attribute, abstract, value string; email sub name, value string; \
+             person sub entity, owns name, owns email, plays friendship:friend; \
+             friendship sub relation, relates friend;",
+        )
+        .unwrap();
+        let reordered = Schema::parse(
+            "define friendship sub relation, relates friend; email sub name; \
+             person sub entity, plays friendship:friend, owns email, owns name; \
+             name sub attribute, abstract, value string;",
+        )
+        .unwrap();
+        assert!(schema.same_definitions(&reordered));
+        assert!(iam_schema().same_definitions(&iam_schema()));
+        assert!(!schema.same_definitions(&iam_schema()));
+        let extended = Schema::parse("define person sub entity, owns name, owns email, owns nickname;").unwrap();
+        assert!(!schema.same_definitions(&extended));
+    }
+
+    #[test]
+    fn diff_names_added_removed_and_changed_items() {
+        let old = Schema::parse(
+            "define name sub attribute, value string; person sub entity, owns name; \
+             friendship sub relation, relates friend; person plays friendship:friend;",
+        )
+        .unwrap();
+        let new = Schema::parse(
+            "define name sub attribute, value string; nickname sub attribute, value string; \
+             person sub entity, abstract, owns name @key, owns nickname; friendship sub relation, relates friend;",
+        )
+        .unwrap();
+        let changes = old.diff(&new).iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                "+ type nickname",
+                "~ person abstract: false -> true",
+                "~ person owns name: owns name -> owns name @key",
+                "+ person owns nickname",
+                "- person plays friendship:friend",
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+        let old = Schema::parse("define age sub attribute, value long; adult sub entity;").unwrap();
+        let new = Schema::parse("define age sub attribute; being sub entity; adult sub being;").unwrap();
+        let changes = old.diff(&new).iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(changes, ["~ adult sub: entity -> being", "~ age value: long -> none", "+ type being"]);
+    }
+
+    #[test]
+    fn exports_iam_schema_as_written() {
+        let file = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("iam-schema.tql")).unwrap();
+        let types = |text: &str| text[text.find("define").unwrap()..text.find("rule ").unwrap()].to_owned();
+        let exported = iam_schema().to_typeql();
+        assert_eq!(types(&exported), types(&file));
+        assert!(Schema::parse(&exported).unwrap().same_definitions(&iam_schema()));
+    }
+
+    #[test]
+    fn strips_leading_keyword_after_comments() {
+        assert_eq!(strip_keyword("# licence\n#\ndefine\nperson sub entity;", "define"), Some("\nperson sub entity;"));
+        assert_eq!(strip_keyword("defined sub entity;", "define"), None);
+    }
+}
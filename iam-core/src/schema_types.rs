@@ -0,0 +1,4 @@
+//! Rust types for the concrete entity and relation types of `iam-schema.tql`, generated by `build.rs` with
+//! [`crate::codegen`] so that they cannot drift from the schema. Relations come with an enum of their roles. The
+//! entity types are re-exported by [`crate::models`], which the stores decode answers into.
+include!(concat!(env!("OUT_DIR"), "/schema_types.rs"));
@@ -0,0 +1,545 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    sync::RwLock,
+};
+
+use typeql::pattern::Constant;
+
+use super::{IamStore, ViewableFile};
+use crate::{
+    database::IAM_SEED,
+    decision::{self, AccessRequest, Decision, Grant},
+    error::AppError,
+    explanation::{ExplainedFile, Explanation, Fact},
+    graph::{Graph, Thing, ThingId},
+    models::Person,
+    rules::{Derivation, RuleEngine, RuleError},
+    schema::Schema,
+    IAM_DATA, IAM_DATA_FILE, IAM_SCHEMA_FILE,
+};
+
+/// [`IamStore`] over an in-memory [`Graph`], for running the sample app without a TypeDB server. The schema rules
+/// are evaluated by a [`RuleEngine`] when a query asks for inference.
+pub struct MemoryStore {
+    graph: RwLock<Graph>,
+    rules: RuleEngine,
+}
+
+impl MemoryStore {
+    pub fn new(graph: Graph) -> Result<Self, RuleError> {
+        let rules = RuleEngine::from_schema(graph.schema())?;
+        Ok(Self { graph: RwLock::new(graph), rules })
+    }
+
+    /// The graph with what the rules infer from it when `inference` is enabled.
+    fn with_inference<'a>(&self, explicit: &'a Graph, inference: bool) -> Result<Cow<'a, Graph>, AppError> {
+        Ok(if inference { Cow::Owned(self.rules.infer(explicit)?) } else { Cow::Borrowed(explicit) })
+    }
+
+    /// A store loaded with the IAM schema and dataset of [`IAM_SEED`], with the rules of the later IAM migrations.
+    pub fn iam() -> Result<Self, AppError> {
+        Self::from_graph(IAM_SCHEMA_FILE, IAM_SEED.parse_schema()?, IAM_DATA_FILE, IAM_DATA)
+    }
+
+    pub fn from_files(schema_file: impl AsRef<Path>, data_file: impl AsRef<Path>) -> Result<Self, AppError> {
+        let (schema_file, data_file) =
+            (schema_file.as_ref().display().to_string(), data_file.as_ref().display().to_string());
+        let schema = fs::read_to_string(&schema_file).map_err(|error| AppError::schema_load(&schema_file, error))?;
+        let data = fs::read_to_string(&data_file).map_err(|error| AppError::schema_load(&data_file, error))?;
+        Self::from_tql(&schema_file, &schema, &data_file, &data)
+    }
+
+    /// A store loaded with the `schema` definition and the `data` insert query, read from the named files.
+    pub fn from_tql(schema_file: &str, schema: &str, data_file: &str, data: &str) -> Result<Self, AppError> {
+        let schema = Schema::parse(schema).map_err(|error| AppError::schema_load(schema_file, error))?;
+        Self::from_graph(schema_file, schema, data_file, data)
+    }
+
+    /// A store of the schema read from `schema_file`, loaded with the `data` insert query read from `data_file`.
+    fn from_graph(schema_file: &str, schema: Schema, data_file: &str, data: &str) -> Result<Self, AppError> {
+        let mut graph = Graph::new(schema);
+        graph.load(data).map_err(|error| AppError::schema_load(data_file, error))?;
+        Self::new(graph).map_err(|error| AppError::schema_load(schema_file, error))
+    }
+}
+
+fn action_name<'a>(graph: &'a Graph, access: &'a Thing) -> impl Iterator<Item = &'a str> + 'a {
+    graph
+        .players(access, "action")
+        .filter_map(|action| graph.get(action))
+        .filter(|action| graph.schema().is_subtype(&action.type_, "action"))
+        .flat_map(|action| action.strings("name"))
+}
+
+fn objects<'a>(graph: &'a Graph, access: &'a Thing) -> impl Iterator<Item = ThingId> + 'a {
+    graph
+        .players(access, "object")
+        .filter(|object| graph.get(*object).is_some_and(|object| graph.schema().is_subtype(&object.type_, "object")))
+}
+
+fn subject(graph: &Graph, subject: &str) -> Result<ThingId, AppError> {
+    only(graph, "subject", &format!("with id or full-name {}", subject), |thing| {
+        has_id(graph, thing, subject) || thing.has_string("full-name", subject)
+    })
+}
+
+/// The groups that the subject is a member of.
+fn groups(graph: &Graph, subject: ThingId) -> BTreeSet<ThingId> {
+    graph
+        .relations("group-membership", "member", subject)
+        .flat_map(|(_, membership)| graph.players(membership, "group"))
+        .collect()
+}
+
+fn user(graph: &Graph, full_name: &str) -> Result<ThingId, AppError> {
+    only(graph, "user", &format!("with full-name {}", full_name), |user| user.has_string("full-name", full_name))
+}
+
+/// The `view_file` permissions of the user, with each object they grant access to.
+fn view_permissions(graph: &Graph, user: ThingId) -> impl Iterator<Item = (ThingId, ThingId)> + '_ {
+    graph.relations("permission", "subject", user).flat_map(move |(permission, relation)| {
+        graph
+            .players(relation, "access")
+            .filter_map(|access| graph.get(access))
+            .filter(|access| action_name(graph, access).any(|name| name == "view_file"))
+            .flat_map(|access| objects(graph, access))
+            .map(move |object| (permission, object))
+    })
+}
+
+/// A thing as one of its identifiers, or a relation as its role players; the type label when it has neither.
+fn describe(graph: &Graph, id: ThingId) -> String {
+    let Some(thing) = graph.get(id) else { return format!("#{}", id) };
+    if !thing.role_players.is_empty() {
+        return fact(graph, id).to_string();
+    }
+    let identifier = thing.attributes.iter().find_map(|(attribute, value)| match value {
+        Constant::String(value) if graph.schema().is_subtype(attribute, "id") => Some(value.clone()),
+        _ => None,
+    });
+    identifier.unwrap_or_else(|| thing.type_.clone())
+}
+
+/// A relation of the graph as a fact, without explanations.
+fn fact(graph: &Graph, relation: ThingId) -> Fact {
+    let thing = graph.get(relation);
+    Fact {
+        type_: thing.map(|thing| thing.type_.clone()).unwrap_or_default(),
+        players: thing
+            .iter()
+            .flat_map(|thing| &thing.role_players)
+            .map(|(role, player)| (role.clone(), describe(graph, *player)))
+            .collect(),
+        inferred: false,
+        explanations: Vec::new(),
+    }
+}
+
+/// A relation of the inferred graph with its derivations, and theirs in turn. `explaining` holds the relations
+/// being explained further up, so that a relation that helps derive itself is not explained again.
+fn explained_fact(
+    graph: &Graph,
+    explicit: &Graph,
+    derivations: &[Derivation],
+    relation: ThingId,
+    explaining: &mut Vec<ThingId>,
+) -> Fact {
+    let mut fact = fact(graph, relation);
+    fact.inferred = explicit.get(relation).is_none();
+    if fact.inferred && !explaining.contains(&relation) {
+        explaining.push(relation);
+        fact.explanations = derivations
+            .iter()
+            .filter(|derivation| derivation.conclusion == relation)
+            .map(|derivation| Explanation {
+                rule: derivation.rule.clone(),
+                condition: derivation
+                    .condition
+                    .iter()
+                    .map(|matched| explained_fact(graph, explicit, derivations, *matched, explaining))
+                    .collect(),
+            })
+            .collect();
+        explaining.pop();
+    }
+    fact
+}
+
+/// Whether the thing owns the value as one of its identifiers, i.e. as an attribute of a subtype of `id`.
+fn has_id(graph: &Graph, thing: &Thing, value: &str) -> bool {
+    thing.attributes.iter().any(|(attribute, owned)| {
+        graph.schema().is_subtype(attribute, "id") && *owned == Constant::String(value.to_owned())
+    })
+}
+
+/// The only instance of the type that matches, failing with [`AppError::NotFound`] or [`AppError::Ambiguous`]
+/// otherwise. `described` names the instances in the error, e.g. `object with id x`.
+fn only(graph: &Graph, type_: &str, described: &str, matches: impl Fn(&Thing) -> bool) -> Result<ThingId, AppError> {
+    let found = graph.instances(type_).filter(|(_, thing)| matches(thing)).map(|(id, _)| id).collect::<Vec<_>>();
+    match found.as_slice() {
+        [id] => Ok(*id),
+        [] => Err(AppError::NotFound(format!("{} {}", type_, described))),
+        found => Err(AppError::Ambiguous(format!("{} {}s {}", found.len(), type_, described))),
+    }
+}
+
+fn boolean(thing: &Thing, attribute: &str) -> Option<bool> {
+    thing.values(attribute).find_map(|value| match value {
+        Constant::Boolean(value) => Some(*value),
+        _ => None,
+    })
+}
+
+impl IamStore for MemoryStore {
+    fn users(&self) -> Result<Vec<Person>, AppError> {
+        let graph = self.graph.read().unwrap();
+        Ok(graph
+            .instances("user")
+            .map(|(_, person)| Person {
+                full_name: person.strings("full-name").next().map(str::to_owned),
+                email: person.strings("email").next().map(str::to_owned),
+                credential: person.strings("credential").next().map(str::to_owned),
+            })
+            .collect())
+    }
+
+    fn insert_user(&self, full_name: &str, email: &str) -> Result<Vec<Person>, AppError> {
+        let mut graph = self.graph.write().unwrap();
+        graph.insert(Thing {
+            type_: "person".to_owned(),
+            attributes: vec![
+                ("full-name".to_owned(), Constant::String(full_name.to_owned())),
+                ("email".to_owned(), Constant::String(email.to_owned())),
+            ],
+            role_players: Vec::new(),
+        })?;
+        Ok(vec![Person { full_name: Some(full_name.to_owned()), email: Some(email.to_owned()), credential: None }])
+    }
+
+    fn files_by_user(&self, full_name: &str, inference: bool) -> Result<Vec<ViewableFile>, AppError> {
+        let explicit = self.graph.read().unwrap();
+        let graph = &*self.with_inference(&explicit, inference)?;
+        let user = user(graph, full_name)?;
+        let paths = |object: ThingId| graph.get(object).into_iter().flat_map(|object| object.strings("path"));
+        let invalid =
+            |permission| graph.get(permission).and_then(|permission| boolean(permission, "validity")) == Some(false);
+        // The groups with a valid permission for each file, and whether all of those permissions are inferred.
+        let mut files = BTreeMap::<String, BTreeMap<ThingId, bool>>::new();
+        for (permission, object) in view_permissions(graph, user) {
+            if invalid(permission) {
+                continue;
+            }
+            for path in paths(object) {
+                files.entry(path.to_owned()).or_default();
+            }
+        }
+        let groups = if inference { groups(graph, user) } else { BTreeSet::new() };
+        for &group in &groups {
+            for (permission, object) in view_permissions(graph, group) {
+                if invalid(permission) {
+                    continue;
+                }
+                let inferred = explicit.get(permission).is_none();
+                for path in paths(object) {
+                    if let Some(granting) = files.get_mut(path) {
+                        *granting.entry(group).or_insert(true) &= inferred;
+                    }
+                }
+            }
+        }
+        let files = files.into_iter().map(|(path, granting)| {
+            let granting =
+                decision::granting_groups(granting, |group, other| self::groups(graph, *group).contains(other));
+            let groups = granting.into_iter().map(|group| describe(graph, group)).collect::<BTreeSet<_>>();
+            ViewableFile { path, groups: groups.into_iter().collect() }
+        });
+        Ok(files.collect())
+    }
+
+    fn decide(&self, request: &AccessRequest, inference: bool) -> Result<Decision, AppError> {
+        let explicit = self.graph.read().unwrap();
+        let graph = &*self.with_inference(&explicit, inference)?;
+        let AccessRequest { subject, action, object } = request;
+        let subject = self::subject(graph, subject)?;
+        let object = only(graph, "object", &format!("with id {}", object), |thing| has_id(graph, thing, object))?;
+        only(graph, "action", &format!("with name {}", action), |thing| thing.has_string("name", action))?;
+        let grants_access = |access: &ThingId| {
+            graph.get(*access).is_some_and(|access| {
+                objects(graph, access).any(|played| played == object)
+                    && action_name(graph, access).any(|name| name == action)
+            })
+        };
+        let grants_of = |holder: ThingId, group: Option<String>| {
+            let mut grants = Vec::new();
+            for (permission_id, permission) in graph.relations("permission", "subject", holder) {
+                for access in graph.players(permission, "access").filter(grants_access) {
+                    grants.push(Grant {
+                        permission: format!("#{}", permission_id),
+                        access: format!("#{}", access),
+                        inferred: explicit.get(permission_id).is_none(),
+                        group: group.clone(),
+                        validity: boolean(permission, "validity"),
+                        review_date: permission.values("review-date").find_map(|value| match value {
+                            Constant::DateTime(value) => Some(*value),
+                            _ => None,
+                        }),
+                    });
+                }
+            }
+            grants
+        };
+        let mut grants = grants_of(subject, None).into_iter().map(|grant| (subject, grant)).collect::<Vec<_>>();
+        if inference {
+            for group in groups(graph, subject) {
+                grants.extend(grants_of(group, Some(describe(graph, group))).into_iter().map(|grant| (group, grant)));
+            }
+        }
+        let grants = decision::without_inherited(grants, |holder, group| groups(graph, *holder).contains(group));
+        Ok(Decision::new(request.clone(), grants))
+    }
+
+    fn groups(&self, subject: &str, inference: bool) -> Result<Vec<String>, AppError> {
+        let explicit = self.graph.read().unwrap();
+        let graph = &*self.with_inference(&explicit, inference)?;
+        let subject = self::subject(graph, subject)?;
+        let groups = groups(graph, subject).into_iter().map(|group| describe(graph, group)).collect::<BTreeSet<_>>();
+        Ok(groups.into_iter().collect())
+    }
+
+    fn explain_files_by_user(&self, full_name: &str) -> Result<Vec<ExplainedFile>, AppError> {
+        let explicit = self.graph.read().unwrap();
+        let (graph, derivations) = self.rules.explain(&explicit)?;
+        let user = user(&graph, full_name)?;
+        let mut files = BTreeMap::<String, Vec<Fact>>::new();
+        for (permission, object) in view_permissions(&graph, user) {
+            for path in graph.get(object).into_iter().flat_map(|object| object.strings("path")) {
+                let fact = explained_fact(&graph, &explicit, &derivations, permission, &mut Vec::new());
+                files.entry(path.to_owned()).or_default().push(fact);
+            }
+        }
+        Ok(files.into_iter().map(|(path, permissions)| ExplainedFile { path, permissions }).collect())
+    }
+
+    fn update_filepath(&self, old_path: &str, new_path: &str) -> Result<usize, AppError> {
+        let mut graph = self.graph.write().unwrap();
+        let files = graph
+            .instances("file")
+            .filter(|(_, file)| file.has_string("path", old_path))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for file in &files {
+            for (attribute, value) in &mut graph.get_mut(*file).unwrap().attributes {
+                if attribute == "path" && *value == Constant::String(old_path.to_owned()) {
+                    *value = Constant::String(new_path.to_owned());
+                }
+            }
+        }
+        Ok(files.len())
+    }
+
+    fn delete_file(&self, path: &str) -> Result<(), AppError> {
+        let mut graph = self.graph.write().unwrap();
+        let files = graph
+            .instances("file")
+            .filter(|(_, file)| file.has_string("path", path))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        match files.as_slice() {
+            [file] => {
+                graph.remove(*file);
+                Ok(())
+            }
+            [] => Err(AppError::NotFound(format!("file with path {}", path))),
+            files => Err(AppError::Ambiguous(format!("{} files with path {}", files.len(), path))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iam_store() -> MemoryStore {
+        MemoryStore::iam().unwrap()
+    }
+
+    fn paths(files: Vec<ViewableFile>) -> Vec<String> {
+        files.into_iter().map(|file| file.path).collect()
+    }
+
+    #[test]
+    fn files_by_user_honours_inference() {
+        let store = iam_store();
+        assert!(store.files_by_user("Kevin Morrison", false).unwrap().is_empty());
+        let files = store.files_by_user("Kevin Morrison", true).unwrap();
+        assert_eq!(files.len(), 10);
+        assert_eq!(files.first().map(|file| file.path.as_str()), Some("LICENSE"));
+        assert_eq!(store.files_by_user("Pearle Goodman", false).unwrap().len(), 5);
+        assert!(matches!(store.files_by_user("Nobody", true), Err(AppError::NotFound(_))));
+        store.insert_user("Kevin Morrison", "kevin2@typedb.com").unwrap();
+        assert!(matches!(store.files_by_user("Kevin Morrison", true), Err(AppError::Ambiguous(_))));
+    }
+
+    #[test]
+    fn renamed_and_deleted_files_disappear_from_results() {
+        let store = iam_store();
+        assert_eq!(store.update_filepath("lzfkn.java", "lzfkn2.java").unwrap(), 1);
+        assert_eq!(store.update_filepath("lzfkn.java", "lzfkn3.java").unwrap(), 0);
+        assert!(paths(store.files_by_user("Kevin Morrison", true).unwrap()).contains(&"lzfkn2.java".to_owned()));
+        store.delete_file("lzfkn2.java").unwrap();
+        assert_eq!(store.files_by_user("Kevin Morrison", true).unwrap().len(), 9);
+        assert!(matches!(store.delete_file("lzfkn2.java"), Err(AppError::NotFound(_))));
+    }
+
+    fn request(subject: &str, action: &str, object: &str) -> AccessRequest {
+        AccessRequest { subject: subject.to_owned(), action: action.to_owned(), object: object.to_owned() }
+    }
+
+    #[test]
+    fn decisions_honour_inference() {
+        let store = iam_store();
+        let view = request("kevin.morrison@typedb.com", "view_file", "LICENSE");
+        let explicit = store.decide(&view, false).unwrap();
+        assert!(!explicit.allowed());
+        assert!(explicit.grants.is_empty());
+        let inferred = store.decide(&view, true).unwrap();
+        assert!(inferred.allowed());
+        assert!(inferred.grants.iter().all(|grant| grant.inferred));
+        let modify = store.decide(&request("Kevin Morrison", "modify_file", "LICENSE"), false).unwrap();
+        assert!(modify.allowed() && !modify.grants[0].inferred);
+        for unknown in [request("Nobody", "view_file", "LICENSE"), request("Kevin Morrison", "fly", "LICENSE")] {
+            assert!(matches!(store.decide(&unknown, true), Err(AppError::NotFound(_))));
+        }
+    }
+
+    #[test]
+    fn invalid_permissions_deny() {
+        let store = iam_store();
+        let mut graph = store.graph.into_inner().unwrap();
+        let permissions = graph.instances("permission").map(|(id, _)| id).collect::<Vec<_>>();
+        for permission in permissions {
+            graph.add_attribute(permission, "validity", Constant::Boolean(false)).unwrap();
+        }
+        let store = MemoryStore::new(graph).unwrap();
+        let decision = store.decide(&request("Kevin Morrison", "modify_file", "LICENSE"), false).unwrap();
+        assert!(!decision.allowed());
+        assert_eq!(decision.grants.len(), 1);
+        assert_eq!(decision.grants[0].validity, Some(false));
+        // Invalid modify permissions do not imply view permissions.
+        let decision = store.decide(&request("Kevin Morrison", "view_file", "LICENSE"), true).unwrap();
+        assert!(!decision.allowed());
+        assert!(decision.grants.is_empty());
+        assert!(store.files_by_user("Kevin Morrison", true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_direct_permissions_hide_files() {
+        let mut graph = iam_store().graph.into_inner().unwrap();
+        let pearle = user(&graph, "Pearle Goodman").unwrap();
+        let (permission, object) = view_permissions(&graph, pearle).next().unwrap();
+        let path = graph.get(object).unwrap().strings("path").next().unwrap().to_owned();
+        graph.add_attribute(permission, "validity", Constant::Boolean(false)).unwrap();
+        let store = MemoryStore::new(graph).unwrap();
+        assert!(!store.decide(&request("Pearle Goodman", "view_file", &path), false).unwrap().allowed());
+        for inference in [false, true] {
+            let files = paths(store.files_by_user("Pearle Goodman", inference).unwrap());
+            assert_eq!(files.len(), 4);
+            assert!(!files.contains(&path));
+        }
+    }
+
+    #[test]
+    fn explains_inferred_view_permissions() {
+        let store = iam_store();
+        let files = store.explain_files_by_user("Kevin Morrison").unwrap();
+        assert_eq!(files.len(), 10);
+        assert_eq!(files[0].path, "LICENSE");
+        let permission = &files[0].permissions[0];
+        assert!(permission.inferred);
+        assert_eq!(permission.explanations.len(), 1);
+        let explanation = &permission.explanations[0];
+        assert_eq!(explanation.rule, "add-view-permission");
+        assert_eq!(explanation.condition.len(), 3);
+        assert!(explanation.condition.iter().all(|fact| !fact.inferred));
+        assert_eq!(
+            permission.tree(),
+            "permission (subject: kevin.morrison@typedb.com, access: access (object: LICENSE, action: view_file)) \
+             [inferred]
+└── rule add-view-permission
+    ├── access (object: LICENSE, action: modify_file)
+    ├── access (object: LICENSE, action: view_file)
+    └── permission (subject: kevin.morrison@typedb.com, access: access (object: LICENSE, action: modify_file))"
+        );
+
+        let files = store.explain_files_by_user("Pearle Goodman").unwrap();
+        assert!(files.iter().flat_map(|file| &file.permissions).any(|permission| !permission.inferred));
+        assert!(matches!(store.explain_files_by_user("Nobody"), Err(AppError::NotFound(_))));
+    }
+
+    /// Pearle Goodman in the developer role of the engineering business unit, which may view a file that Pearle
+    /// has no permission for. Returns the store and the path of that file.
+    fn engineering_store(validity: Option<bool>) -> (MemoryStore, String) {
+        let mut graph = iam_store().graph.into_inner().unwrap();
+        let viewable = paths(MemoryStore::new(graph.clone()).unwrap().files_by_user("Pearle Goodman", true).unwrap());
+        let (access, path) = graph
+            .instances("access")
+            .filter(|(_, access)| action_name(&graph, access).any(|name| name == "view_file"))
+            .flat_map(|(id, access)| {
+                let paths = objects(&graph, access).flat_map(|object| graph.get(object).unwrap().strings("path"));
+                paths.map(move |path| (id, path.to_owned())).collect::<Vec<_>>()
+            })
+            .find(|(_, path)| !viewable.contains(path))
+            .unwrap();
+        let (pearle, _) = graph.instances("person").find(|(_, p)| p.has_string("full-name", "Pearle Goodman")).unwrap();
+        let mut insert = |type_: &str, attributes: Vec<(&str, Constant)>, role_players: Vec<(&str, ThingId)>| {
+            let attributes = attributes.into_iter().map(|(attribute, value)| (attribute.to_owned(), value)).collect();
+            let role_players = role_players.into_iter().map(|(role, player)| (role.to_owned(), player)).collect();
+            graph.insert(Thing { type_: type_.to_owned(), attributes, role_players }).unwrap()
+        };
+        let engineering = insert("business-unit", vec![("name", Constant::String("engineering".to_owned()))], vec![]);
+        let developer = insert("user-role", vec![("name", Constant::String("developer".to_owned()))], vec![]);
+        insert("group-membership", vec![], vec![("group", engineering), ("member", developer)]);
+        insert("group-membership", vec![], vec![("group", developer), ("member", pearle)]);
+        let validity = validity.into_iter().map(|validity| ("validity", Constant::Boolean(validity))).collect();
+        insert("permission", validity, vec![("subject", engineering), ("access", access)]);
+        (MemoryStore::new(graph).unwrap(), path)
+    }
+
+    #[test]
+    fn members_inherit_the_permissions_of_their_groups() {
+        let (store, path) = engineering_store(None);
+        assert_eq!(store.groups("pearle.goodman@typedb.com", false).unwrap(), ["developer"]);
+        assert_eq!(store.groups("Pearle Goodman", true).unwrap(), ["developer", "engineering"]);
+        assert!(!paths(store.files_by_user("Pearle Goodman", false).unwrap()).contains(&path));
+        let files = store.files_by_user("Pearle Goodman", true).unwrap();
+        assert_eq!(files.into_iter().find(|file| file.path == path).unwrap().groups, ["engineering"]);
+
+        let view = request("Pearle Goodman", "view_file", &path);
+        assert!(!store.decide(&view, false).unwrap().allowed());
+        let decision = store.decide(&view, true).unwrap();
+        assert!(decision.allowed());
+        assert_eq!(decision.grants.len(), 1);
+        assert_eq!(decision.grants[0].group.as_deref(), Some("engineering"));
+        assert!(!decision.grants[0].inferred);
+
+        let file = store.explain_files_by_user("Pearle Goodman").unwrap().into_iter().find(|file| file.path == path);
+        let explanations = &file.unwrap().permissions[0].explanations;
+        assert_eq!(explanations.len(), 2);
+        assert!(explanations.iter().all(|explanation| explanation.rule == "inherit-group-permission"));
+        let transitive = explanations.iter().flat_map(|explanation| &explanation.condition[0].explanations);
+        assert_eq!(
+            transitive.map(|explanation| explanation.rule.as_str()).collect::<Vec<_>>(),
+            ["transitive-group-membership"]
+        );
+
+        let (store, path) = engineering_store(Some(false));
+        let decision = store.decide(&request("Pearle Goodman", "view_file", &path), true).unwrap();
+        assert!(!decision.allowed());
+        assert_eq!(decision.grants.len(), 1);
+        assert_eq!(decision.grants[0].group.as_deref(), Some("engineering"));
+        assert!(!paths(store.files_by_user("Pearle Goodman", true).unwrap()).contains(&path));
+    }
+}
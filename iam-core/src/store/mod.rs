@@ -0,0 +1,63 @@
+// tag::store[]
+//! Storage backends for the IAM operations performed by the sample app.
+mod memory;
+mod typedb;
+
+pub use self::{memory::MemoryStore, typedb::TypeDbStore};
+use crate::{
+    decision::{AccessRequest, Decision},
+    error::AppError,
+    explanation::ExplainedFile,
+    models::Person,
+};
+
+/// A file that a user may view. With inference, it comes with the identifiers of the groups whose valid
+/// permissions the user inherits access to it from, sorted in ascending order; without, or when only the user's own
+/// permissions grant it, there are none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ViewableFile {
+    pub path: String,
+    pub groups: Vec<String>,
+}
+
+/// The user, file, permission and access operations of the sample app, independent of where the data lives.
+pub trait IamStore {
+    /// All users: the instances of `user`, whose only concrete subtype in the IAM schema is `person`.
+    fn users(&self) -> Result<Vec<Person>, AppError>;
+
+    /// Inserts a person and returns the inserted users, one per answer of the insert query.
+    fn insert_user(&self, full_name: &str, email: &str) -> Result<Vec<Person>, AppError>;
+
+    /// The objects that the user with the given full name has a `view_file` permission for, sorted by path. With
+    /// `inference`, permissions derived by the schema rules are included, and each file names the groups it is
+    /// inherited from. Fails with [`AppError::NotFound`] or [`AppError::Ambiguous`] unless exactly one user has
+    /// that name.
+    fn files_by_user(&self, full_name: &str, inference: bool) -> Result<Vec<ViewableFile>, AppError>;
+
+    /// The identifiers of the groups that the subject with the given identifier or full name is a member of, sorted
+    /// in ascending order. With `inference`, the groups of those groups are included, and so on.
+    fn groups(&self, subject: &str, inference: bool) -> Result<Vec<String>, AppError>;
+
+    /// The files of [`files_by_user`](Self::files_by_user) with inference, each with the `view_file` permissions
+    /// that grant it and, for inferred permissions, the rules and facts they were inferred from.
+    fn explain_files_by_user(&self, full_name: &str) -> Result<Vec<ExplainedFile>, AppError>;
+
+    /// Renames a file path and returns the number of files that were updated.
+    fn update_filepath(&self, old_path: &str, new_path: &str) -> Result<usize, AppError>;
+
+    /// Deletes the only file with the given path, failing with [`AppError::NotFound`] or [`AppError::Ambiguous`]
+    /// otherwise.
+    fn delete_file(&self, path: &str) -> Result<(), AppError>;
+
+    /// Whether the subject may perform the action on the object of the request, with every permission that grants
+    /// it. With `inference`, permissions derived by the schema rules are included. Fails with
+    /// [`AppError::NotFound`] or [`AppError::Ambiguous`] unless the request names exactly one subject, action and
+    /// object.
+    fn decide(&self, request: &AccessRequest, inference: bool) -> Result<Decision, AppError>;
+
+    /// Whether the store can serve the operations, e.g. whether the server is reachable and the database exists.
+    fn ready(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+// end::store[]
@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use typedb_driver::{
+    answer::ConceptMap, concept::Concept, Connection, DatabaseManager, Error as TypeDBError, Options, Promise, Session,
+    SessionType, Transaction, TransactionType,
+};
+
+use typeql::{
+    pattern::{Conjunction, Pattern, Statement},
+    variable::{ConceptVariable, TypeReference},
+};
+
+use super::{IamStore, ViewableFile};
+use crate::{
+    decision::{self, AccessRequest, Decision, Grant},
+    error::AppError,
+    explanation::{ExplainedFile, Explanation, Fact},
+    models::{self, Model, Person, TypeQLEntity},
+    query::Query,
+    schema_types,
+};
+
+/// [`IamStore`] backed by a TypeDB database.
+pub struct TypeDbStore {
+    driver: Connection,
+    db_name: String,
+}
+
+impl TypeDbStore {
+    pub fn new(driver: Connection, db_name: String) -> Self {
+        Self { driver, db_name }
+    }
+
+    fn data_session(&self) -> Result<Session, TypeDBError> {
+        let databases = DatabaseManager::new(self.driver.clone());
+        Session::new(databases.get(&self.db_name)?, SessionType::Data)
+    }
+}
+
+/// The subject `$s`, action `$a` and object `$o` of an access request, with the accesses `$ac` that grant the action
+/// on the object.
+macro_rules! request_pattern {
+    () => {
+        "match
+            $s isa subject;
+            {{ $s has id {subject}; }} or {{ $s has full-name {subject}; }};
+            $o isa object, has id {object};
+            $a isa action, has name {action};
+            $ac (object: $o, action: $a) isa access;"
+    };
+}
+
+/// The queries of the permissions `$p` that grant the request: with their accesses, validity and review date.
+macro_rules! grant_queries {
+    ($pattern:expr) => {
+        [
+            concat!(request_pattern!(), $pattern, " get;"),
+            concat!(request_pattern!(), $pattern, " $p has validity $p-validity; get $p, $p-validity;"),
+            concat!(request_pattern!(), $pattern, " $p has review-date $p-review-date; get $p, $p-review-date;"),
+        ]
+    };
+}
+
+/// The permissions of the subject itself.
+const SUBJECT_GRANTS: [&str; 3] = grant_queries!("$p (subject: $s, access: $ac) isa permission;");
+
+/// The permissions of the groups `$g` of the subject.
+const GROUP_GRANTS: [&str; 3] = grant_queries!(
+    "(group: $g, member: $s) isa group-membership; $g has id $g-id; $p (subject: $g, access: $ac) isa permission;"
+);
+
+/// The IID of the entity or relation bound to `var`, and whether it was inferred.
+fn thing(answer: &ConceptMap, var: &str) -> Option<(String, bool)> {
+    match answer.get(var)? {
+        Concept::Entity(entity) => Some((entity.iid.to_string(), entity.is_inferred)),
+        Concept::Relation(relation) => Some((relation.iid.to_string(), relation.is_inferred)),
+        _ => None,
+    }
+}
+
+/// Checks that the query, which binds `var` to instances of `type_`, matches exactly one thing. `described` names
+/// the things in the error after their type, e.g. `with id x`.
+fn only(tx: &Transaction<'_>, query: Query, var: &str, type_: &str, described: &str) -> Result<(), AppError> {
+    let mut found = BTreeSet::new();
+    for answer in tx.query().get(&query.build()?)? {
+        found.extend(thing(&answer?, var).map(|(iid, _)| iid));
+    }
+    match found.len() {
+        1 => Ok(()),
+        0 => Err(AppError::NotFound(format!("{} {}", type_, described))),
+        count => Err(AppError::Ambiguous(format!("{} {}s {}", count, type_, described))),
+    }
+}
+
+/// The permissions that the [`grant_queries`] find, with the IID of the group that holds them if the queries bind
+/// `$g`.
+fn find_grants(
+    tx: &Transaction<'_>,
+    request: &AccessRequest,
+    queries: [&'static str; 3],
+) -> Result<Vec<(Option<String>, Grant)>, AppError> {
+    let query = |template| {
+        Query::new(template)
+            .bind("subject", &request.subject)
+            .bind("object", &request.object)
+            .bind("action", &request.action)
+            .build()
+    };
+    let [grants_query, validity_query, review_date_query] = queries;
+    let mut grants = BTreeMap::<_, Vec<(Option<String>, Grant)>>::new();
+    for answer in tx.query().get(&query(grants_query)?)? {
+        let answer = answer?;
+        if let (Some((permission, inferred)), Some((access, _))) = (thing(&answer, "p"), thing(&answer, "ac")) {
+            let holder = thing(&answer, "g").map(|(group, _)| group);
+            let group = models::all::<String>(&answer, "g", "id")?.into_iter().min();
+            let grant =
+                Grant { permission: permission.clone(), access, inferred, group, validity: None, review_date: None };
+            grants.entry(permission).or_default().push((holder, grant));
+        }
+    }
+    for answer in tx.query().get(&query(validity_query)?)? {
+        let answer = answer?;
+        if let Some(grants) = thing(&answer, "p").and_then(|(permission, _)| grants.get_mut(&permission)) {
+            let validity = schema_types::Permission::from_concept_map(&answer, "p")?.validity;
+            grants.iter_mut().for_each(|(_, grant)| grant.validity = validity);
+        }
+    }
+    for answer in tx.query().get(&query(review_date_query)?)? {
+        let answer = answer?;
+        if let Some(grants) = thing(&answer, "p").and_then(|(permission, _)| grants.get_mut(&permission)) {
+            let review_date = schema_types::Permission::from_concept_map(&answer, "p")?.review_date;
+            grants.iter_mut().for_each(|(_, grant)| grant.review_date = review_date);
+        }
+    }
+    Ok(grants.into_values().flatten().collect())
+}
+
+/// Checks that exactly one subject has the identifier or full name.
+fn check_subject(tx: &Transaction<'_>, subject: &str) -> Result<(), AppError> {
+    let query =
+        Query::new("match $s isa subject; {{ $s has id {subject}; }} or {{ $s has full-name {subject}; }}; get $s;")
+            .bind("subject", subject);
+    only(tx, query, "s", "subject", &format!("with id or full-name {}", subject))
+}
+
+/// A relation statement of a query or rule: its variable, type and `(role, player variable)` pairs.
+struct RelationPattern {
+    var: String,
+    type_: String,
+    role_players: Vec<(String, String)>,
+}
+
+impl RelationPattern {
+    fn new(var: &str, type_: &str, role_players: &[(&str, &str)]) -> Self {
+        let role_players = role_players.iter().map(|(role, player)| (role.to_string(), player.to_string())).collect();
+        Self { var: var.to_owned(), type_: type_.to_owned(), role_players }
+    }
+}
+
+/// The relation statements of a rule condition. Anonymous variables are named `_1`, `_2`, ..., which no answer
+/// binds, so the relations they match are described by their players alone.
+fn relation_patterns(conjunction: &Conjunction) -> Vec<RelationPattern> {
+    let mut anonymous = 0;
+    let mut variable = |var: &ConceptVariable| match var {
+        ConceptVariable::Named(name) => name.clone(),
+        _ => {
+            anonymous += 1;
+            format!("_{}", anonymous)
+        }
+    };
+    let label = |type_: Option<&TypeReference>| match type_ {
+        Some(TypeReference::Label(label)) => Some(label.name.clone()),
+        _ => None,
+    };
+    let mut patterns = Vec::new();
+    for pattern in &conjunction.patterns {
+        let Pattern::Statement(Statement::Thing(statement)) = pattern else { continue };
+        let Some(relation) = &statement.relation else { continue };
+        let role_players = relation
+            .role_players
+            .iter()
+            .map(|role_player| {
+                (label(role_player.role_type.as_ref()).unwrap_or_default(), variable(&role_player.player))
+            })
+            .collect();
+        patterns.push(RelationPattern {
+            var: variable(&statement.variable),
+            type_: label(statement.isa.as_ref().map(|isa| &isa.type_reference))
+                .unwrap_or_else(|| "relation".to_owned()),
+            role_players,
+        });
+    }
+    patterns
+}
+
+/// Builds explained facts from the answers of an explainable transaction.
+struct Explainer<'a, 'tx> {
+    tx: &'a Transaction<'tx>,
+    /// The descriptions of things by IID.
+    descriptions: HashMap<String, String>,
+}
+
+impl Explainer<'_, '_> {
+    /// The first identifier of the thing bound to `var` in sorted order, or its type label.
+    fn describe(&mut self, answer: &ConceptMap, var: &str) -> Result<String, AppError> {
+        let (iid, label) = match answer.get(var) {
+            Some(Concept::Entity(entity)) => (entity.iid.to_string(), &entity.type_.label),
+            Some(Concept::Relation(relation)) => (relation.iid.to_string(), &relation.type_.label),
+            _ => return Ok(format!("${}", var)),
+        };
+        if let Some(description) = self.descriptions.get(&iid) {
+            return Ok(description.clone());
+        }
+        let query = format!("match $x iid {}; $x has id $x-id; get $x-id;", iid);
+        let mut identifiers = Vec::new();
+        for identifier in self.tx.query().get(&query)? {
+            identifiers.extend(models::all::<String>(&identifier?, "x", "id")?);
+        }
+        let description = identifiers.into_iter().min().unwrap_or_else(|| label.clone());
+        self.descriptions.insert(iid, description.clone());
+        Ok(description)
+    }
+
+    /// The relation that the pattern for `var` matched in the answer. With `explain`, an inferred relation comes
+    /// with the explanations of the server, whose conditions are explained in turn.
+    fn fact(
+        &mut self,
+        patterns: &[RelationPattern],
+        var: &str,
+        answer: &ConceptMap,
+        explain: bool,
+    ) -> Result<Fact, AppError> {
+        let pattern = patterns
+            .iter()
+            .find(|pattern| pattern.var == var)
+            .ok_or_else(|| models::ModelError::MissingVariable(var.to_owned()))?;
+        let mut players = Vec::new();
+        for (role, player) in &pattern.role_players {
+            let description = if patterns.iter().any(|pattern| pattern.var == *player) {
+                self.fact(patterns, player, answer, false)?.to_string()
+            } else {
+                self.describe(answer, player)?
+            };
+            players.push((role.clone(), description));
+        }
+        let inferred = matches!(answer.get(var), Some(Concept::Relation(relation)) if relation.is_inferred);
+        let mut explanations = Vec::new();
+        if let Some(explainable) = answer.explainables.relations.get(var).filter(|_| explain) {
+            for explanation in self.tx.query().explain(explainable)? {
+                let explanation = explanation?;
+                let patterns = relation_patterns(&explanation.rule.when);
+                let condition = patterns
+                    .iter()
+                    .map(|pattern| self.fact(&patterns, &pattern.var, &explanation.condition, true))
+                    .collect::<Result<_, _>>()?;
+                explanations.push(Explanation { rule: explanation.rule.label, condition });
+            }
+        }
+        Ok(Fact { type_: pattern.type_.clone(), players, inferred, explanations })
+    }
+}
+
+impl IamStore for TypeDbStore {
+    // tag::fetch[]
+    fn users(&self) -> Result<Vec<Person>, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction(TransactionType::Read)?;
+        let query = format!("match $u isa user; fetch {};", Person::fetch_projection("u"));
+        let iterator = tx.query().fetch(&query)?;
+        let mut result = vec![];
+        for item in iterator {
+            result.push(Person::from_json(&item?, "u")?);
+        }
+        Ok(result)
+    }
+    // end::fetch[]
+
+    // tag::insert[]
+    fn insert_user(&self, full_name: &str, email: &str) -> Result<Vec<Person>, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction(TransactionType::Write)?;
+        let person = Person { full_name: Some(full_name.to_owned()), email: Some(email.to_owned()), credential: None };
+        let query = format!("insert {};", person.insert_pattern("p")?);
+        let iterator = tx.query().insert(&query)?;
+        let mut result = vec![];
+        for item in iterator {
+            item?;
+            result.push(person.clone());
+        }
+        if !result.is_empty() {
+            tx.commit().resolve()?;
+        }
+        Ok(result)
+    }
+    // end::insert[]
+
+    // tag::get[]
+    fn files_by_user(&self, full_name: &str, inference: bool) -> Result<Vec<ViewableFile>, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction_with_options(TransactionType::Read, Options::new().infer(inference))?;
+        let query = Query::new("match $u isa user, has full-name {name}; get;").bind("name", full_name).build()?;
+        let users = tx.query().get(&query)?.collect::<Result<Vec<_>, _>>()?;
+        if users.len() > 1 {
+            Err(AppError::Ambiguous(format!("{} users with full-name {}", users.len(), full_name)))
+        } else if users.len() == 1 {
+            let query = Query::new(
+                "match
+                    $fn == {name};
+                    $u isa user, has full-name $fn;
+                    $p($u, $pa) isa permission;
+                    not {{ $p has validity false; }};
+                    $o isa object, has path $o-path;
+                    $pa($o, $va) isa access;
+                    $va isa action, has name 'view_file';
+                    get $o-path; sort $o-path asc;
+                    ",
+            )
+            .bind("name", full_name)
+            .build()?;
+            let response = tx.query().get(&query)?;
+            // The groups with a valid permission for each file, by IID, and whether all of those are inferred.
+            let mut files = BTreeMap::<String, BTreeMap<String, bool>>::new();
+            for answer in response {
+                files.entry(models::one(&answer?, "o", "path")?).or_default();
+            }
+            let mut ids = BTreeMap::<String, BTreeSet<String>>::new();
+            let mut memberships = BTreeSet::new();
+            if inference {
+                let query = Query::new(
+                    "match
+                        $fn == {name};
+                        $u isa user, has full-name $fn;
+                        (group: $g, member: $u) isa group-membership;
+                        $g has id $g-id;
+                        $p(subject: $g, access: $pa) isa permission;
+                        not {{ $p has validity false; }};
+                        $o isa object, has path $o-path;
+                        $pa($o, $va) isa access;
+                        $va isa action, has name 'view_file';
+                        get $o-path, $g, $g-id, $p;
+                        ",
+                )
+                .bind("name", full_name)
+                .build()?;
+                for answer in tx.query().get(&query)? {
+                    let answer = answer?;
+                    let (Some((group, _)), Some((_, inferred)), Some(groups)) = (
+                        thing(&answer, "g"),
+                        thing(&answer, "p"),
+                        files.get_mut(&models::one::<String>(&answer, "o", "path")?),
+                    ) else {
+                        continue;
+                    };
+                    *groups.entry(group.clone()).or_insert(true) &= inferred;
+                    ids.entry(group).or_default().extend(models::all::<String>(&answer, "g", "id")?);
+                }
+                let query = Query::new(
+                    "match
+                        $u isa user, has full-name {name};
+                        (group: $g, member: $u) isa group-membership;
+                        (group: $parent, member: $g) isa group-membership;
+                        get $g, $parent;",
+                )
+                .bind("name", full_name)
+                .build()?;
+                for answer in tx.query().get(&query)? {
+                    let answer = answer?;
+                    if let (Some((group, _)), Some((parent, _))) = (thing(&answer, "g"), thing(&answer, "parent")) {
+                        memberships.insert((group, parent));
+                    }
+                }
+            }
+            let files = files.into_iter().map(|(path, groups)| {
+                let groups = decision::granting_groups(groups, |group, other| {
+                    memberships.contains(&(group.clone(), other.clone()))
+                });
+                let groups = groups.iter().filter_map(|group| ids.get(group)?.iter().next().cloned());
+                ViewableFile { path, groups: groups.collect::<BTreeSet<_>>().into_iter().collect() }
+            });
+            Ok(files.collect())
+        } else {
+            Err(AppError::NotFound(format!("user with full-name {}", full_name)))
+        }
+    }
+    // end::get[]
+
+    // tag::decide[]
+    fn decide(&self, request: &AccessRequest, inference: bool) -> Result<Decision, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction_with_options(TransactionType::Read, Options::new().infer(inference))?;
+        let AccessRequest { subject, action, object } = request;
+        check_subject(&tx, subject)?;
+        let query = Query::new("match $o isa object, has id {object}; get $o;").bind("object", object);
+        only(&tx, query, "o", "object", &format!("with id {}", object))?;
+        let query = Query::new("match $a isa action, has name {action}; get $a;").bind("action", action);
+        only(&tx, query, "a", "action", &format!("with name {}", action))?;
+
+        let mut grants = find_grants(&tx, request, SUBJECT_GRANTS)?;
+        let mut memberships = BTreeSet::new();
+        if inference {
+            grants.extend(find_grants(&tx, request, GROUP_GRANTS)?);
+            let query = Query::new(
+                "match
+                    $s isa subject;
+                    {{ $s has id {subject}; }} or {{ $s has full-name {subject}; }};
+                    (group: $g, member: $s) isa group-membership;
+                    (group: $parent, member: $g) isa group-membership;
+                    get $g, $parent;",
+            )
+            .bind("subject", subject);
+            for answer in tx.query().get(&query.build()?)? {
+                let answer = answer?;
+                if let (Some((group, _)), Some((parent, _))) = (thing(&answer, "g"), thing(&answer, "parent")) {
+                    memberships.insert((group, parent));
+                }
+            }
+        }
+        // The subject, as the holder `None`, is a member of every group that the group queries find.
+        let grants = decision::without_inherited(grants, |holder: &Option<String>, group| match (holder, group) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(holder), Some(group)) => memberships.contains(&(holder.clone(), group.clone())),
+        });
+        Ok(Decision::new(request.clone(), grants))
+    }
+    // end::decide[]
+
+    fn groups(&self, subject: &str, inference: bool) -> Result<Vec<String>, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction_with_options(TransactionType::Read, Options::new().infer(inference))?;
+        check_subject(&tx, subject)?;
+        let query = Query::new(
+            "match
+                $s isa subject;
+                {{ $s has id {subject}; }} or {{ $s has full-name {subject}; }};
+                (group: $g, member: $s) isa group-membership;
+                $g has id $g-id;
+                get $g, $g-id;",
+        )
+        .bind("subject", subject)
+        .build()?;
+        let mut groups = BTreeMap::<_, BTreeSet<String>>::new();
+        for answer in tx.query().get(&query)? {
+            let answer = answer?;
+            if let Some((group, _)) = thing(&answer, "g") {
+                groups.entry(group).or_default().extend(models::all::<String>(&answer, "g", "id")?);
+            }
+        }
+        let groups = groups.into_values().filter_map(|ids| ids.into_iter().next()).collect::<BTreeSet<_>>();
+        Ok(groups.into_iter().collect())
+    }
+
+    fn explain_files_by_user(&self, full_name: &str) -> Result<Vec<ExplainedFile>, AppError> {
+        let session = self.data_session()?;
+        let options = Options::new().infer(true).explain(true);
+        let tx = session.transaction_with_options(TransactionType::Read, options)?;
+        let query = Query::new("match $u isa user, has full-name {name}; get;").bind("name", full_name);
+        only(&tx, query, "u", "user", &format!("with full-name {}", full_name))?;
+        let query = Query::new(
+            "match
+                $u isa user, has full-name {name};
+                $p (subject: $u, access: $ac) isa permission;
+                $o isa object, has path $o-path;
+                $ac (object: $o, action: $va) isa access;
+                $va isa action, has name 'view_file';
+                get;",
+        )
+        .bind("name", full_name)
+        .build()?;
+        let patterns = [
+            RelationPattern::new("p", "permission", &[("subject", "u"), ("access", "ac")]),
+            RelationPattern::new("ac", "access", &[("object", "o"), ("action", "va")]),
+        ];
+        let mut explainer = Explainer { tx: &tx, descriptions: HashMap::new() };
+        let mut files = BTreeMap::<String, Vec<Fact>>::new();
+        for answer in tx.query().get(&query)? {
+            let answer = answer?;
+            let fact = explainer.fact(&patterns, "p", &answer, true)?;
+            files.entry(models::one(&answer, "o", "path")?).or_default().push(fact);
+        }
+        Ok(files.into_iter().map(|(path, permissions)| ExplainedFile { path, permissions }).collect())
+    }
+
+    // tag::update[]
+    fn update_filepath(&self, old_path: &str, new_path: &str) -> Result<usize, AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction(TransactionType::Write)?;
+        let query = Query::new(
+            "match
+                $f isa file, has path $old_path;
+                $old_path = {old};
+                delete
+                $f has $old_path;
+                insert
+                $f has path $new_path;
+                $new_path = {new};",
+        )
+        .bind("old", old_path)
+        .bind("new", new_path)
+        .build()?;
+        let response = tx.query().update(&query)?.collect::<Result<Vec<_>, _>>()?;
+        if !response.is_empty() {
+            tx.commit().resolve()?;
+        }
+        Ok(response.len())
+    }
+    // end::update[]
+
+    // tag::delete[]
+    fn delete_file(&self, path: &str) -> Result<(), AppError> {
+        let session = self.data_session()?;
+        let tx = session.transaction(TransactionType::Write)?;
+        let query = Query::new(
+            "match
+                $f isa file, has path {path};
+                get;",
+        )
+        .bind("path", path)
+        .build()?;
+        let files = tx.query().get(&query)?.collect::<Result<Vec<_>, _>>()?;
+        if files.len() == 1 {
+            let query = Query::new(
+                "match
+                    $f isa file, has path {path};
+                    delete
+                    $f isa file;
+                    ",
+            )
+            .bind("path", path)
+            .build()?;
+            tx.query().delete(&query).resolve()?;
+            Ok(tx.commit().resolve()?)
+        } else if files.is_empty() {
+            Err(AppError::NotFound(format!("file with path {}", path)))
+        } else {
+            Err(AppError::Ambiguous(format!("{} files with path {}", files.len(), path)))
+        }
+    }
+    // end::delete[]
+
+    /// Opens a read transaction, which needs the server to be reachable and the database to exist.
+    fn ready(&self) -> Result<(), AppError> {
+        self.data_session()?.transaction(TransactionType::Read)?;
+        Ok(())
+    }
+}
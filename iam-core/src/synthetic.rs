@@ -0,0 +1,374 @@
+// tag::synthetic[]
+//! Synthetic IAM datasets of any size for performance and correctness testing. The same [`GenerateOptions`],
+//! seed included, always produce the same statements, on every platform.
+//!
+//! The dataset is consistent with `iam-schema.tql`: persons are members of a business unit and of some user roles,
+//! files are members of directories, every file can be accessed by a few operations, always including `view_file`
+//! (and `modify_file` where there are two), and persons are given permissions to some of those accesses.
+//! Segregation policies each name two operations.
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    sync::mpsc,
+    thread,
+};
+
+use chrono::{Duration, NaiveDate};
+use typedb_driver::Connection;
+
+use crate::{
+    error::AppError,
+    loader::{self, LoadOptions, Progress, Statement},
+};
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Bola", "Chen", "Dmitri", "Elif", "Farah", "Goran", "Hana", "Ines", "Jomo", "Kira", "Luis", "Mei", "Nadia",
+    "Omar", "Priya", "Quinn", "Rosa", "Sven", "Tariq", "Uma", "Viktor", "Wen", "Yara", "Zeno",
+];
+const LAST_NAMES: &[&str] = &[
+    "Abara", "Berg", "Costa", "Dubois", "Eze", "Fischer", "Garcia", "Haddad", "Ito", "Jensen", "Kowalski", "Larsen",
+    "Moreau", "Nakamura", "Okafor", "Petrov", "Rossi", "Silva", "Tanaka", "Varga", "Weber", "Yilmaz", "Zhang",
+];
+const EXTENSIONS: &[&str] = &["java", "ts", "rs", "md", "xlsx", "pdf", "txt"];
+const OPERATIONS: &[&str] = &["view_file", "modify_file", "delete_file", "share_file", "rename_file", "print_file"];
+
+/// How many things of each kind to generate. Counts that depend on others are capped by them, e.g. a file cannot be
+/// accessed by more operations than there are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenerateOptions {
+    pub seed: u64,
+    pub persons: usize,
+    pub business_units: usize,
+    /// User roles, the user groups that persons take part in besides their business unit.
+    pub user_roles: usize,
+    pub directories: usize,
+    pub files: usize,
+    pub operations: usize,
+    /// Operations that each file can be accessed by.
+    pub accesses_per_file: usize,
+    pub permissions_per_person: usize,
+    pub roles_per_person: usize,
+    pub segregation_policies: usize,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            persons: 100,
+            business_units: 5,
+            user_roles: 10,
+            directories: 20,
+            files: 1000,
+            operations: 4,
+            accesses_per_file: 2,
+            permissions_per_person: 10,
+            roles_per_person: 2,
+            segregation_policies: 3,
+        }
+    }
+}
+
+/// SplitMix64, small and with a fixed output for each seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `n`, which must not be 0.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// `k` distinct numbers below `n`, in the order they were drawn: the first `k` of a Fisher–Yates shuffle of
+    /// `0..n`, which only records the positions it swapped, so that it takes O(k) time and memory.
+    fn distinct(&mut self, k: usize, n: usize) -> Vec<usize> {
+        let mut swapped = HashMap::new();
+        (0..k.min(n))
+            .map(|i| {
+                let j = i + self.below(n - i);
+                let drawn = swapped.get(&j).copied().unwrap_or(j);
+                swapped.insert(j, swapped.get(&i).copied().unwrap_or(i));
+                drawn
+            })
+            .collect()
+    }
+}
+
+fn operation_name(index: usize) -> String {
+    match OPERATIONS.get(index) {
+        Some(name) => name.to_string(),
+        None => format!("operation_{}", index + 1),
+    }
+}
+
+/// Generates the statements of the dataset, each without `insert` or the final `;`, and passes them to `emit` in
+/// order, role players first. Returns the number of things generated of each type.
+pub fn generate<E>(
+    options: &GenerateOptions,
+    emit: &mut dyn FnMut(String) -> Result<(), E>,
+) -> Result<BTreeMap<&'static str, usize>, E> {
+    let mut rng = Rng(options.seed);
+    let mut counts = BTreeMap::new();
+    let mut emit = |type_: &'static str, statement: String| {
+        *counts.entry(type_).or_default() += 1;
+        emit(statement)
+    };
+
+    let operations = options.operations;
+    for index in 0..operations {
+        let name = operation_name(index);
+        emit(
+            "operation",
+            format!("$operation-{} isa operation, has name \"{}\", has object-type \"file\"", index, name),
+        )?;
+    }
+    let business_units = if options.persons > 0 { options.business_units.max(1) } else { options.business_units };
+    for index in 0..business_units {
+        emit("business-unit", format!("$business-unit-{} isa business-unit, has name \"unit-{}\"", index, index + 1))?;
+    }
+    for index in 0..options.user_roles {
+        emit("user-role", format!("$user-role-{} isa user-role, has name \"role-{}\"", index, index + 1))?;
+    }
+    for index in 0..options.persons {
+        let first = FIRST_NAMES[rng.below(FIRST_NAMES.len())];
+        let last = LAST_NAMES[rng.below(LAST_NAMES.len())];
+        let email = format!("{}.{}.{}@example.com", first.to_lowercase(), last.to_lowercase(), index + 1);
+        emit(
+            "person",
+            format!("$person-{} isa person, has full-name \"{} {}\", has email \"{}\"", index, first, last, email),
+        )?;
+    }
+    let directories = if options.files > 0 { options.directories.max(1) } else { options.directories };
+    for index in 0..directories {
+        let size = 4 * (1 + rng.below(64));
+        emit(
+            "directory",
+            format!("$directory-{} isa directory, has path \"dir-{}\", has size-kb {}", index, index + 1, size),
+        )?;
+    }
+    for index in 0..options.files {
+        let directory = rng.below(directories);
+        let extension = EXTENSIONS[rng.below(EXTENSIONS.len())];
+        let size = 1 + rng.below(4096);
+        emit(
+            "file",
+            format!(
+                "$file-{} isa file, has path \"dir-{}/file-{}.{}\", has size-kb {}",
+                index,
+                directory + 1,
+                index + 1,
+                extension,
+                size
+            ),
+        )?;
+        emit(
+            "collection-membership",
+            format!("(collection: $directory-{}, member: $file-{}) isa collection-membership", directory, index),
+        )?;
+    }
+
+    // Access `i * accesses_per_file + j` is the `j`th access of file `i`; its first two are by `view_file` and
+    // `modify_file`, any others by operations drawn at random.
+    let accesses_per_file = options.accesses_per_file.min(operations);
+    for file in 0..options.files {
+        let mut chosen = (0..accesses_per_file.min(2)).collect::<Vec<_>>();
+        while chosen.len() < accesses_per_file {
+            let operation = 2 + rng.below(operations - 2);
+            if !chosen.contains(&operation) {
+                chosen.push(operation);
+            }
+        }
+        for (j, operation) in chosen.into_iter().enumerate() {
+            emit(
+                "access",
+                format!(
+                    "$access-{} (object: $file-{}, action: $operation-{}) isa access",
+                    file * accesses_per_file + j,
+                    file,
+                    operation
+                ),
+            )?;
+        }
+    }
+
+    let review_start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let accesses = options.files * accesses_per_file;
+    for person in 0..options.persons {
+        emit(
+            "group-membership",
+            format!(
+                "(group: $business-unit-{}, member: $person-{}) isa group-membership",
+                person % business_units,
+                person
+            ),
+        )?;
+        for role in rng.distinct(options.roles_per_person, options.user_roles) {
+            emit(
+                "group-membership",
+                format!("(group: $user-role-{}, member: $person-{}) isa group-membership", role, person),
+            )?;
+        }
+        for access in rng.distinct(options.permissions_per_person, accesses) {
+            let review_date = (review_start + Duration::days(rng.below(365) as i64)).format("%FT%T%.3f");
+            let validity = rng.below(10) > 0;
+            emit(
+                "permission",
+                format!(
+                    "(subject: $person-{}, access: $access-{}) isa permission, has review-date {}, has validity {}",
+                    person, access, review_date, validity
+                ),
+            )?;
+        }
+    }
+
+    if operations >= 2 {
+        for index in 0..options.segregation_policies {
+            let pair = rng.distinct(2, operations);
+            emit(
+                "segregation-policy",
+                format!(
+                    "(action: $operation-{}, action: $operation-{}) isa segregation-policy, has name \"policy-{}\"",
+                    pair[0],
+                    pair[1],
+                    index + 1
+                ),
+            )?;
+        }
+    }
+    Ok(counts)
+}
+
+/// Writes the dataset as a single `insert` query.
+pub fn write_typeql(options: &GenerateOptions, writer: &mut impl Write) -> io::Result<BTreeMap<&'static str, usize>> {
+    writeln!(writer, "insert")?;
+    generate(options, &mut |statement| writeln!(writer, "{};", statement))
+}
+
+/// Generates the dataset into the existing database with the bulk loader, as it is generated.
+pub fn load(
+    driver: &Connection,
+    db_name: &str,
+    options: &GenerateOptions,
+    load_options: &LoadOptions,
+    progress: &(dyn Fn(&Progress) + Sync),
+) -> Result<Progress, AppError> {
+    let file = format!("synthetic dataset with seed {}", options.seed);
+    let (sender, receiver) = mpsc::sync_channel(load_options.batch_size.max(1));
+    thread::scope(|scope| {
+        // The generator stops when the loader fails and drops the receiver.
+        scope.spawn(move || {
+            let mut line = 0;
+            generate(options, &mut |text| {
+                line += 1;
+                sender.send(Statement::new(text, line))
+            })
+        });
+        loader::load_statements(driver, db_name, &file, receiver.into_iter().map(Ok), load_options, progress)
+    })
+}
+// end::synthetic[]
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        graph::Graph,
+        loader::Planner,
+        schema::Schema,
+        store::{IamStore, MemoryStore},
+        IAM_SCHEMA,
+    };
+
+    fn typeql(options: &GenerateOptions) -> (String, BTreeMap<&'static str, usize>) {
+        let mut out = Vec::new();
+        let counts = write_typeql(options, &mut out).unwrap();
+        (String::from_utf8(out).unwrap(), counts)
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_dataset() {
+        let options = GenerateOptions { persons: 20, files: 50, ..GenerateOptions::default() };
+        assert_eq!(typeql(&options), typeql(&options));
+        assert_ne!(typeql(&options).0, typeql(&GenerateOptions { seed: 1, ..options.clone() }).0);
+        let (text, _) = typeql(&options);
+        assert!(
+            text.starts_with("insert\n$operation-0 isa operation, has name \"view_file\", has object-type \"file\";\n")
+        );
+    }
+
+    #[test]
+    fn datasets_fit_the_schema() {
+        let options = GenerateOptions {
+            persons: 30,
+            files: 40,
+            operations: 8,
+            accesses_per_file: 3,
+            permissions_per_person: 200,
+            ..GenerateOptions::default()
+        };
+        let (text, counts) = typeql(&options);
+        assert_eq!(
+            counts,
+            BTreeMap::from([
+                ("access", 120),
+                ("business-unit", 5),
+                ("collection-membership", 40),
+                ("directory", 20),
+                ("file", 40),
+                ("group-membership", 90),
+                ("operation", 8),
+                ("permission", 30 * 120),
+                ("person", 30),
+                ("segregation-policy", 3),
+                ("user-role", 10),
+            ])
+        );
+        let mut graph = Graph::new(Schema::parse(IAM_SCHEMA).unwrap());
+        assert_eq!(graph.load(&text).unwrap(), counts.values().sum::<usize>());
+
+        let store = MemoryStore::new(graph).unwrap();
+        assert_eq!(store.users().unwrap().len(), 30);
+
+        let mut planner = Planner::new("synthetic", 100);
+        let statements = loader::Statements::new("synthetic", text.as_bytes());
+        for statement in statements {
+            planner.push(statement.unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn distinct_numbers_are_distinct() {
+        let mut rng = Rng(7);
+        let mut all = rng.distinct(1000, 1000);
+        all.sort_unstable();
+        assert_eq!(all, (0..1000).collect::<Vec<_>>());
+        let some = rng.distinct(100_000, 1_000_000);
+        assert_eq!(some.iter().collect::<HashSet<_>>().len(), 100_000);
+        assert!(some.iter().all(|&number| number < 1_000_000));
+        assert_eq!(rng.distinct(5, 3).len(), 3);
+    }
+
+    #[test]
+    fn empty_datasets_are_empty() {
+        let options = GenerateOptions {
+            persons: 0,
+            business_units: 0,
+            user_roles: 0,
+            directories: 0,
+            files: 0,
+            operations: 0,
+            segregation_policies: 3,
+            ..GenerateOptions::default()
+        };
+        assert_eq!(typeql(&options), ("insert\n".to_owned(), BTreeMap::new()));
+    }
+}
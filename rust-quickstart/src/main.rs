@@ -1,52 +1,28 @@
-use typedb_driver::{
-    concept::{Attribute, Concept, Value},
-    Connection, DatabaseManager, Error, Promise, Session, SessionType, TransactionType,
-};
+use iam_core::{config::ConfigLayer, connection, database, query::Query, AppError};
 
-fn main() -> Result<(), Error> {
+fn main() -> Result<(), AppError> {
     const DB_NAME: &str = "access-management-db";
     const SERVER_ADDR: &str = "127.0.0.1:1729";
-    let driver = Connection::new_core(SERVER_ADDR)?;
-    let databases = DatabaseManager::new(driver);
-    if databases.contains(DB_NAME)? {
-        databases.get(DB_NAME)?.delete()?;
+    let config = ConfigLayer {
+        db_name: Some(DB_NAME.to_owned()),
+        addresses: Some(vec![SERVER_ADDR.to_owned()]),
+        ..ConfigLayer::defaults()
     }
-    databases.create(DB_NAME)?;
-    {
-        let session = Session::new(databases.get(DB_NAME)?, SessionType::Schema)?;
-        let tx = session.transaction(TransactionType::Write)?;
-        tx.query().define("define person sub entity;").resolve()?;
-        tx.query().define("define name sub attribute, value string; person owns name;").resolve()?;
-        tx.commit().resolve()?;
+    .validate()?;
+    let driver = connection::connect(&config)?;
+    if database::exists(&driver, DB_NAME)? {
+        database::delete(&driver, DB_NAME)?;
     }
-    {
-        let session = Session::new(databases.get(DB_NAME)?, SessionType::Data)?;
-        {
-            let tx = session.transaction(TransactionType::Write)?;
-            let a = tx.query().insert("insert $p isa person, has name 'Alice';")?;
-            let _ = tx.query().insert("insert $p isa person, has name 'Bob';")?;
-            let mut counter = 0;
-            for item in a {
-                let concept = item.unwrap().map.get("_1").unwrap().clone();
-                let string = match concept {
-                    Concept::Attribute(Attribute {
-                        value: Value::String(value),
-                        ..
-                    }) => value,
-                    _ => unreachable!(),
-                };
-                counter += 1;
-                println!("Result #{}: {}", counter, string);
-            }
-            tx.commit().resolve()?;
-        }
-        {
-            let tx = session.transaction(TransactionType::Read)?;
-            let res = tx.query().fetch("match $p isa person; fetch $p: name;").unwrap();
-            for item in res {
-                println!("{}", &item.unwrap().to_string());
-            }
-            Ok(())
-        }
+    database::create(&driver, DB_NAME)?;
+    database::define_schema(&driver, DB_NAME, "schema", "define person sub entity;")?;
+    database::define_schema(&driver, DB_NAME, "schema", "define name sub attribute, value string; person owns name;")?;
+    for (count, name) in ["Alice", "Bob"].into_iter().enumerate() {
+        let query = Query::new("insert $p isa person, has name {name};").bind("name", name).build()?;
+        database::load_data(&driver, DB_NAME, "data", &query)?;
+        println!("Result #{}: {}", count + 1, name);
     }
-}
\ No newline at end of file
+    for answer in database::fetch(&driver, DB_NAME, "match $p isa person; fetch $p: name;")? {
+        println!("{}", answer);
+    }
+    Ok(())
+}
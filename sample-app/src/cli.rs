@@ -0,0 +1,414 @@
+// tag::cli[]
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use iam_core::{config::ConfigLayer, synthetic::GenerateOptions};
+
+pub static DEFAULT_CONFIG_FILE: &str = "sample-app.toml";
+/// Prefix of the environment variables that configure the sample app, e.g. `SAMPLE_APP_DB_NAME`.
+pub static ENV_PREFIX: &str = "SAMPLE_APP_";
+
+/// Sample App: IAM operations against a TypeDB database.
+#[derive(Debug, Parser)]
+#[command(name = "sample-app", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Connection settings. Flags take precedence over `SAMPLE_APP_*` environment variables, which take precedence
+/// over the configuration file.
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Configuration file [default: sample-app.toml, if present]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Storage backend: typedb, or memory to run against the bundled schema and dataset without a server
+    #[arg(long, global = true)]
+    pub backend: Option<String>,
+    /// What to do when the database exists: reuse, replace, fail-if-exists or ensure-schema
+    #[arg(long, global = true, value_name = "POLICY")]
+    pub bootstrap: Option<String>,
+    /// Database name
+    #[arg(long, global = true)]
+    pub db_name: Option<String>,
+    /// TypeDB edition: core or cloud
+    #[arg(long, global = true)]
+    pub edition: Option<String>,
+    /// Server address as host:port; repeat for several TypeDB Cloud servers
+    #[arg(long = "address", global = true, value_name = "ADDRESS")]
+    pub addresses: Vec<String>,
+    /// TypeDB Cloud username
+    #[arg(long, global = true)]
+    pub username: Option<String>,
+    /// Root CA certificate for TLS connections to TypeDB Cloud
+    #[arg(long, global = true, value_name = "PATH")]
+    pub tls_root_ca: Option<PathBuf>,
+}
+
+impl ConfigArgs {
+    pub fn config_file(&self) -> (PathBuf, bool) {
+        match &self.config {
+            Some(path) => (path.clone(), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        }
+    }
+
+    pub fn layer(&self) -> ConfigLayer {
+        ConfigLayer {
+            backend: self.backend.clone(),
+            bootstrap: self.bootstrap.clone(),
+            db_name: self.db_name.clone(),
+            edition: self.edition.clone(),
+            addresses: Some(self.addresses.clone()).filter(|addresses| !addresses.is_empty()),
+            username: self.username.clone(),
+            password: None,
+            tls_root_ca: self.tls_root_ca.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Manage users
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Query and modify files
+    #[command(subcommand)]
+    Files(FilesCommand),
+    /// Decide whether subjects may perform actions on objects
+    #[command(subcommand)]
+    Access(AccessCommand),
+    /// Manage the database
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Evolve the database schema and data with versioned migrations
+    Migrate(MigrateArgs),
+    /// Set up the database and run the scripted sequence of six requests (the default)
+    Demo,
+    /// Serve the user and file operations as an HTTP API
+    Serve(ServeArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080", value_name = "ADDRESS")]
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UsersCommand {
+    /// Fetch all users with their full names and emails
+    List,
+    /// Add a new user
+    Add(AddUserArgs),
+    /// List the groups that a user or group is a member of
+    Groups(GroupsArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct GroupsArgs {
+    /// Identifier or full name of the member, e.g. an email or a business unit name
+    pub subject: String,
+    /// Enable rule inference, which includes the groups of those groups
+    #[arg(long)]
+    pub infer: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct AddUserArgs {
+    /// Full name of the new user
+    #[arg(long)]
+    pub name: String,
+    /// Email of the new user
+    #[arg(long)]
+    pub email: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum FilesCommand {
+    /// List files that a user has access to view
+    ForUser(ForUserArgs),
+    /// Change the path of a file
+    Move(MoveArgs),
+    /// Delete a file
+    Delete(DeleteArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ForUserArgs {
+    /// Full name of the user
+    pub name: String,
+    /// Enable rule inference
+    #[arg(long)]
+    pub infer: bool,
+    /// Show the permissions that grant each file and the rules they were inferred by (implies --infer)
+    #[arg(long)]
+    pub explain: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MoveArgs {
+    /// Current path of the file
+    pub old_path: String,
+    /// New path of the file
+    pub new_path: String,
+}
+
+#[derive(Debug, Args)]
+pub struct DeleteArgs {
+    /// Path of the file to delete
+    pub path: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AccessCommand {
+    /// Decide whether a subject may perform an action on an object, with the permissions that grant it
+    Check(CheckArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Identifier of the subject, e.g. an email or a business unit name, or the full name of a person
+    #[arg(long)]
+    pub subject: String,
+    /// Name of the action
+    #[arg(long)]
+    pub action: String,
+    /// Identifier of the object, e.g. a path, a record number or a database name
+    #[arg(long)]
+    pub object: String,
+    /// Enable rule inference
+    #[arg(long)]
+    pub infer: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Create the database with the IAM schema and dataset, or handle an existing one as the bootstrap policy says
+    Setup(SetupArgs),
+    /// Load an insert-only data file into the existing database in batches
+    Load(LoadArgs),
+    /// Import the rows of a CSV or JSON file into the existing database as a mapping file says
+    Import(ImportArgs),
+    /// Back up the schema and all data of the database to a .jsonl or .tql file
+    Export(ExportArgs),
+    /// Create the database from a backup made by export; the database must not exist
+    Restore(RestoreArgs),
+    /// Generate a synthetic IAM dataset into the existing database, or into a TypeQL file with --output
+    Generate(GenerateArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct SetupArgs {
+    /// Replace an existing database, the same as --bootstrap replace
+    #[arg(long)]
+    pub reset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct LoadArgs {
+    /// Data file holding insert statements, such as iam-data-single-query.tql
+    pub file: PathBuf,
+    /// Statements committed per transaction
+    #[arg(long, default_value_t = 500)]
+    pub batch_size: usize,
+    /// Data sessions loading batches in parallel
+    #[arg(long, default_value_t = 4)]
+    pub sessions: usize,
+    /// File recording the committed batches; loading again with it resumes after the last committed batch
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Mapping file saying which things each row inserts, such as iam-core/import/hr.toml
+    pub mapping: PathBuf,
+    /// CSV file with a header line, or JSON file holding an array of objects
+    pub data: PathBuf,
+    /// Statements committed per transaction
+    #[arg(long, default_value_t = 500)]
+    pub batch_size: usize,
+    /// Data sessions loading batches in parallel
+    #[arg(long, default_value_t = 4)]
+    pub sessions: usize,
+    /// File recording the committed batches; importing again with it resumes after the last committed batch
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint: Option<PathBuf>,
+    /// Print the generated statements and the rejected rows without a server, assuming that the players not
+    /// inserted by earlier rows exist
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Backup file: .jsonl for JSON Lines, or .tql for a TypeQL define and insert script
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// Backup file made by export
+    pub file: PathBuf,
+    /// Statements committed per transaction
+    #[arg(long, default_value_t = 500)]
+    pub batch_size: usize,
+    /// Data sessions loading batches in parallel
+    #[arg(long, default_value_t = 4)]
+    pub sessions: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    /// Seed of the generator; the same seed and counts always give the same dataset
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    #[arg(long, default_value_t = 100)]
+    pub persons: usize,
+    #[arg(long, default_value_t = 5)]
+    pub business_units: usize,
+    #[arg(long, default_value_t = 10)]
+    pub user_roles: usize,
+    #[arg(long, default_value_t = 20)]
+    pub directories: usize,
+    #[arg(long, default_value_t = 1000)]
+    pub files: usize,
+    #[arg(long, default_value_t = 4)]
+    pub operations: usize,
+    /// Operations that each file can be accessed by
+    #[arg(long, default_value_t = 2)]
+    pub accesses_per_file: usize,
+    #[arg(long, default_value_t = 10)]
+    pub permissions_per_person: usize,
+    /// User roles that each person is a member of, besides their business unit
+    #[arg(long, default_value_t = 2)]
+    pub roles_per_person: usize,
+    #[arg(long, default_value_t = 3)]
+    pub segregation_policies: usize,
+    /// Write the dataset as an insert query to this file instead, without a server
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Statements committed per transaction
+    #[arg(long, default_value_t = 500)]
+    pub batch_size: usize,
+    /// Data sessions loading batches in parallel
+    #[arg(long, default_value_t = 4)]
+    pub sessions: usize,
+}
+
+impl GenerateArgs {
+    pub fn options(&self) -> GenerateOptions {
+        GenerateOptions {
+            seed: self.seed,
+            persons: self.persons,
+            business_units: self.business_units,
+            user_roles: self.user_roles,
+            directories: self.directories,
+            files: self.files,
+            operations: self.operations,
+            accesses_per_file: self.accesses_per_file,
+            permissions_per_person: self.permissions_per_person,
+            roles_per_person: self.roles_per_person,
+            segregation_policies: self.segregation_policies,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateArgs {
+    /// Directory of <version>_<name>.tql migrations to apply after the bundled IAM migrations, which are built
+    /// into the binary
+    #[arg(long, value_name = "DIR")]
+    pub dir: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: MigrateCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply pending migrations, creating the database if it does not exist
+    Up(MigrateUpArgs),
+    /// List the known migrations and whether each has been applied
+    Status,
+    /// List the migrations that `up` would apply, without applying them
+    Plan(MigrateTarget),
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateUpArgs {
+    #[command(flatten)]
+    pub target: MigrateTarget,
+    /// Record the migrations up to this version as applied without running them, for a database created with
+    /// `db setup`
+    #[arg(long, value_name = "VERSION")]
+    pub baseline: Option<i64>,
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateTarget {
+    /// Stop at this version instead of the latest
+    #[arg(long, value_name = "VERSION")]
+    pub to: Option<i64>,
+}
+// end::cli[]
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_subcommands() {
+        let cli = Cli::parse_from(["sample-app", "files", "for-user", "Kevin Morrison", "--infer"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Files(FilesCommand::ForUser(ForUserArgs { ref name, infer: true, explain: false }))) if name == "Kevin Morrison"
+        ));
+        let cli = Cli::parse_from(["sample-app", "db", "setup", "--reset"]);
+        assert!(matches!(cli.command, Some(Command::Db(DbCommand::Setup(SetupArgs { reset: true })))));
+        let cli = Cli::parse_from(["sample-app", "migrate", "--dir", "m", "up", "--to", "3", "--baseline", "2"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Migrate(MigrateArgs {
+                dir: Some(_),
+                command: MigrateCommand::Up(MigrateUpArgs { target: MigrateTarget { to: Some(3) }, baseline: Some(2) })
+            }))
+        ));
+        assert!(Cli::parse_from(["sample-app"]).command.is_none());
+        assert!(Cli::try_parse_from(["sample-app", "users", "add", "--name", "Jack Keeper"]).is_err());
+    }
+
+    #[test]
+    fn parses_global_config_flags() {
+        let cli = Cli::parse_from([
+            "sample-app",
+            "users",
+            "list",
+            "--edition",
+            "cloud",
+            "--address",
+            "a:1",
+            "--address",
+            "b:2",
+            "--bootstrap",
+            "ensure-schema",
+        ]);
+        let layer = cli.config.layer();
+        assert_eq!(layer.edition.as_deref(), Some("cloud"));
+        assert_eq!(layer.addresses, Some(vec!["a:1".to_owned(), "b:2".to_owned()]));
+        assert_eq!(layer.bootstrap.as_deref(), Some("ensure-schema"));
+        assert_eq!(layer.db_name, None);
+        assert_eq!(cli.config.config_file(), (PathBuf::from(DEFAULT_CONFIG_FILE), false));
+    }
+}
@@ -1,397 +1,453 @@
 // tag::code[]
 // tag::import[]
-use std::{error::Error, fs, io};
+mod cli;
+mod server;
 
-use typedb_driver::{
-    answer::{ConceptMap, JSON},
-    concept::{Attribute, Concept, Value},
-    Connection, Credential, DatabaseManager, Error as TypeDBError, Options, Promise, Session, SessionType,
-    TransactionType,
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
 };
-// end::import[]
-// tag::constants[]
-static DB_NAME: &str = "sample_app_db";
-static SERVER_ADDR: &str = "127.0.0.1:1729";
 
-enum Edition {
-    Core,
-    Cloud,
-}
+use clap::Parser;
+use iam_core::{
+    backup::{self, BackupFormat},
+    config::{Backend, Bootstrap, Config, ConfigError, ConfigLayer},
+    connection,
+    database::{self, Bootstrapped, IAM_SEED},
+    decision::{AccessRequest, Decision},
+    explanation::ExplainedFile,
+    import::{self, Generated, Mapping},
+    loader::{self, LoadOptions},
+    migration::{self, Migrations},
+    models::Person,
+    store::{IamStore, MemoryStore, TypeDbStore, ViewableFile},
+    synthetic, AppError, IAM_DATA_FILE, IAM_SCHEMA_FILE,
+};
+use typedb_driver::Connection;
 
-static TYPEDB_EDITION: Edition = Edition::Core;
-static CLOUD_USERNAME: &str = "admin";
-static CLOUD_PASSWORD: &str = "password";
-// end::constants[]
+use crate::{
+    cli::{
+        AccessCommand, Cli, Command, DbCommand, ExportArgs, FilesCommand, GenerateArgs, ImportArgs, LoadArgs,
+        MigrateArgs, MigrateCommand, RestoreArgs, UsersCommand, ENV_PREFIX,
+    },
+    server::SharedStore,
+};
+
+// end::import[]
 // tag::fetch[]
-fn fetch_all_users(driver: Connection, db_name: String) -> Result<Vec<JSON>, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver);
-    let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
-    let tx = session.transaction(TransactionType::Read)?;
-    let iterator = tx.query().fetch("match $u isa user; fetch $u: full-name, email;")?;
-    let mut count = 0;
-    let mut result = vec![];
-    for item in iterator {
-        count += 1;
-        let json = item?;
-        println!("User #{}: {}", count.to_string(), json.to_string());
-        result.push(json);
+fn fetch_all_users(store: &dyn IamStore) -> Result<Vec<Person>, AppError> {
+    let users = store.users()?;
+    for (count, user) in users.iter().enumerate() {
+        println!("User #{}: {}", count + 1, user);
     }
-    if result.len() > 0 {
-        Ok(result)
+    if !users.is_empty() {
+        Ok(users)
     } else {
-        Err(Box::new(TypeDBError::Other("Error: No users found in a database.".to_string())))
+        Err(AppError::NotFound("users in the database".to_owned()))
     }
 }
 // end::fetch[]
 // tag::insert[]
-fn insert_new_user(
-    driver: Connection,
-    db_name: String,
-    new_name: &str,
-    new_email: &str,
-) -> Result<Vec<ConceptMap>, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver);
-    let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
-    let tx = session.transaction(TransactionType::Write)?;
-    let iterator = tx.query().insert(&format!(
-        "insert $p isa person, has full-name $fn, has email $e; $fn == '{}'; $e == '{}';",
-        new_name, new_email
-    ))?;
-    let mut result = vec![];
-    for item in iterator {
-        let concept_map = item?;
-        let name = unwrap_string(concept_map.get("fn").unwrap().clone());
-        let email = unwrap_string(concept_map.get("e").unwrap().clone());
+fn insert_new_user(store: &dyn IamStore, new_name: &str, new_email: &str) -> Result<Vec<Person>, AppError> {
+    let users = store.insert_user(new_name, new_email)?;
+    for user in &users {
+        let (name, email) = (user.full_name.as_deref().unwrap_or_default(), user.email.as_deref().unwrap_or_default());
         println!("Added new user. Name: {}, E-mail: {}", name, email);
-        result.push(concept_map);
     }
-    if result.len() > 0 {
-        let _ = tx.commit().resolve();
-        Ok(result)
+    if !users.is_empty() {
+        Ok(users)
     } else {
-        Err(Box::new(TypeDBError::Other("Error: No users found in a database.".to_string())))
+        Err(AppError::NotFound("users in the database".to_owned()))
     }
 }
 // end::insert[]
+// tag::groups[]
+fn get_groups(store: &dyn IamStore, subject: &str, inference: bool) -> Result<Vec<String>, AppError> {
+    let groups = store.groups(subject, inference)?;
+    for (count, group) in groups.iter().enumerate() {
+        println!("Group #{}: {}", count + 1, group);
+    }
+    if groups.is_empty() {
+        println!("No groups found.");
+    }
+    Ok(groups)
+}
+// end::groups[]
 // tag::get[]
-fn get_files_by_user(
-    driver: Connection,
-    db_name: String,
-    name: &str,
-    inference: bool,
-) -> Result<Vec<(usize, ConceptMap)>, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver);
-    let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
-    let tx = session.transaction_with_options(TransactionType::Read, Options::new().infer(inference))?;
-    let users = tx
-        .query()
-        .get(&format!("match $u isa user, has full-name '{}'; get;", name))?
-        .map(|x| x.unwrap())
-        .collect::<Vec<_>>();
-    let response;
-    if users.len() > 1 {
-        return Err(Box::new(TypeDBError::Other("Found more than one user with that name.".to_string())));
-    } else if users.len() == 1 {
-        response = tx
-            .query()
-            .get(&format!(
-                "match
-                    $fn == '{}';
-                    $u isa user, has full-name $fn;
-                    $p($u, $pa) isa permission;
-                    $o isa object, has path $fp;
-                    $pa($o, $va) isa access;
-                    $va isa action, has name 'view_file';
-                    get $fp; sort $fp asc;
-                    ",
-                name
-            ))?
-            .map(|x| x.unwrap())
-            .enumerate()
-            .collect::<Vec<_>>();
-        for (count, file) in &response {
-            println!("File #{}: {}", count + 1, unwrap_string(file.get("fp").unwrap().clone()));
+fn get_files_by_user(store: &dyn IamStore, name: &str, inference: bool) -> Result<Vec<ViewableFile>, AppError> {
+    let files = store.files_by_user(name, inference)?;
+    for (count, file) in files.iter().enumerate() {
+        match file.groups.as_slice() {
+            [] => println!("File #{}: {}", count + 1, file.path),
+            groups => println!("File #{}: {} (inherited from {})", count + 1, file.path, groups.join(", ")),
         }
-        if response.len() == 0 {
-            println!("No files found. Try enabling inference.");
-        }
-        return Ok(response);
-    } else {
-        return Err(Box::new(TypeDBError::Other("No users found with that name.".to_string())));
     }
+    if files.is_empty() {
+        println!("No files found. Try enabling inference.");
+    }
+    Ok(files)
 }
 // end::get[]
+// tag::explain[]
+fn explain_files_by_user(store: &dyn IamStore, name: &str) -> Result<Vec<ExplainedFile>, AppError> {
+    let files = store.explain_files_by_user(name)?;
+    for (count, file) in files.iter().enumerate() {
+        println!("File #{}: {}", count + 1, file.path);
+        for permission in &file.permissions {
+            println!("  {}", permission.tree().replace('\n', "\n  "));
+        }
+    }
+    if files.is_empty() {
+        println!("No files found.");
+    }
+    Ok(files)
+}
+// end::explain[]
 // tag::update[]
-fn update_filepath(
-    driver: Connection,
-    db_name: String,
-    old_path: &str,
-    new_path: &str,
-) -> Result<Vec<ConceptMap>, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver);
-    let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
-    let tx = session.transaction(TransactionType::Write)?;
-    let response = tx
-        .query()
-        .update(&format!(
-            "match
-                $f isa file, has path $old_path;
-                $old_path = '{old}';
-                delete
-                $f has $old_path;
-                insert
-                $f has path $new_path;
-                $new_path = '{new}';",
-            old = old_path,
-            new = new_path
-        ))?
-        .map(|x| x.unwrap())
-        .collect::<Vec<_>>();
-    if response.len() > 0 {
-        let _ = tx.commit().resolve();
-        println!("Total number of paths updated: {}", response.len());
-        return Ok(response);
-    } else if response.len() == 0 {
-        println!("No matched paths: nothing to update");
-        return Ok(response);
+fn update_filepath(store: &dyn IamStore, old_path: &str, new_path: &str) -> Result<usize, AppError> {
+    let updated = store.update_filepath(old_path, new_path)?;
+    if updated > 0 {
+        println!("Total number of paths updated: {}", updated);
     } else {
-        return Err(Box::new(TypeDBError::Other("Impossible query response.".to_string())));
+        println!("No matched paths: nothing to update");
     }
+    Ok(updated)
 }
 // end::update[]
 // tag::delete[]
-fn delete_file(driver: Connection, db_name: String, path: &str) -> Result<(), Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver);
-    let session = Session::new(databases.get(db_name)?, SessionType::Data)?;
-    let tx = session.transaction(TransactionType::Write)?;
-    let files = tx
-        .query()
-        .get(&format!(
-            "match
-                $f isa file, has path '{}';
-                get;",
-            path
-        ))?
-        .map(|x| x.unwrap())
-        .collect::<Vec<_>>();
-    if files.len() == 1 {
-        let response = tx
-            .query()
-            .delete(&format!(
-                "match
-                    $f isa file, has path '{path}';
-                    delete
-                    $f isa file;
-                    "
-            ))
-            .resolve();
-        match response {
-            Ok(_) => {
-                println!("File has been deleted.");
-                Ok(())
-            }
-            Err(_) => return Err(Box::new(TypeDBError::Other("Error: Failed to delete.".to_string()))),
-        }
-    } else {
-        return Err(Box::new(TypeDBError::Other(
-            format!("Wrong number of files to delete: {}", files.len()).to_string(),
-        )));
-    }
+fn delete_file(store: &dyn IamStore, path: &str) -> Result<(), AppError> {
+    store.delete_file(path)?;
+    println!("File has been deleted.");
+    Ok(())
 }
 // end::delete[]
+// tag::decide[]
+fn decide(store: &dyn IamStore, request: &AccessRequest, inference: bool) -> Result<Decision, AppError> {
+    let decision = store.decide(request, inference)?;
+    println!("Decision: {}", decision);
+    for (count, grant) in decision.grants.iter().enumerate() {
+        println!("Grant #{}: {}", count + 1, grant);
+    }
+    Ok(decision)
+}
+// end::decide[]
 // tag::queries[]
-fn queries(driver: Connection, db_name: String) -> Result<(), Box<dyn Error>> {
+fn queries(store: &dyn IamStore) -> Result<(), AppError> {
     println!("Request 1 of 6: Fetch all users as JSON objects with full names and emails");
-    let users = fetch_all_users(driver.clone(), db_name.clone());
+    let users = fetch_all_users(store);
     assert!(users?.len() == 3);
 
     let new_name = "Jack Keeper";
     let new_email = "jk@typedb.com";
     println!("Request 2 of 6: Add a new user with the full-name {} and email {}", new_name, new_email);
-    let new_user = insert_new_user(driver.clone(), db_name.clone(), new_name, new_email);
+    let new_user = insert_new_user(store, new_name, new_email);
     assert!(new_user?.len() == 1);
 
     let infer = false;
     let name = "Kevin Morrison";
     println!("Request 3 of 6: Find all files that the user {} has access to view (no inference)", name);
-    let no_files = get_files_by_user(driver.clone(), db_name.clone(), name, infer);
-    assert!(no_files?.len() == 0);
+    let no_files = get_files_by_user(store, name, infer);
+    assert!(no_files?.is_empty());
 
     let infer = true;
     println!("Request 4 of 6: Find all files that the user {} has access to view (with inference)", name);
-    let files = get_files_by_user(driver.clone(), db_name.clone(), name, infer);
+    let files = get_files_by_user(store, name, infer);
     assert!(files?.len() == 10);
 
     let old_path = "lzfkn.java";
     let new_path = "lzfkn2.java";
     println!("Request 5 of 6: Update the path of a file from {} to {}", old_path, new_path);
-    let updated_files = update_filepath(driver.clone(), db_name.clone(), old_path, new_path);
-    assert!(updated_files?.len() == 1);
+    let updated_files = update_filepath(store, old_path, new_path);
+    assert!(updated_files? == 1);
 
     let path = "lzfkn2.java";
     println!("Request 6 of 6: Delete the file with path {}", path);
-    let deleted = delete_file(driver.clone(), db_name.clone(), path);
-
-    match deleted {
-        Ok(_) => return Ok(()),
-        Err(_) => return Err(Box::new(TypeDBError::Other("Application terminated unexpectedly".to_string()))),
-    };
+    delete_file(store, path)
 }
 // end::queries[]
-// tag::connection[]
-fn connect_to_TypeDB(edition: &Edition, addr: &str) -> Result<Connection, typedb_driver::Error> {
-    match edition {
-        Edition::Core => return Connection::new_core(addr),
-        Edition::Cloud => {
-            return Connection::new_cloud(&[addr], Credential::with_tls(CLOUD_USERNAME, CLOUD_PASSWORD, None)?)
-        }
-    };
+// tag::db-setup[]
+fn db_setup(driver: &Connection, db_name: &str, policy: Bootstrap) -> Result<(), AppError> {
+    println!("Setting up the database: {}", db_name);
+    match database::bootstrap(driver, db_name, policy, &IAM_SEED)? {
+        Bootstrapped::Created => println!("Created a new database with {} and {}.", IAM_SCHEMA_FILE, IAM_DATA_FILE),
+        Bootstrapped::Replaced => println!("Replaced the existing database."),
+        Bootstrapped::SchemaDefined => println!("Defined {} in the existing database.", IAM_SCHEMA_FILE),
+        Bootstrapped::Reused => println!("Reusing the existing database."),
+    }
+    print!("Testing the database...");
+    database::check_iam(driver, db_name)?;
+    println!("OK");
+    Ok(())
 }
-// end::connection[]
-// tag::create_new_db[]
-fn create_database(driver: &Connection, db_name: String) -> Result<bool, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver.to_owned());
-    print!("Creating a new database...");
-    let result = databases.create(&db_name);
-    match result {
-        Ok(_) => {
-            println!("OK");
-        }
-        Err(_) => return Err(Box::new(TypeDBError::Other("Failed to create a DB.".to_string()))),
-    };
-    {
-        let schema_session = Session::new(databases.get(&db_name)?, SessionType::Schema)?;
-        db_schema_setup(&schema_session, "iam-schema.tql".to_string())?;
+// end::db-setup[]
+// tag::db-load[]
+fn db_load(driver: &Connection, db_name: &str, args: LoadArgs) -> Result<(), AppError> {
+    let file = args.file.display().to_string();
+    let reader = BufReader::new(File::open(&args.file)?);
+    let options = LoadOptions { batch_size: args.batch_size, sessions: args.sessions, checkpoint: args.checkpoint };
+    println!("Loading {} into {} in batches of {} statements", file, db_name, options.batch_size);
+    let progress = loader::load(driver, db_name, &file, reader, &options, &|progress| {
+        println!("Committed {} batches, {} statements", progress.batches, progress.statements);
+    })?;
+    if progress.skipped > 0 {
+        println!("Skipped {} batches committed before, according to the checkpoint.", progress.skipped);
     }
-    {
-        let data_session = Session::new(databases.get(&db_name)?, SessionType::Data)?;
-        db_dataset_setup(&data_session, "iam-data-single-query.tql".to_string())?;
+    println!("Loaded {} statements in {} batches.", progress.statements, progress.batches);
+    Ok(())
+}
+// end::db-load[]
+// tag::db-import[]
+fn print_rejected(generated: &Generated) {
+    for rejected in &generated.rejected {
+        println!("Rejected {}", rejected);
     }
-    return Ok(true);
 }
-// end::create_new_db[]
-// tag::replace_db[]
-fn replace_database(driver: &Connection, db_name: String) -> Result<bool, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver.to_owned());
-    print!("Deleting an existing database...");
-    let deletion_result = databases.get(&db_name)?.delete();
-    match deletion_result {
-        Ok(_) => println!("OK"),
-        Err(_) => return Err(Box::new(TypeDBError::Other("Failed to delete a database.".to_string()))),
-    };
-    let creation_result = create_database(&driver, db_name);
-    match creation_result {
-        Ok(_) => return Ok(true),
-        Err(_) => return Err(Box::new(TypeDBError::Other("Failed to create a new database.".to_string()))),
-    };
+
+/// Prints the statements an import would load, without a server.
+fn db_import_dry_run(args: &ImportArgs) -> Result<(), AppError> {
+    let mapping = Mapping::from_file(&args.mapping)?;
+    let generated = import::generate(&mapping, &args.data, &mut |_| Ok(true))?;
+    for statement in &generated.statements {
+        let keyword = if statement.existing { "match" } else { "insert" };
+        println!("{} {};", keyword, statement.text);
+    }
+    print_rejected(&generated);
+    println!("{} rows would be imported, {} rejected.", generated.imported, generated.rejected.len());
+    Ok(())
 }
-// end::replace_db[]
 
-// tag::db-schema-setup[]
-fn db_schema_setup(schema_session: &Session, schema_file: String) -> Result<(), TypeDBError> {
-    let tx = schema_session.transaction(TransactionType::Write)?;
-    let data = fs::read_to_string(schema_file)?; // "iam-schema.tql"
-    print!("Defining schema...");
-    let response = tx.query().define(&data).resolve();
-    tx.commit().resolve()?;
-    println!("OK");
-    return response;
+fn db_import(driver: &Connection, db_name: &str, args: ImportArgs) -> Result<(), AppError> {
+    let mapping = Mapping::from_file(&args.mapping)?;
+    let options = LoadOptions { batch_size: args.batch_size, sessions: args.sessions, checkpoint: args.checkpoint };
+    println!("Importing {} into {} as {} says", args.data.display(), db_name, args.mapping.display());
+    let (generated, progress) = import::import(driver, db_name, &mapping, &args.data, &options, &|progress| {
+        println!("Committed {} batches, {} statements", progress.batches, progress.statements);
+    })?;
+    print_rejected(&generated);
+    println!(
+        "Imported {} rows in {} batches, rejected {}.",
+        generated.imported,
+        progress.batches,
+        generated.rejected.len()
+    );
+    Ok(())
 }
-// end::db-schema-setup[]
-// tag::db-dataset-setup[]
-fn db_dataset_setup(data_session: &Session, data_file: String) -> Result<(), Box<dyn Error>> {
-    let tx = data_session.transaction(TransactionType::Write)?;
-    let data = fs::read_to_string(data_file)?; // "iam-data-single-query.tql"
-    print!("Loading data...");
-    let response = tx.query().insert(&data)?;
-    let result = response.collect::<Vec<_>>();
-    tx.commit().resolve()?;
-    println!("OK");
-    Ok({
-        drop(result);
-    })
+// end::db-import[]
+// tag::db-backup[]
+fn db_export(driver: &Connection, db_name: &str, args: ExportArgs) -> Result<(), AppError> {
+    let format = BackupFormat::from_extension(&args.file)?;
+    let backup = backup::export(driver, db_name)?;
+    let mut writer = BufWriter::new(File::create(&args.file)?);
+    backup.write(format, &mut writer)?;
+    writer.flush()?;
+    print_counts(backup.counts());
+    println!("Exported {} things from {} to {}.", backup.records.len(), db_name, args.file.display());
+    Ok(())
 }
-// end::db-dataset-setup[]
-// tag::test-db[]
-fn db_check(data_session: &Session) -> Result<bool, Box<dyn Error>> {
-    let tx = data_session.transaction(TransactionType::Write)?;
-    let test_query = "match $u isa user; get $u; count;";
-    print!("Testing the database...");
-    let response = tx.query().get_aggregate(test_query).resolve();
-    let result = match response?.ok_or("Error: unexpected test query response.")? {
-        Value::Long(value) => value,
-        _ => unreachable!(),
-    };
-    if result == 3 {
-        println!("OK");
-        Ok(true)
-    } else {
-        Err(Box::new(TypeDBError::Other("Test failed. Terminating...".to_string())))
+
+fn db_restore(driver: &Connection, db_name: &str, args: RestoreArgs) -> Result<(), AppError> {
+    let options = LoadOptions { batch_size: args.batch_size, sessions: args.sessions, checkpoint: None };
+    println!("Restoring {} from {}", db_name, args.file.display());
+    let progress = backup::restore(driver, db_name, &args.file, &options, &|progress| {
+        println!("Committed {} batches, {} statements", progress.batches, progress.statements);
+    })?;
+    println!("Restored {} things in {} batches.", progress.statements, progress.batches);
+    Ok(())
+}
+// end::db-backup[]
+// tag::db-generate[]
+fn print_counts<'a>(counts: impl IntoIterator<Item = (&'a str, usize)>) {
+    for (type_, count) in counts {
+        println!("{}: {}", type_, count);
     }
 }
-// end::test-db[]
-// tag::db-setup[]
-pub fn db_setup(driver: Connection, db_name: String, db_reset: bool) -> Result<bool, Box<dyn Error>> {
-    let databases = DatabaseManager::new(driver.to_owned());
-    println!("Setting up the database: {}", &db_name);
-    if databases.contains(&db_name)? {
-        if db_reset {
-            match replace_database(&driver, db_name.clone()) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("Error: {:#?}", e);
-                    std::process::exit(1);
-                }
+
+/// Writes a synthetic dataset to the file given by --output, without a server.
+fn db_generate_file(args: &GenerateArgs, path: &Path) -> Result<(), AppError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let counts = synthetic::write_typeql(&args.options(), &mut writer)?;
+    writer.flush()?;
+    print_counts(counts);
+    println!("Wrote a synthetic dataset with seed {} to {}.", args.seed, path.display());
+    Ok(())
+}
+
+fn db_generate(driver: &Connection, db_name: &str, args: GenerateArgs) -> Result<(), AppError> {
+    let options = LoadOptions { batch_size: args.batch_size, sessions: args.sessions, checkpoint: None };
+    println!("Generating a synthetic dataset with seed {} into {}", args.seed, db_name);
+    let progress = synthetic::load(driver, db_name, &args.options(), &options, &|progress| {
+        println!("Committed {} batches, {} statements", progress.batches, progress.statements);
+    })?;
+    println!("Loaded {} statements in {} batches.", progress.statements, progress.batches);
+    Ok(())
+}
+// end::db-generate[]
+// tag::migrate[]
+fn migrations(dir: Option<PathBuf>) -> Result<Migrations, AppError> {
+    let mut migrations = Migrations::iam();
+    if let Some(dir) = dir {
+        migrations.add_dir(&dir)?;
+    }
+    Ok(migrations)
+}
+
+fn migrate(driver: &Connection, db_name: &str, args: MigrateArgs) -> Result<(), AppError> {
+    let migrations = migrations(args.dir)?;
+    match args.command {
+        MigrateCommand::Up(args) => {
+            if !database::exists(driver, db_name)? {
+                println!("Creating a new database: {}", db_name);
+                database::create(driver, db_name)?;
             }
-        } else {
-            let mut answer = String::new();
-            print!("Found a pre-existing database. Do you want to replace it? (Y/N) ");
-            io::Write::flush(&mut io::stdout()).unwrap();
-            io::stdin().read_line(&mut answer).expect("Failed to read a line");
-            if answer.trim().to_lowercase() == "y" {
-                match replace_database(&driver, db_name.clone()) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        eprintln!("Error: {:#?}", e);
-                        std::process::exit(1);
-                    }
+            if let Some(version) = args.baseline {
+                for recorded in migration::baseline(driver, db_name, &migrations, version)? {
+                    println!("Recorded without running: {}", recorded);
                 }
-            } else {
-                println!("Reusing an existing database.");
+            }
+            let applied = migration::up(driver, db_name, &migrations, args.target.to)?;
+            for migration in &applied {
+                println!("Applied: {}", migration);
+            }
+            if applied.is_empty() {
+                println!("The database is up to date.");
             }
         }
-    } else {
-        // No such database found on the server
-        let _ = create_database(&driver, db_name.clone());
+        MigrateCommand::Status => {
+            let applied = migration::applied(driver, db_name)?;
+            for migration in migrations.iter() {
+                let done = applied.iter().any(|applied| applied.version == migration.version);
+                let status = if done { "applied" } else { "pending" };
+                println!("{:<8} {}", status, migration);
+            }
+            for unknown in applied.iter().filter(|applied| migrations.iter().all(|m| m.version != applied.version)) {
+                println!("{:<8} {:04} {}", "unknown", unknown.version, unknown.name);
+            }
+        }
+        MigrateCommand::Plan(target) => {
+            let plan = migrations.plan(&migration::applied(driver, db_name)?, target.to)?;
+            for migration in &plan {
+                println!("Pending: {}", migration);
+            }
+            if plan.is_empty() {
+                println!("The database is up to date.");
+            }
+        }
+    }
+    Ok(())
+}
+// end::migrate[]
+// tag::demo[]
+fn demo(driver: Connection, db_name: String, policy: Bootstrap) -> Result<(), AppError> {
+    db_setup(&driver, &db_name, policy)?;
+    queries(&TypeDbStore::new(driver, db_name))
+}
+// end::demo[]
+// tag::run[]
+/// Runs a command that only needs the store, serving it over HTTP for `serve`.
+fn run_store_command(command: Command, shared: SharedStore) -> Result<(), AppError> {
+    let store = shared.as_ref();
+    match command {
+        Command::Users(UsersCommand::List) => fetch_all_users(store).map(drop),
+        Command::Users(UsersCommand::Add(args)) => insert_new_user(store, &args.name, &args.email).map(drop),
+        Command::Users(UsersCommand::Groups(args)) => get_groups(store, &args.subject, args.infer).map(drop),
+        Command::Files(FilesCommand::ForUser(args)) if args.explain => {
+            explain_files_by_user(store, &args.name).map(drop)
+        }
+        Command::Files(FilesCommand::ForUser(args)) => get_files_by_user(store, &args.name, args.infer).map(drop),
+        Command::Files(FilesCommand::Move(args)) => match update_filepath(store, &args.old_path, &args.new_path)? {
+            0 => Err(AppError::NotFound(format!("file with path {}", args.old_path))),
+            _ => Ok(()),
+        },
+        Command::Files(FilesCommand::Delete(args)) => delete_file(store, &args.path),
+        Command::Access(AccessCommand::Check(args)) => {
+            let request = AccessRequest { subject: args.subject, action: args.action, object: args.object };
+            decide(store, &request, args.infer).map(drop)
+        }
+        Command::Demo => queries(store),
+        Command::Serve(args) => server::serve(shared, args.listen),
+        Command::Db(_) | Command::Migrate(_) => Err(AppError::Config(ConfigError::Invalid {
+            key: "backend",
+            reason: "the db and migrate commands need the 'typedb' backend".to_owned(),
+        })),
     }
-    let data_session = Session::new(databases.get(db_name.clone())?, SessionType::Data)?;
-    match db_check(&data_session) {
-        Ok(_) => return Ok(true),
-        Err(x) => return Err(x),
+}
+
+fn run(command: Command, config: &Config) -> Result<(), AppError> {
+    // These run without a server.
+    match &command {
+        Command::Db(DbCommand::Import(args)) if args.dry_run => return db_import_dry_run(args),
+        Command::Db(DbCommand::Generate(args @ GenerateArgs { output: Some(path), .. })) => {
+            return db_generate_file(args, path)
+        }
+        _ => (),
+    }
+    match config.backend {
+        Backend::Memory => run_store_command(command, Arc::new(MemoryStore::iam()?)),
+        Backend::TypeDb => {
+            let driver = connection::connect(config)?;
+            let db_name = config.db_name.clone();
+            match command {
+                Command::Db(DbCommand::Setup(args)) => {
+                    let policy = if args.reset { Bootstrap::Replace } else { config.bootstrap };
+                    db_setup(&driver, &db_name, policy)
+                }
+                Command::Db(DbCommand::Load(args)) => db_load(&driver, &db_name, args),
+                Command::Db(DbCommand::Import(args)) => db_import(&driver, &db_name, args),
+                Command::Db(DbCommand::Export(args)) => db_export(&driver, &db_name, args),
+                Command::Db(DbCommand::Restore(args)) => db_restore(&driver, &db_name, args),
+                Command::Db(DbCommand::Generate(args)) => db_generate(&driver, &db_name, args),
+                Command::Migrate(args) => migrate(&driver, &db_name, args),
+                Command::Demo => demo(driver, db_name, config.bootstrap),
+                command => run_store_command(command, Arc::new(TypeDbStore::new(driver, db_name))),
+            }
+        }
     }
 }
-// end::db-setup[]
+// end::run[]
 // tag::main[]
-fn main() -> Result<(), Box<dyn Error>> {
+fn load_config(cli: &Cli) -> Result<Config, AppError> {
+    let (config_file, required) = cli.config.config_file();
+    Ok(ConfigLayer::defaults()
+        .merge(ConfigLayer::from_file(&config_file, required)?)
+        .merge(ConfigLayer::from_env(ENV_PREFIX, |key| std::env::var(key).ok()))
+        .merge(cli.config.layer())
+        .validate()?)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
     println!("Sample App");
-    let driver = connect_to_TypeDB(&TYPEDB_EDITION, SERVER_ADDR)?;
-    match db_setup(driver.clone(), DB_NAME.to_owned(), false) {
-        Ok(_) => match queries(driver, DB_NAME.to_owned()) {
-            Ok(_) => {
-                return Ok(());
-            }
-            Err(x) => return Err(x),
-        },
-        Err(_) => return Err(Box::new(TypeDBError::Other("DB setup failed.".to_string()))),
-    };
+    match load_config(&cli).and_then(|config| run(cli.command.unwrap_or(Command::Demo), &config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
 }
 // end::main[]
-// tag::string[]
-fn unwrap_string(concept: Concept) -> String {
-    match concept {
-        Concept::Attribute(Attribute { value: Value::String(value), .. }) => value,
-        _ => unreachable!(),
+// end::code[]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_scenario_runs_against_memory_store() {
+        let store = MemoryStore::iam().unwrap();
+        queries(&store).unwrap();
+        assert_eq!(store.users().unwrap().len(), 4);
+        assert!(store.files_by_user("Kevin Morrison", true).unwrap().iter().all(|file| file.path != "lzfkn2.java"));
+    }
+
+    #[test]
+    fn database_commands_need_the_typedb_backend() {
+        let cli = Cli::parse_from(["sample-app", "--backend", "memory", "migrate", "status"]);
+        let error = run_store_command(cli.command.unwrap(), Arc::new(MemoryStore::iam().unwrap())).unwrap_err();
+        assert!(matches!(error, AppError::Config(ConfigError::Invalid { key: "backend", .. })), "{:?}", error);
+        assert_eq!(error.exit_code(), 78);
     }
 }
-// end::string[]
-// end::code[]
@@ -0,0 +1,369 @@
+// tag::server[]
+//! The IAM operations over HTTP, for services that call them rather than linking the sample app:
+//!
+//! * `GET /health`: the process is up.
+//! * `GET /ready`: the store can serve requests, e.g. the database exists.
+//! * `GET /users` and `POST /users` with `{"full-name": ..., "email": ...}`.
+//! * `GET /users/{full name}/files?infer=true`: the files the user may view, as `{"path": ..., "groups": [...]}`
+//!   with the groups the user inherits access from. With `explain=true`, which implies `infer=true` and rejects
+//!   `infer=false`, the files come with the permissions that grant them and the rules those were inferred by.
+//! * `GET /users/{identifier or full name}/groups?infer=true`: the groups of a user, or of a group.
+//! * `PATCH /files/{path}` with `{"path": ...}` to rename a file, and `DELETE /files/{path}`.
+//! * `POST /decisions` with `{"subject": ..., "action": ..., "object": ..., "infer": true}`: whether the subject may
+//!   perform the action on the object, with the permissions that grant it.
+//!
+//! Each request calls the [`IamStore`] operation behind the matching command, without its console output, on the
+//! blocking thread pool, as the stores use the synchronous driver. Errors are answered as `{"error": ...}` with a
+//! status matching the exit code of the command.
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, patch, post},
+    Json, Router,
+};
+use iam_core::{
+    decision::{AccessRequest, Decision},
+    explanation::Fact,
+    models::Person,
+    store::{IamStore, ViewableFile},
+    AppError,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub type SharedStore = Arc<dyn IamStore + Send + Sync>;
+
+#[derive(Debug)]
+pub enum ApiError {
+    App(AppError),
+    BadRequest(String),
+    /// The operation panicked or was cancelled.
+    Internal(String),
+}
+
+impl From<AppError> for ApiError {
+    fn from(error: AppError) -> Self {
+        ApiError::App(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::App(error) => {
+                let status = match error {
+                    AppError::NotFound(_) | AppError::DatabaseNotFound { .. } => StatusCode::NOT_FOUND,
+                    AppError::Ambiguous(_) | AppError::Conflict(_) => StatusCode::CONFLICT,
+                    AppError::Query(_) | AppError::Config(_) => StatusCode::BAD_REQUEST,
+                    AppError::Connection(_) => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                (status, error.to_string())
+            }
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// Runs an operation on the store on the blocking thread pool.
+async fn blocking<T: Send + 'static>(
+    store: SharedStore,
+    operation: impl FnOnce(&dyn IamStore) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, ApiError> {
+    let result = tokio::task::spawn_blocking(move || operation(store.as_ref())).await;
+    Ok(result.map_err(|error| ApiError::Internal(error.to_string()))??)
+}
+
+fn person_json(person: &Person) -> Value {
+    json!({ "full-name": person.full_name, "email": person.email, "credential": person.credential })
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn ready(Extension(store): Extension<SharedStore>) -> Result<Json<Value>, Response> {
+    match blocking(store, |store| store.ready()).await {
+        Ok(()) => Ok(Json(json!({ "status": "ready" }))),
+        Err(ApiError::App(error)) => {
+            Err((StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": error.to_string() }))).into_response())
+        }
+        Err(error) => Err(error.into_response()),
+    }
+}
+
+async fn list_users(Extension(store): Extension<SharedStore>) -> Result<Json<Value>, ApiError> {
+    let users = blocking(store, |store| store.users()).await?;
+    Ok(Json(users.iter().map(person_json).collect()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct NewUser {
+    full_name: String,
+    email: String,
+}
+
+async fn add_user(
+    Extension(store): Extension<SharedStore>,
+    Json(user): Json<NewUser>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let users = blocking(store, move |store| store.insert_user(&user.full_name, &user.email)).await?;
+    Ok((StatusCode::CREATED, Json(users.iter().map(person_json).collect())))
+}
+
+/// The value of a flag in the decoded query, e.g. `infer`: `true` or `false`, `true` when it is given without a
+/// value, and `false` when it is left out.
+fn flag(query: &HashMap<String, String>, name: &str) -> Result<bool, ApiError> {
+    match query.get(name).map(String::as_str) {
+        None | Some("false") => Ok(false),
+        Some("true" | "") => Ok(true),
+        Some(value) => Err(ApiError::BadRequest(format!("{} must be true or false, not {}", name, value))),
+    }
+}
+
+fn fact_json(fact: &Fact) -> Value {
+    let players = fact.players.iter().map(|(role, player)| json!({ "role": role, "player": player }));
+    let explanations = fact.explanations.iter().map(|explanation| {
+        json!({ "rule": explanation.rule, "condition": explanation.condition.iter().map(fact_json).collect::<Vec<_>>() })
+    });
+    json!({
+        "relation": fact.type_,
+        "players": players.collect::<Vec<_>>(),
+        "inferred": fact.inferred,
+        "explanations": explanations.collect::<Vec<_>>(),
+    })
+}
+
+fn file_json(file: &ViewableFile) -> Value {
+    json!({ "path": file.path, "groups": file.groups })
+}
+
+async fn files_by_user(
+    Extension(store): Extension<SharedStore>,
+    Path(name): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<Value>, ApiError> {
+    let inference = flag(&query, "infer")?;
+    if flag(&query, "explain")? {
+        if query.contains_key("infer") && !inference {
+            return Err(ApiError::BadRequest("explain=true requires inference, not infer=false".to_owned()));
+        }
+        let files = blocking(store, move |store| store.explain_files_by_user(&name)).await?;
+        let files = files.iter().map(|file| {
+            json!({ "path": file.path, "permissions": file.permissions.iter().map(fact_json).collect::<Vec<_>>() })
+        });
+        return Ok(Json(files.collect()));
+    }
+    let files = blocking(store, move |store| store.files_by_user(&name, inference)).await?;
+    Ok(Json(files.iter().map(file_json).collect()))
+}
+
+async fn groups(
+    Extension(store): Extension<SharedStore>,
+    Path(subject): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let inference = flag(&query, "infer")?;
+    Ok(Json(blocking(store, move |store| store.groups(&subject, inference)).await?))
+}
+
+/// The file path captured by `/files/*path`, which starts with the slash before it.
+fn file_path(Path(path): Path<String>) -> String {
+    path.strip_prefix('/').map(str::to_owned).unwrap_or(path)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rename {
+    path: String,
+}
+
+async fn rename_file(
+    Extension(store): Extension<SharedStore>,
+    path: Path<String>,
+    Json(rename): Json<Rename>,
+) -> Result<Json<Value>, ApiError> {
+    let path = file_path(path);
+    let updated = blocking(store, move |store| match store.update_filepath(&path, &rename.path)? {
+        0 => Err(AppError::NotFound(format!("file with path {}", path))),
+        updated => Ok(updated),
+    })
+    .await?;
+    Ok(Json(json!({ "updated": updated })))
+}
+
+async fn delete_file(Extension(store): Extension<SharedStore>, path: Path<String>) -> Result<StatusCode, ApiError> {
+    let path = file_path(path);
+    blocking(store, move |store| store.delete_file(&path)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecisionRequest {
+    subject: String,
+    action: String,
+    object: String,
+    #[serde(default)]
+    infer: bool,
+}
+
+fn decision_json(decision: &Decision) -> Value {
+    let grants = decision.grants.iter().map(|grant| {
+        json!({
+            "permission": grant.permission,
+            "access": grant.access,
+            "inferred": grant.inferred,
+            "group": grant.group,
+            "valid": grant.is_valid(),
+            "review-date": grant.review_date.map(|date| date.format("%FT%T%.3f").to_string()),
+        })
+    });
+    json!({ "allowed": decision.allowed(), "grants": grants.collect::<Vec<_>>() })
+}
+
+async fn decide(
+    Extension(store): Extension<SharedStore>,
+    Json(request): Json<DecisionRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let DecisionRequest { subject, action, object, infer } = request;
+    let request = AccessRequest { subject, action, object };
+    let decision = blocking(store, move |store| store.decide(&request, infer)).await?;
+    Ok(Json(decision_json(&decision)))
+}
+
+pub fn router(store: SharedStore) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/users", get(list_users).post(add_user))
+        .route("/users/:name/files", get(files_by_user))
+        .route("/users/:name/groups", get(groups))
+        .route("/files/*path", patch(rename_file).delete(delete_file))
+        .route("/decisions", post(decide))
+        .layer(Extension(store))
+}
+
+/// Serves the API on `address` until the process is stopped.
+pub fn serve(store: SharedStore, address: SocketAddr) -> Result<(), AppError> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let server = axum::Server::try_bind(&address).map_err(std::io::Error::other)?;
+        println!("Listening on http://{}", address);
+        server.serve(router(store).into_make_service()).await.map_err(std::io::Error::other)?;
+        Ok(())
+    })
+}
+// end::server[]
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use iam_core::{query::QueryError, store::MemoryStore};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn call(router: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = router.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn flags_default_to_false() {
+        let query = |pairs: &[(&str, &str)]| {
+            pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>()
+        };
+        assert!(!flag(&query(&[]), "infer").unwrap());
+        assert!(flag(&query(&[("infer", "true")]), "infer").unwrap());
+        assert!(flag(&query(&[("x", "1"), ("infer", "")]), "infer").unwrap());
+        assert!(!flag(&query(&[("infer", "false"), ("inference", "1")]), "infer").unwrap());
+        assert!(matches!(flag(&query(&[("infer", "yes")]), "infer"), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn invalid_queries_are_bad_requests() {
+        let error = ApiError::from(AppError::Query(QueryError::Unquotable(r#"'"\"#.to_owned())));
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_the_operations_of_a_memory_store() {
+        let router = router(Arc::new(MemoryStore::iam().unwrap()));
+        assert_eq!(call(&router, "GET", "/health", None).await.0, StatusCode::OK);
+        assert_eq!(call(&router, "GET", "/ready", None).await, (StatusCode::OK, json!({ "status": "ready" })));
+
+        let (status, users) = call(&router, "GET", "/users", None).await;
+        assert_eq!((status, users.as_array().unwrap().len()), (StatusCode::OK, 3));
+        let jack = json!({ "full-name": "Jack Keeper", "email": "jk@typedb.com" });
+        let (status, added) = call(&router, "POST", "/users", Some(jack)).await;
+        assert_eq!((status, &added[0]["email"]), (StatusCode::CREATED, &json!("jk@typedb.com")));
+        assert_eq!(call(&router, "POST", "/users", Some(json!({ "name": "x" }))).await.0.as_u16() / 100, 4);
+
+        let files = "/users/Kevin%20Morrison/files";
+        assert_eq!(call(&router, "GET", files, None).await, (StatusCode::OK, json!([])));
+        let (status, paths) = call(&router, "GET", &format!("{}?infer=true", files), None).await;
+        assert_eq!((status, paths.as_array().unwrap().len()), (StatusCode::OK, 10));
+        assert_eq!(paths[0], json!({ "path": "LICENSE", "groups": [] }));
+        assert_eq!(call(&router, "GET", &format!("{}?infer=%74rue", files), None).await, (status, paths));
+        assert_eq!(call(&router, "GET", &format!("{}?infer=maybe", files), None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call(&router, "GET", "/users/Nobody/files", None).await.0, StatusCode::NOT_FOUND);
+        let groups = "/users/kevin.morrison@typedb.com/groups?infer=true";
+        assert_eq!(call(&router, "GET", groups, None).await, (StatusCode::OK, json!([])));
+        assert_eq!(call(&router, "GET", "/users/Nobody/groups", None).await.0, StatusCode::NOT_FOUND);
+        let (status, explained) = call(&router, "GET", &format!("{}?explain=true", files), None).await;
+        assert_eq!((status, &explained[0]["path"]), (StatusCode::OK, &json!("LICENSE")));
+        let permission = &explained[0]["permissions"][0];
+        assert_eq!((&permission["relation"], &permission["inferred"]), (&json!("permission"), &json!(true)));
+        let explanation = &permission["explanations"][0];
+        assert_eq!(explanation["rule"], json!("add-view-permission"));
+        assert_eq!(explanation["condition"][0]["players"][1], json!({ "role": "action", "player": "modify_file" }));
+        let uninferred = format!("{}?explain=true&infer=false", files);
+        assert_eq!(call(&router, "GET", &uninferred, None).await.0, StatusCode::BAD_REQUEST);
+        let inferred = format!("{}?explain=true&infer=true", files);
+        assert_eq!(call(&router, "GET", &inferred, None).await, (StatusCode::OK, explained));
+
+        let rename = Some(json!({ "path": "lzfkn2.java" }));
+        assert_eq!(
+            call(&router, "PATCH", "/files/lzfkn.java", rename.clone()).await,
+            (StatusCode::OK, json!({ "updated": 1 }))
+        );
+        assert_eq!(call(&router, "PATCH", "/files/lzfkn.java", rename).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call(&router, "DELETE", "/files/lzfkn2.java", None).await.0, StatusCode::NO_CONTENT);
+        let (status, error) = call(&router, "DELETE", "/files/lzfkn2.java", None).await;
+        assert_eq!(
+            (status, error),
+            (StatusCode::NOT_FOUND, json!({ "error": "Not found: file with path lzfkn2.java" }))
+        );
+    }
+
+    #[tokio::test]
+    async fn decides_access_requests() {
+        let router = router(Arc::new(MemoryStore::iam().unwrap()));
+        let request = json!({ "subject": "Kevin Morrison", "action": "view_file", "object": "LICENSE" });
+        assert_eq!(
+            call(&router, "POST", "/decisions", Some(request.clone())).await,
+            (StatusCode::OK, json!({ "allowed": false, "grants": [] }))
+        );
+        let mut inferred = request.clone();
+        inferred["infer"] = json!(true);
+        let (status, decision) = call(&router, "POST", "/decisions", Some(inferred)).await;
+        assert_eq!((status, &decision["allowed"]), (StatusCode::OK, &json!(true)));
+        assert_eq!((&decision["grants"][0]["inferred"], &decision["grants"][0]["valid"]), (&json!(true), &json!(true)));
+        assert_eq!(decision["grants"][0]["group"], Value::Null);
+
+        let mut unknown = request;
+        unknown["object"] = json!("missing.txt");
+        assert_eq!(call(&router, "POST", "/decisions", Some(unknown)).await.0, StatusCode::NOT_FOUND);
+    }
+}
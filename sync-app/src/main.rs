@@ -1,35 +1,47 @@
-use typedb_driver::{
-    concept::{Attribute, Concept, Value},
-    Connection, DatabaseManager, Error, Options, Promise, Session, SessionType, TransactionType
+use clap::Parser;
+use iam_core::{
+    config::{Config, ConfigLayer},
+    connection,
+    database::{self, Seed},
+    AppError,
 };
-use serde_json::json;
 
-fn main() {
-    let connection = Connection::new_core("localhost:1729")?;
-    let databases = DatabaseManager::new(connection);
+const DB_NAME: &str = "access-management-db";
 
-    databases.create("access-management-db").await?;
+const SEED: Seed<'static> = Seed {
+    schema_file: "schema",
+    schema: "define subject sub entity, owns name; name sub attribute, value string;",
+    data_file: "data",
+    data: "insert $s isa subject, has name 'Bob';",
+    definitions: &[],
+};
+
+/// Sets up the database without prompting and fetches the names of all subjects.
+#[derive(Debug, Parser)]
+#[command(name = "sync-app")]
+struct Cli {
+    /// What to do when the database exists: reuse, replace, fail-if-exists or ensure-schema
+    #[arg(long, value_name = "POLICY")]
+    bootstrap: Option<String>,
+}
+
+fn config(cli: Cli) -> Result<Config, AppError> {
+    let layer = ConfigLayer { db_name: Some(DB_NAME.to_owned()), ..ConfigLayer::default() };
+    let env = ConfigLayer::from_env("SYNC_APP_", |key| std::env::var(key).ok());
+    let flags = ConfigLayer { bootstrap: cli.bootstrap, ..ConfigLayer::default() };
+    Ok(ConfigLayer::defaults().merge(layer).merge(env).merge(flags).validate()?)
+}
+
+fn main() -> Result<(), AppError> {
+    let config = config(Cli::parse())?;
+    let driver = connection::connect(&config)?;
+    let db_name = &config.db_name;
 
-    let session = Session::new(databases.get("access-management-db").await?, SessionType::Schema).await?;
-    let tx = session.transaction(TransactionType::Write).await?;
-    tx.query().define("define subject sub entity;").await?;
-    tx.query().define("define subject owns name; name sub attribute, value string;").await?;
-    tx.commit().await?;
-    drop(session);
+    let outcome = database::bootstrap(&driver, db_name, config.bootstrap, &SEED)?;
+    println!("Database {}: {:?}", db_name, outcome);
 
-    let session = Session::new(databases.get("access-management-db").await?, SessionType::Data).await?;
-    let tx = session.transaction(TransactionType::Write).await?;
-    tx.query().insert("insert $s isa subject, has name 'Bob';").await?;
-    tx.commit().await?;
-    
-    let tx = session.transaction(TransactionType::Read).await?;
-    let mut stream = tx.query().fetch("match $s isa subject; fetch $s: name;")?;
-    for item in stream {
-        let answer = item.unwrap();
-        println!(
-            "Name: {}",
-            serde_json::to_string_pretty(&answer).unwrap()            
-        );
+    for answer in database::fetch(&driver, db_name, "match $s isa subject; fetch $s: name;")? {
+        println!("Name: {}", answer);
     }
-    drop(session);
+    Ok(())
 }
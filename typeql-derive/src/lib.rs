@@ -0,0 +1,164 @@
+//! `#[derive(TypeQLEntity)]`: maps a struct with named fields to a TypeQL entity type.
+//!
+//! ```ignore
+//! #[derive(TypeQLEntity)]
+//! #[typeql(type = "file")]
+//! pub struct File {
+//!     pub path: String,
+//!     #[typeql(attribute = "size-kb")]
+//!     pub size_kb: Option<i64>,
+//!     pub object_type: Option<String>,
+//! }
+//! ```
+//!
+//! Every field is an attribute of the entity, named after the field with underscores replaced by hyphens unless
+//! `#[typeql(attribute = "...")]` says otherwise. A field of type `T` is an attribute owned exactly once,
+//! `Option<T>` at most once and `Vec<T>` any number of times. The derive implements `Model` (decoding from
+//! concept maps and fetch answers) and `TypeQLEntity` (insert, match and fetch patterns) from the models module,
+//! which is `crate::models` unless `#[typeql(crate = "path::to::models")]` is given.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, LitStr, PathArguments, Type,
+};
+
+#[proc_macro_derive(TypeQLEntity, attributes(typeql))]
+pub fn derive_typeql_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+enum Cardinality {
+    One,
+    Optional,
+    Many,
+}
+
+struct Field {
+    ident: syn::Ident,
+    attribute: String,
+    cardinality: Cardinality,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut type_ = None;
+    let mut models: syn::Path = syn::parse_quote!(crate::models);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("typeql")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                type_ = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("crate") {
+                models = meta.value()?.parse::<LitStr>()?.parse()?;
+            } else {
+                return Err(meta.error("expected `type` or `crate`"));
+            }
+            Ok(())
+        })?;
+    }
+    let type_ = type_.ok_or_else(|| syn::Error::new(input.span(), "missing #[typeql(type = \"...\")]"))?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new(input.span(), "TypeQLEntity needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new(input.span(), "TypeQLEntity can only be derived for structs")),
+    };
+    let fields = fields.iter().map(field).collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let attributes = fields.iter().map(|field| &field.attribute);
+    let decoders = fields.iter().map(|Field { ident, attribute, cardinality }| {
+        let decode = match cardinality {
+            Cardinality::One => quote!(one),
+            Cardinality::Optional => quote!(optional),
+            Cardinality::Many => quote!(all),
+        };
+        quote!(#ident: #models::#decode(answer, var, #attribute)?)
+    });
+    let has_clauses = fields.iter().map(|Field { ident, attribute, cardinality }| match cardinality {
+        Cardinality::One => quote!(pattern.push_str(&#models::has_clause(#attribute, &self.#ident)?);),
+        Cardinality::Optional => quote! {
+            if let Some(value) = &self.#ident {
+                pattern.push_str(&#models::has_clause(#attribute, value)?);
+            }
+        },
+        Cardinality::Many => quote! {
+            for value in &self.#ident {
+                pattern.push_str(&#models::has_clause(#attribute, value)?);
+            }
+        },
+    });
+    let required = fields.iter().filter(|field| matches!(field.cardinality, Cardinality::One)).map(|field| {
+        let attribute = &field.attribute;
+        quote!(pattern.push_str(&format!(", has {} ${}-{}", #attribute, var, #attribute));)
+    });
+
+    Ok(quote! {
+        impl #impl_generics #models::Model for #name #ty_generics #where_clause {
+            const TYPES: &'static [&'static str] = &[#type_];
+
+            fn decode(
+                answer: &(impl #models::Answer + ?Sized),
+                var: &str,
+            ) -> ::std::result::Result<Self, #models::ModelError> {
+                #models::check_type(answer, var, Self::TYPES)?;
+                ::std::result::Result::Ok(Self { #(#decoders,)* })
+            }
+        }
+
+        impl #impl_generics #models::TypeQLEntity for #name #ty_generics #where_clause {
+            const TYPE: &'static str = #type_;
+            const ATTRIBUTES: &'static [&'static str] = &[#(#attributes),*];
+
+            fn insert_pattern(
+                &self,
+                var: &str,
+            ) -> ::std::result::Result<::std::string::String, #models::QueryError> {
+                let mut pattern = format!("${} isa {}", var, #type_);
+                #(#has_clauses)*
+                ::std::result::Result::Ok(pattern)
+            }
+
+            fn match_pattern(var: &str) -> ::std::string::String {
+                let mut pattern = format!("${} isa {}", var, #type_);
+                #(#required)*
+                pattern
+            }
+        }
+    })
+}
+
+fn field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("named field");
+    let mut attribute = ident.to_string().trim_start_matches("r#").replace('_', "-");
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("typeql")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("attribute") {
+                attribute = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `attribute`"))
+            }
+        })?;
+    }
+    let cardinality = match wrapper(&field.ty) {
+        Some("Option") => Cardinality::Optional,
+        Some("Vec") => Cardinality::Many,
+        _ => Cardinality::One,
+    };
+    Ok(Field { ident, attribute, cardinality })
+}
+
+/// The name of the generic wrapper of a type such as `Option<T>` or `Vec<T>`.
+fn wrapper(ty: &Type) -> Option<&'static str> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    if !matches!(arguments.args.first(), Some(GenericArgument::Type(_))) || arguments.args.len() != 1 {
+        return None;
+    }
+    ["Option", "Vec"].into_iter().find(|name| segment.ident == name)
+}